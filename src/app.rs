@@ -1,8 +1,21 @@
+use crate::composer::CommitComposer;
+use crate::config::Config;
 use crate::event::Event;
-use crate::git::{FileChange, FileStatus, GitRepository, GitWatcher};
+use crate::git::{
+    sort_by_mtime, ConflictFile, DiffCache, DiffHunk, DiffKey, DiffLayout, DiffLine, DiffSettings,
+    DiffSource, FileChange, FileDiff, FileStatus, GitRepository, GitRequest, GitWatcher, GitWorker,
+    Granularity, HeadInfo, LineKind, RefreshPlanner, Resolution, StashEntry, StashMode,
+    StatusUpdate, CLEAN_CONTEXT, CONTEXT_EXPAND_STEP, MAX_CONTEXT_LINES,
+};
+use crate::notification::Notifications;
+use crate::ui::highlight::DiffHighlights;
+use crate::ui::theme::{Background, ColorDepth, Theme};
+use crate::undo::{FileSnapshot, UndoBuffer, UndoEntry};
+use color_eyre::eyre::eyre;
 use color_eyre::Result;
+use std::collections::HashSet;
 use std::path::PathBuf;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
 /// Active panel in the UI
@@ -12,53 +25,284 @@ pub enum ActivePanel {
     DiffView,
 }
 
+/// A row of the file list: one side (staged or unstaged) of a changed file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileRow {
+    /// Index into `App::files`
+    pub file: usize,
+    pub source: DiffSource,
+}
+
+/// A line of the rendered file list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEntry {
+    /// Section header with the number of rows in it
+    Section(DiffSource, usize),
+    /// Index into `App::rows`
+    Row(usize),
+}
+
+/// Visual line selection inside one hunk of the diff view
+#[derive(Debug, Clone)]
+pub struct LineSelection {
+    pub path: String,
+    pub source: DiffSource,
+    pub hunk: usize,
+    /// Line index (within the hunk) where the selection started
+    pub anchor: usize,
+    /// Line index (within the hunk) of the moving end of the selection
+    pub cursor: usize,
+    /// Copies of the anchor/cursor lines, used to re-locate the selection
+    /// after the diff is recomputed
+    anchor_line: DiffLine,
+    cursor_line: DiffLine,
+}
+
+impl LineSelection {
+    /// Selected line indices within the hunk (inclusive range)
+    pub fn range(&self) -> std::ops::RangeInclusive<usize> {
+        self.anchor.min(self.cursor)..=self.anchor.max(self.cursor)
+    }
+
+    pub fn contains(&self, hunk: usize, line: usize) -> bool {
+        self.hunk == hunk && self.range().contains(&line)
+    }
+}
+
+/// A discard of working tree changes waiting for confirmation
+#[derive(Debug, Clone)]
+pub enum Discard {
+    /// All changes to a file; discarding a worktree rename also brings back
+    /// the old path
+    File {
+        path: String,
+        renamed_from: Option<String>,
+        untracked: bool,
+    },
+    Hunk {
+        path: String,
+        hunk: DiffHunk,
+    },
+    /// Line indices within the hunk
+    Lines {
+        path: String,
+        hunk: DiffHunk,
+        lines: Vec<usize>,
+    },
+}
+
+impl Discard {
+    pub fn description(&self) -> String {
+        match self {
+            Discard::File {
+                path,
+                untracked: true,
+                ..
+            } => format!("untracked file {}", path),
+            Discard::File { path, .. } => format!("all changes to {}", path),
+            Discard::Hunk { path, .. } => format!("hunk in {}", path),
+            Discard::Lines { path, hunk, lines } => {
+                let changed = lines
+                    .iter()
+                    .filter_map(|&i| hunk.lines.get(i))
+                    .filter(|l| matches!(l.kind, LineKind::Addition | LineKind::Deletion))
+                    .count();
+                format!(
+                    "{} changed line{} in {}",
+                    changed,
+                    if changed == 1 { "" } else { "s" },
+                    path
+                )
+            }
+        }
+    }
+
+    /// Working tree files the discard rewrites
+    fn paths(&self) -> Vec<&str> {
+        match self {
+            Discard::File {
+                path, renamed_from, ..
+            } => std::iter::once(path.as_str())
+                .chain(renamed_from.as_deref())
+                .collect(),
+            Discard::Hunk { path, .. } | Discard::Lines { path, .. } => vec![path.as_str()],
+        }
+    }
+}
+
+/// Conflict resolution view of one conflicted file
+pub struct ConflictView {
+    pub file: ConflictFile,
+    /// Selected conflict region
+    pub selected: usize,
+    /// First visible row
+    pub scroll: usize,
+}
+
+impl ConflictView {
+    fn new(file: ConflictFile) -> Self {
+        let mut view = Self {
+            file,
+            selected: 0,
+            scroll: 0,
+        };
+        view.scroll_to_selected();
+        view
+    }
+
+    /// Show the selected region with some agreed-on lines above it
+    fn scroll_to_selected(&mut self) {
+        self.scroll = self
+            .file
+            .conflict_offset(self.selected)
+            .saturating_sub(CLEAN_CONTEXT);
+    }
+}
+
+/// Stash panel state
+#[derive(Debug, Clone, Default)]
+pub struct StashPanel {
+    /// Selected entry of `App::stashes`
+    pub selected: usize,
+    /// Scroll offset of the patch preview
+    pub scroll: usize,
+}
+
 /// Application state
 pub struct App {
     /// Is the application running
     running: bool,
-    /// Git repository wrapper
+    /// Git repository wrapper, used for user-initiated writes only
     repo: GitRepository,
+    /// Background thread doing all status and diff reads
+    worker: GitWorker,
     /// File watcher
     watcher: Option<GitWatcher>,
     /// Current file statuses
     pub files: Vec<FileChange>,
-    /// Selected file index
+    /// Waiting for the first status scan
+    pub loading: bool,
+    /// Diffs received from the worker, along with their syntax colors
+    diff_cache: DiffCache,
+    /// File list rows, staged section first
+    pub rows: Vec<FileRow>,
+    /// Selected row index
     pub selected_index: usize,
     /// Active panel
     pub active_panel: ActivePanel,
-    /// Current branch name
-    pub branch_name: String,
+    /// Current branch, upstream tracking and in-progress operation
+    pub head: HeadInfo,
     /// Last update time
     pub last_update: Instant,
+    /// Decides what watcher events re-read, and when to rescan everything
+    planner: RefreshPlanner,
     /// Recently changed files (for animation)
     pub recently_changed: Vec<(String, Instant)>,
     /// Scroll offset for diff view
     pub diff_scroll: usize,
+    /// Unified or side-by-side diff view
+    pub diff_layout: DiffLayout,
+    /// How finely paired changed lines are compared
+    pub granularity: Granularity,
+    /// Context, whitespace and algorithm options for diffs and line counts
+    pub diff_settings: DiffSettings,
+    /// Selected hunk in the diff view
+    pub selected_hunk: usize,
+    /// Active visual line selection in the diff view
+    pub line_selection: Option<LineSelection>,
+    /// Open commit message editor
+    pub composer: Option<CommitComposer>,
+    /// Open conflict resolution view
+    pub conflict: Option<ConflictView>,
+    /// Stash list, newest first
+    pub stashes: Vec<StashEntry>,
+    /// Open stash panel
+    pub stash_panel: Option<StashPanel>,
+    /// Patch lines of a stash, by stash id
+    pub stash_patch: Option<(String, Vec<String>)>,
+    /// Discard shown in the confirmation dialog
+    pub pending_discard: Option<Discard>,
+    /// Working tree content of recent discards
+    undo: UndoBuffer,
+    /// Toasts and message log
+    pub notifications: Notifications,
+    /// Is the message log panel open
+    pub show_log: bool,
+    /// Scroll offset for the message log panel (lines from the newest)
+    pub log_scroll: usize,
     /// File list area for mouse events (x, y, width, height)
     pub file_list_area: Option<(u16, u16, u16, u16)>,
     /// Diff view area for mouse events (x, y, width, height)
     pub diff_view_area: Option<(u16, u16, u16, u16)>,
+    /// Width of the file list, in percent of the content area
+    pub file_list_percent: u16,
+    /// How long a changed file stays highlighted
+    pub animation: Duration,
+    /// Colors of the interface
+    pub theme: Theme,
+    /// Terminal background, for themes set to `auto`
+    pub background: Background,
+    /// Colors the terminal can show, frames are converted to them
+    pub color_depth: ColorDepth,
 }
 
 impl App {
-    pub fn new(repo_path: PathBuf) -> Result<Self> {
-        let repo = GitRepository::new(repo_path)?;
-        let branch_name = repo.current_branch()?;
-        let files = repo.get_status()?;
+    /// Open the repository and queue the initial status scan; results arrive
+    /// on `sender` as events
+    pub fn new(
+        repo_path: PathBuf,
+        sender: mpsc::UnboundedSender<Event>,
+        diff_settings: DiffSettings,
+    ) -> Result<Self> {
+        let mut repo = GitRepository::new(repo_path)?;
+        repo.set_diff_settings(diff_settings);
+        let head = repo.head_info()?;
+        let worker = GitWorker::new(repo.path().to_path_buf(), sender)?;
+        worker.send(GitRequest::Settings(diff_settings))?;
+        worker.send(GitRequest::Status {
+            paths: Vec::new(),
+            full: true,
+        })?;
+        worker.send(GitRequest::Stashes)?;
 
         Ok(Self {
             running: true,
             repo,
+            worker,
             watcher: None,
-            files,
+            files: Vec::new(),
+            loading: true,
+            diff_cache: DiffCache::new(),
+            rows: Vec::new(),
             selected_index: 0,
             active_panel: ActivePanel::FileList,
-            branch_name,
+            head,
             last_update: Instant::now(),
+            planner: RefreshPlanner::new(),
             recently_changed: Vec::new(),
             diff_scroll: 0,
+            diff_layout: DiffLayout::default(),
+            granularity: Granularity::default(),
+            diff_settings,
+            selected_hunk: 0,
+            line_selection: None,
+            composer: None,
+            conflict: None,
+            stashes: Vec::new(),
+            stash_panel: None,
+            stash_patch: None,
+            pending_discard: None,
+            undo: UndoBuffer::new(),
+            notifications: Notifications::new(),
+            show_log: false,
+            log_scroll: 0,
             file_list_area: None,
             diff_view_area: None,
+            file_list_percent: 50,
+            animation: Duration::from_millis(800),
+            theme: Theme::default(),
+            background: Background::default(),
+            color_depth: ColorDepth::default().detect(),
         })
     }
 
@@ -73,25 +317,263 @@ impl App {
     pub fn tick(&mut self) {
         // Animation cleanup is handled by is_recently_changed() and get_change_progress()
         // We keep recently_changed records for stable sorting by modification time
+        if let Some(request) = self.planner.rescan() {
+            self.diff_cache.invalidate();
+            let result = self.worker.send(request);
+            self.report_error("refresh", result);
+        }
     }
 
-    pub fn start_watcher(&mut self, sender: mpsc::UnboundedSender<Event>) -> Result<()> {
-        let watcher = GitWatcher::new(self.repo.path().to_path_buf(), sender)?;
+    pub fn start_watcher(
+        &mut self,
+        sender: mpsc::UnboundedSender<Event>,
+        excludes: &[String],
+        debounce: Duration,
+    ) -> Result<()> {
+        let watcher = GitWatcher::new(self.repo.path().to_path_buf(), sender, excludes, debounce)?;
         self.watcher = Some(watcher);
         Ok(())
     }
 
+    /// Take over the layout, animation, theme and diff values of a
+    /// (re)loaded configuration
+    pub fn apply_config(&mut self, config: &Config) -> Result<()> {
+        self.file_list_percent = config.split;
+        self.animation = config.animation;
+        self.color_depth = config.color.detect();
+        let syntax = self.theme.syntax;
+        self.theme = config.theme(self.background)?;
+        if self.theme.syntax != syntax {
+            // Reload the diffs so they are highlighted again
+            self.worker
+                .send(GitRequest::SyntaxTheme(self.theme.syntax))?;
+            self.diff_cache.invalidate();
+        }
+        self.update_diff_settings(DiffSettings {
+            rename_threshold: config.rename_threshold,
+            ..self.diff_settings
+        })
+    }
+
+    /// Refresh both the file list and the HEAD information, after a change
+    /// made from within the app
     pub fn refresh_status(&mut self) -> Result<()> {
+        self.diff_cache.invalidate();
+        self.worker.send(GitRequest::Head)?;
         self.refresh_status_with_paths(Vec::new())
     }
 
+    /// A file watcher event: re-read whatever it may have changed
+    pub fn on_repo_change(&mut self, event: &Event) {
+        if matches!(
+            event,
+            Event::IndexChange | Event::HeadChange | Event::RefsChange | Event::OperationChange
+        ) {
+            // Diffs may change without the file's mtime changing
+            self.diff_cache.invalidate();
+        }
+        let files = &self.files;
+        let requests = self
+            .planner
+            .requests(event, |path| is_rename_path(files, path));
+        for request in requests {
+            let result = self.worker.send(request);
+            self.report_error("refresh", result);
+        }
+    }
+
+    pub fn on_stash_change(&mut self) {
+        self.notifications.info("Stash list changed");
+        let result = self.worker.send(GitRequest::Stashes);
+        self.report_error("list stashes", result);
+    }
+
+    /// New stash list from the worker
+    pub fn apply_stashes(&mut self, stashes: Vec<StashEntry>) {
+        self.stashes = stashes;
+        if let Some(panel) = self.stash_panel.as_mut() {
+            panel.selected = panel.selected.min(self.stashes.len().saturating_sub(1));
+        }
+        self.request_stash_patch();
+    }
+
+    pub fn apply_stash_patch(&mut self, id: String, lines: Vec<String>) {
+        if self.selected_stash().is_some_and(|s| s.id == id) {
+            self.stash_patch = Some((id, lines));
+        }
+    }
+
+    pub fn toggle_stash_panel(&mut self) {
+        if self.stash_panel.take().is_none() {
+            self.stash_panel = Some(StashPanel::default());
+            self.request_stash_patch();
+        }
+    }
+
+    pub fn selected_stash(&self) -> Option<&StashEntry> {
+        let panel = self.stash_panel.as_ref()?;
+        self.stashes.get(panel.selected)
+    }
+
+    pub fn select_stash(&mut self, down: bool) {
+        let count = self.stashes.len();
+        if let Some(panel) = self.stash_panel.as_mut() {
+            panel.selected = if down {
+                (panel.selected + 1).min(count.saturating_sub(1))
+            } else {
+                panel.selected.saturating_sub(1)
+            };
+            panel.scroll = 0;
+        }
+        self.request_stash_patch();
+    }
+
+    pub fn scroll_stash_patch(&mut self, down: bool, lines: usize) {
+        let total = self.stash_patch.as_ref().map_or(0, |(_, l)| l.len());
+        if let Some(panel) = self.stash_panel.as_mut() {
+            panel.scroll = if down {
+                (panel.scroll + lines).min(total.saturating_sub(1))
+            } else {
+                panel.scroll.saturating_sub(lines)
+            };
+        }
+    }
+
+    /// Ask the worker for the patch of the selected stash unless it is shown
+    fn request_stash_patch(&mut self) {
+        let Some(id) = self.selected_stash().map(|s| s.id.clone()) else {
+            self.stash_patch = None;
+            return;
+        };
+        if self
+            .stash_patch
+            .as_ref()
+            .is_some_and(|(shown, _)| *shown == id)
+        {
+            return;
+        }
+        let result = self.worker.send(GitRequest::StashPatch(id));
+        self.report_error("show stash", result);
+    }
+
+    /// Stash all changes, the staged ones, or those of the selected file
+    pub fn stash(&mut self, mode: StashMode) -> Result<()> {
+        let description = match &mode {
+            StashMode::All => "all changes".to_string(),
+            StashMode::Staged => "staged changes".to_string(),
+            StashMode::Paths(paths) => paths.join(", "),
+        };
+        self.repo.stash_save(&mode)?;
+        self.notifications
+            .success(format!("Stashed {}", description));
+        self.after_stash_change()
+    }
+
+    /// Stash every change to the selected file (both paths of a rename)
+    pub fn stash_selected_file(&mut self) -> Result<()> {
+        let Some((file, source)) = self.selected_row() else {
+            return Ok(());
+        };
+        let paths = std::iter::once(file.path.clone())
+            .chain(file.old_path(source).map(str::to_string))
+            .collect();
+        self.stash(StashMode::Paths(paths))
+    }
+
+    /// Apply the selected stash to the working tree, dropping it when `pop`
+    pub fn apply_stash(&mut self, pop: bool) -> Result<()> {
+        let Some(stash) = self.selected_stash().cloned() else {
+            return Ok(());
+        };
+        self.repo.stash_apply(&stash.id, pop)?;
+        let action = if pop { "Popped" } else { "Applied" };
+        self.notifications
+            .success(format!("{} {}", action, stash.name()));
+        self.after_stash_change()
+    }
+
+    pub fn drop_stash(&mut self) -> Result<()> {
+        let Some(stash) = self.selected_stash().cloned() else {
+            return Ok(());
+        };
+        self.repo.stash_drop(&stash.id)?;
+        // The commit stays around for `git stash apply <id>` until gc
+        self.notifications
+            .info(format!("Dropped {} ({})", stash.name(), &stash.id[..7]));
+        self.after_stash_change()
+    }
+
+    fn after_stash_change(&mut self) -> Result<()> {
+        self.worker.send(GitRequest::Stashes)?;
+        self.refresh_status()
+    }
+
+    /// New HEAD information from the worker: announce branch switches and
+    /// commits made elsewhere
+    pub fn apply_head(&mut self, head: HeadInfo) {
+        if head.branch != self.head.branch {
+            match &head.branch {
+                Some(branch) => self
+                    .notifications
+                    .info(format!("Switched to branch {}", branch)),
+                None => self.notifications.info(format!(
+                    "HEAD detached at {}",
+                    head.commit.as_deref().unwrap_or("?")
+                )),
+            }
+        } else if head.commit != self.head.commit {
+            if let (Some(branch), Some(commit)) = (&head.branch, &head.commit) {
+                self.notifications.success(format!(
+                    "New commit on {}: {} {}",
+                    branch,
+                    commit,
+                    head.summary.as_deref().unwrap_or("")
+                ));
+            }
+        }
+
+        self.head = head;
+    }
+
+    /// Ask the worker to refresh the file list, see `RefreshPlanner::status`
     pub fn refresh_status_with_paths(&mut self, changed_paths: Vec<String>) -> Result<()> {
-        // Get current selected file path to maintain selection after sort
-        let selected_path = self.files.get(self.selected_index).map(|f| f.path.clone());
+        let files = &self.files;
+        let request = self
+            .planner
+            .status(changed_paths, |path| is_rename_path(files, path));
+        self.worker.send(request)
+    }
+
+    /// Merge a status result from the worker into the file list, animating
+    /// the changed paths
+    pub fn apply_status(&mut self, update: StatusUpdate) {
+        // Get current selected row to maintain selection after sort
+        let selected = self
+            .selected_row()
+            .map(|(file, source)| (file.path.clone(), source));
 
-        // get_status() now returns files sorted by modification time (newest first)
-        self.files = self.repo.get_status()?;
-        self.branch_name = self.repo.current_branch()?;
+        let changed_paths = match update {
+            StatusUpdate::Full {
+                files,
+                changed_paths,
+            } => {
+                // get_status() returns files sorted by modification time (newest first)
+                self.files = files;
+                changed_paths
+            }
+            StatusUpdate::Paths(updates) => {
+                let mut changed_paths = Vec::with_capacity(updates.len());
+                for (path, change) in updates {
+                    self.files.retain(|f| f.path != path);
+                    self.files.extend(change);
+                    changed_paths.push(path);
+                }
+                sort_by_mtime(&mut self.files);
+                changed_paths
+            }
+        };
+        self.loading = false;
+        self.rows = build_rows(&self.files);
         self.last_update = Instant::now();
 
         let now = Instant::now();
@@ -109,41 +591,47 @@ impl App {
             }
         }
 
-        // Clean up animation records for files that no longer exist
-        let current_paths: std::collections::HashSet<&str> =
-            self.files.iter().map(|f| f.path.as_str()).collect();
+        // Clean up animation records and diffs for files that no longer exist
+        let current_paths: HashSet<&str> = self.files.iter().map(|f| f.path.as_str()).collect();
         self.recently_changed
             .retain(|(path, _)| current_paths.contains(path.as_str()));
+        self.diff_cache.retain_paths(&current_paths);
 
-        // Restore selection to the same file if possible
-        if let Some(path) = selected_path {
-            if let Some(idx) = self.files.iter().position(|f| f.path == path) {
+        // Restore selection to the same row if possible, falling back to the
+        // other side of the same file (e.g. right after staging it)
+        if let Some((path, source)) = selected {
+            let position = |wanted: Option<DiffSource>| {
+                self.rows.iter().position(|row| {
+                    self.files[row.file].path == path && wanted.is_none_or(|s| row.source == s)
+                })
+            };
+            if let Some(idx) = position(Some(source)).or_else(|| position(None)) {
                 self.selected_index = idx;
             }
         }
 
         // Adjust selection if needed
-        if self.selected_index >= self.files.len() && !self.files.is_empty() {
-            self.selected_index = self.files.len() - 1;
+        if self.selected_index >= self.rows.len() && !self.rows.is_empty() {
+            self.selected_index = self.rows.len() - 1;
         }
 
-        Ok(())
+        self.resync_line_selection();
     }
 
     pub fn select_next(&mut self) {
-        if !self.files.is_empty() {
-            self.selected_index = (self.selected_index + 1) % self.files.len();
-            self.diff_scroll = 0;
+        if !self.rows.is_empty() {
+            self.selected_index = (self.selected_index + 1) % self.rows.len();
+            self.reset_diff_position();
         }
     }
 
     pub fn select_previous(&mut self) {
-        if !self.files.is_empty() {
+        if !self.rows.is_empty() {
             self.selected_index = self
                 .selected_index
                 .checked_sub(1)
-                .unwrap_or(self.files.len() - 1);
-            self.diff_scroll = 0;
+                .unwrap_or(self.rows.len() - 1);
+            self.reset_diff_position();
         }
     }
 
@@ -155,19 +643,285 @@ impl App {
     }
 
     pub fn toggle_stage(&mut self) -> Result<()> {
-        if let Some(file) = self.files.get(self.selected_index) {
+        if let Some((file, source)) = self.selected_row() {
             let path = file.path.clone();
-            let is_staged = file.staged;
-            if is_staged {
-                self.repo.unstage_file(&path)?;
-            } else {
-                self.repo.stage_file(&path)?;
+            if file.status(source) == Some(FileStatus::Conflicted) {
+                return self.open_conflict_view(&path);
+            }
+            // A rename moves the old path along; a copy leaves it alone
+            let renamed_from = file
+                .old_path(source)
+                .filter(|_| file.status(source) == Some(FileStatus::Renamed))
+                .map(str::to_string);
+            let paths: Vec<&str> = std::iter::once(path.as_str())
+                .chain(renamed_from.as_deref())
+                .collect();
+            match source {
+                DiffSource::Staged => {
+                    for path in &paths {
+                        self.repo.unstage_file(path)?;
+                    }
+                    self.notifications.info(format!("Unstaged {}", path));
+                }
+                DiffSource::Unstaged => {
+                    for path in &paths {
+                        self.repo.stage_file(path)?;
+                    }
+                    self.notifications.info(format!("Staged {}", path));
+                }
             }
             self.refresh_status()?;
         }
         Ok(())
     }
 
+    /// Open the side-by-side resolution view of a conflicted file
+    pub fn open_conflict_view(&mut self, path: &str) -> Result<()> {
+        let file = self.repo.conflict_file(path)?;
+        self.conflict = Some(ConflictView::new(file));
+        Ok(())
+    }
+
+    pub fn close_conflict_view(&mut self) {
+        self.conflict = None;
+    }
+
+    pub fn select_next_conflict(&mut self) {
+        if let Some(view) = self.conflict.as_mut() {
+            if view.selected + 1 < view.file.conflict_count() {
+                view.selected += 1;
+                view.scroll_to_selected();
+            }
+        }
+    }
+
+    pub fn select_previous_conflict(&mut self) {
+        if let Some(view) = self.conflict.as_mut() {
+            if view.selected > 0 {
+                view.selected -= 1;
+                view.scroll_to_selected();
+            }
+        }
+    }
+
+    pub fn scroll_conflict(&mut self, down: bool) {
+        if let Some(view) = self.conflict.as_mut() {
+            if down {
+                let last = view.file.rows().len().saturating_sub(1);
+                view.scroll = (view.scroll + 1).min(last);
+            } else {
+                view.scroll = view.scroll.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Choose how the selected region is resolved (`None` to undo the
+    /// choice), moving on to the next region
+    pub fn resolve_selected_conflict(&mut self, resolution: Option<Resolution>) {
+        if let Some(view) = self.conflict.as_mut() {
+            let selected = view.selected;
+            if let Some(region) = view.file.region_mut(selected) {
+                region.resolution = resolution;
+            }
+            if resolution.is_some() {
+                self.select_next_conflict();
+            }
+        }
+    }
+
+    /// Write the merged file and stage it, once every region is resolved
+    pub fn mark_conflict_resolved(&mut self) -> Result<()> {
+        let Some(view) = self.conflict.as_ref() else {
+            return Ok(());
+        };
+        let Some(merged) = view.file.merged() else {
+            return Err(eyre!(
+                "{} of {} conflicts still unresolved",
+                view.file.unresolved_count(),
+                view.file.conflict_count()
+            ));
+        };
+
+        let path = view.file.path.clone();
+        self.repo.resolve_conflict(&path, &merged)?;
+        self.conflict = None;
+        self.notifications.success(format!("Resolved {}", path));
+        self.refresh_status()
+    }
+
+    /// Show a failed action as an error notification
+    pub fn report_error(&mut self, action: &str, result: Result<()>) {
+        if let Err(e) = result {
+            self.notifications
+                .error(format!("Failed to {}: {}", action, e));
+        }
+    }
+
+    pub fn toggle_log(&mut self) {
+        self.show_log = !self.show_log;
+        self.log_scroll = 0;
+    }
+
+    pub fn scroll_log_up(&mut self) {
+        let max = self.notifications.log().len().saturating_sub(1);
+        self.log_scroll = (self.log_scroll + 1).min(max);
+    }
+
+    pub fn scroll_log_down(&mut self) {
+        self.log_scroll = self.log_scroll.saturating_sub(1);
+    }
+
+    pub fn open_commit_composer(&mut self) {
+        if self.composer.is_none() {
+            self.composer = Some(CommitComposer::new());
+        }
+    }
+
+    pub fn close_commit_composer(&mut self) {
+        self.composer = None;
+    }
+
+    /// Toggle amending HEAD, pre-filling an empty message with HEAD's
+    pub fn toggle_commit_amend(&mut self) {
+        let head_message = self.repo.head_message().ok();
+        if let Some(composer) = self.composer.as_mut() {
+            composer.amend = !composer.amend;
+            if composer.amend && composer.is_empty() {
+                if let Some(message) = head_message {
+                    composer.set_message(&message);
+                }
+            }
+        }
+    }
+
+    /// Create the commit from the composer, keeping it open with the error on failure
+    pub fn submit_commit(&mut self) -> Result<()> {
+        let Some(composer) = self.composer.as_mut() else {
+            return Ok(());
+        };
+
+        let amend = composer.amend;
+        match self.repo.commit(&composer.message(), amend) {
+            Ok(oid) => {
+                self.composer = None;
+                let short_id = &oid.to_string()[..7];
+                if amend {
+                    self.notifications
+                        .success(format!("Amended HEAD ({})", short_id));
+                } else {
+                    self.notifications
+                        .success(format!("Committed {}", short_id));
+                }
+                self.refresh_status()
+            }
+            Err(e) => {
+                composer.error = Some(e.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Paths with staged changes, in list order
+    pub fn staged_files(&self) -> Vec<&str> {
+        self.rows
+            .iter()
+            .filter(|row| row.source == DiffSource::Staged)
+            .map(|row| self.files[row.file].path.as_str())
+            .collect()
+    }
+
+    /// Switch between the unified and side-by-side diff layouts, keeping the
+    /// selected hunk in view
+    pub fn toggle_diff_layout(&mut self) {
+        self.diff_layout = self.diff_layout.toggle();
+        if let Some(diff) = self.get_diff().cloned() {
+            self.diff_scroll = diff.hunk_offset(self.selected_hunk, self.diff_layout);
+            self.scroll_to_selection_cursor(&diff);
+        }
+    }
+
+    /// Cycle intra-line emphasis between line, word and character granularity
+    pub fn cycle_granularity(&mut self) {
+        self.granularity = self.granularity.next();
+        // The worker only computes emphasis for the active granularity
+        self.request_diff();
+    }
+
+    /// Show more context lines around changes
+    pub fn expand_context(&mut self) -> Result<()> {
+        let lines = (self.diff_settings.context_lines + 1).min(MAX_CONTEXT_LINES);
+        self.update_diff_settings(DiffSettings {
+            context_lines: lines,
+            ..self.diff_settings
+        })
+    }
+
+    /// Show fewer context lines around changes
+    pub fn collapse_context(&mut self) -> Result<()> {
+        let lines = self.diff_settings.context_lines.saturating_sub(1);
+        self.update_diff_settings(DiffSettings {
+            context_lines: lines,
+            ..self.diff_settings
+        })
+    }
+
+    pub fn toggle_ignore_whitespace(&mut self) -> Result<()> {
+        self.update_diff_settings(DiffSettings {
+            ignore_whitespace: !self.diff_settings.ignore_whitespace,
+            ..self.diff_settings
+        })
+    }
+
+    pub fn toggle_ignore_blank_lines(&mut self) -> Result<()> {
+        self.update_diff_settings(DiffSettings {
+            ignore_blank_lines: !self.diff_settings.ignore_blank_lines,
+            ..self.diff_settings
+        })
+    }
+
+    pub fn cycle_diff_algorithm(&mut self) -> Result<()> {
+        let algorithm = self.diff_settings.algorithm.next();
+        self.notifications
+            .info(format!("Diff algorithm: {}", algorithm.label()));
+        self.update_diff_settings(DiffSettings {
+            algorithm,
+            ..self.diff_settings
+        })
+    }
+
+    /// Use new diff options everywhere: for the hunks staged from this
+    /// thread, and for the worker's diffs and line counts
+    fn update_diff_settings(&mut self, settings: DiffSettings) -> Result<()> {
+        if settings == self.diff_settings {
+            return Ok(());
+        }
+        self.diff_settings = settings;
+        self.repo.set_diff_settings(settings);
+        self.worker.send(GitRequest::Settings(settings))?;
+        self.line_selection = None;
+        self.diff_cache.invalidate();
+        self.refresh_status_with_paths(Vec::new())
+    }
+
+    /// Hunks of diffs that hide changes, of diffs cut short for size, or of
+    /// diffs against another path would not stage what they show
+    fn ensure_exact_diff(&self, diff: &FileDiff) -> Result<()> {
+        if self.diff_settings.hides_changes() {
+            return Err(eyre!(
+                "stop ignoring whitespace and blank lines to stage or discard hunks"
+            ));
+        }
+        if diff.is_truncated() {
+            return Err(eyre!("load the full diff (x) to stage or discard hunks"));
+        }
+        if diff.old_path.is_some() {
+            return Err(eyre!(
+                "stage or unstage renamed and copied files as a whole"
+            ));
+        }
+        Ok(())
+    }
+
     pub fn scroll_diff_down(&mut self) {
         self.diff_scroll = self.diff_scroll.saturating_add(1);
     }
@@ -176,20 +930,449 @@ impl App {
         self.diff_scroll = self.diff_scroll.saturating_sub(1);
     }
 
-    pub fn selected_file(&self) -> Option<&FileChange> {
-        self.files.get(self.selected_index)
+    /// The selected file together with the side of it the row shows
+    pub fn selected_row(&self) -> Option<(&FileChange, DiffSource)> {
+        self.rows
+            .get(self.selected_index)
+            .map(|row| (&self.files[row.file], row.source))
+    }
+
+    /// Lines of the file list: a header per non-empty section followed by its rows
+    pub fn list_entries(&self) -> Vec<ListEntry> {
+        let mut entries = Vec::with_capacity(self.rows.len() + 2);
+        for source in [DiffSource::Staged, DiffSource::Unstaged] {
+            let count = self.rows.iter().filter(|r| r.source == source).count();
+            if count == 0 {
+                continue;
+            }
+            entries.push(ListEntry::Section(source, count));
+            entries.extend(
+                self.rows
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, r)| r.source == source)
+                    .map(|(i, _)| ListEntry::Row(i)),
+            );
+        }
+        entries
+    }
+
+    /// Latest diff of the selected row, possibly stale while a newer one loads
+    pub fn get_diff(&self) -> Option<&FileDiff> {
+        self.selected_row()
+            .and_then(|(f, source)| self.diff_cache.get(&f.path, source))
+    }
+
+    /// Syntax colors and emphasis of a diff from `get_diff`
+    pub fn diff_highlights(&self, diff: &FileDiff) -> Option<&DiffHighlights> {
+        self.diff_cache.highlights(&diff.path, diff.source)
+    }
+
+    /// Ask the worker for the selected row's diff unless it is cached and current
+    pub fn request_diff(&mut self) {
+        let Some((file, source)) = self.selected_row() else {
+            return;
+        };
+        let key = self.diff_cache.key(file, source, self.granularity);
+        if self.diff_cache.needs_request(&key) {
+            let result = self.worker.send(GitRequest::Diff(key));
+            self.report_error("load diff", result);
+        }
+    }
+
+    /// Store a diff from the worker, re-anchoring the hunk and line
+    /// selection when it belongs to the selected row
+    pub fn apply_diff(&mut self, key: DiffKey, diff: FileDiff, highlights: DiffHighlights) {
+        let is_selected = self
+            .selected_row()
+            .is_some_and(|(f, source)| f.path == key.path && source == key.source);
+        self.diff_cache.insert(key, diff, highlights);
+        if is_selected {
+            self.resync_line_selection();
+            self.clamp_selected_hunk();
+        }
+    }
+
+    /// Scroll back to the top of the diff and select its first hunk
+    pub fn reset_diff_position(&mut self) {
+        self.diff_scroll = 0;
+        self.selected_hunk = 0;
+        self.line_selection = None;
+    }
+
+    /// Start a visual line selection on the first changed line of the
+    /// selected hunk, or cancel the active one
+    pub fn toggle_line_selection(&mut self) {
+        if self.line_selection.take().is_some() {
+            return;
+        }
+        let Some(diff) = self.get_diff().cloned() else {
+            return;
+        };
+        let Some(hunk) = diff.hunks.get(self.selected_hunk) else {
+            return;
+        };
+        let Some(start) = hunk
+            .lines
+            .iter()
+            .position(|l| matches!(l.kind, LineKind::Addition | LineKind::Deletion))
+        else {
+            return;
+        };
+
+        self.line_selection = Some(LineSelection {
+            path: diff.path.clone(),
+            source: diff.source,
+            hunk: self.selected_hunk,
+            anchor: start,
+            cursor: start,
+            anchor_line: hunk.lines[start].clone(),
+            cursor_line: hunk.lines[start].clone(),
+        });
+        self.scroll_to_selection_cursor(&diff);
+    }
+
+    /// Move the moving end of the visual selection within its hunk
+    pub fn move_line_selection(&mut self, down: bool) {
+        let Some(diff) = self.get_diff().cloned() else {
+            return;
+        };
+        let Some(selection) = self.line_selection.as_mut() else {
+            return;
+        };
+        let Some(hunk) = diff.hunks.get(selection.hunk) else {
+            return;
+        };
+
+        let last = hunk.lines.len().saturating_sub(1);
+        selection.cursor = if down {
+            (selection.cursor + 1).min(last)
+        } else {
+            selection.cursor.saturating_sub(1)
+        };
+        selection.cursor_line = hunk.lines[selection.cursor].clone();
+        self.scroll_to_selection_cursor(&diff);
+    }
+
+    /// Keep the selection cursor inside the visible part of the diff view
+    fn scroll_to_selection_cursor(&mut self, diff: &FileDiff) {
+        let Some(selection) = &self.line_selection else {
+            return;
+        };
+        let Some(hunk) = diff.hunks.get(selection.hunk) else {
+            return;
+        };
+        let row = diff.hunk_offset(selection.hunk, self.diff_layout)
+            + 1
+            + hunk.line_row(selection.cursor, self.diff_layout);
+        let visible = self
+            .diff_view_area
+            .map(|(_, _, _, height)| height.saturating_sub(2) as usize)
+            .unwrap_or(1)
+            .max(1);
+
+        if row < self.diff_scroll {
+            self.diff_scroll = row;
+        } else if row >= self.diff_scroll + visible {
+            self.diff_scroll = row + 1 - visible;
+        }
+    }
+
+    /// Re-locate the visual selection after the diff was recomputed,
+    /// dropping it when its lines are gone or no longer share a hunk
+    fn resync_line_selection(&mut self) {
+        let Some(selection) = self.line_selection.take() else {
+            return;
+        };
+        let Some(diff) = self.get_diff().cloned() else {
+            return;
+        };
+        if diff.path != selection.path || diff.source != selection.source {
+            return;
+        }
+
+        if let (Some((anchor_hunk, anchor)), Some((cursor_hunk, cursor))) = (
+            diff.locate_line(&selection.anchor_line),
+            diff.locate_line(&selection.cursor_line),
+        ) {
+            if anchor_hunk == cursor_hunk {
+                self.selected_hunk = anchor_hunk;
+                self.line_selection = Some(LineSelection {
+                    hunk: anchor_hunk,
+                    anchor,
+                    cursor,
+                    ..selection
+                });
+            }
+        }
+    }
+
+    /// Stage (or unstage) the lines of the active visual selection
+    pub fn toggle_stage_lines(&mut self) -> Result<()> {
+        let Some(selection) = self.line_selection.clone() else {
+            return Ok(());
+        };
+        let Some(diff) = self.get_diff().cloned() else {
+            return Ok(());
+        };
+        self.ensure_exact_diff(&diff)?;
+        let Some(hunk) = diff.hunks.get(selection.hunk) else {
+            return Ok(());
+        };
+
+        let selected: Vec<usize> = selection.range().collect();
+        match diff.source {
+            DiffSource::Unstaged => self.repo.stage_lines(&diff.path, hunk, &selected)?,
+            DiffSource::Staged => self.repo.unstage_lines(&diff.path, hunk, &selected)?,
+        }
+        self.line_selection = None;
+        self.refresh_status()?;
+        self.clamp_selected_hunk();
+        Ok(())
+    }
+
+    pub fn select_next_hunk(&mut self) {
+        self.line_selection = None;
+        if let Some(diff) = self.get_diff().cloned() {
+            if self.selected_hunk + 1 < diff.hunks.len() {
+                self.selected_hunk += 1;
+            }
+            self.diff_scroll = diff.hunk_offset(self.selected_hunk, self.diff_layout);
+        }
+    }
+
+    pub fn select_previous_hunk(&mut self) {
+        self.line_selection = None;
+        if let Some(diff) = self.get_diff().cloned() {
+            self.selected_hunk = self
+                .selected_hunk
+                .saturating_sub(1)
+                .min(diff.hunks.len().saturating_sub(1));
+            self.diff_scroll = diff.hunk_offset(self.selected_hunk, self.diff_layout);
+        }
     }
 
-    pub fn get_diff(&self) -> Option<String> {
-        self.selected_file()
-            .and_then(|f| self.repo.get_diff(&f.path).ok())
+    /// Replace the preview of a diff too large to show by default with
+    /// the whole diff
+    pub fn load_full_diff(&mut self) {
+        let Some((file, source)) = self.selected_row() else {
+            return;
+        };
+        let path = file.path.clone();
+        if self.get_diff().is_some_and(FileDiff::is_truncated) {
+            self.diff_cache.load_full(&path, source);
+            self.request_diff();
+        }
+    }
+
+    /// Reveal more unchanged lines above or below the selected hunk. The
+    /// worker re-reads the diff with the extra context.
+    pub fn expand_hunk_context(&mut self, above: bool) {
+        let Some((file, source)) = self.selected_row() else {
+            return;
+        };
+        let key = self.diff_cache.key(file, source, self.granularity);
+        let Some(diff) = self.get_diff() else {
+            return;
+        };
+        let range = diff.context_range(self.selected_hunk, above, CONTEXT_EXPAND_STEP);
+        if range.is_empty() {
+            return;
+        }
+        // Revealing the whole gap merges the hunk into the one above
+        let merges_above = above
+            && self.selected_hunk > 0
+            && diff.context_range(self.selected_hunk, true, u32::MAX) == range;
+
+        self.line_selection = None;
+        self.diff_cache.expand(&key, range);
+        if merges_above {
+            self.selected_hunk -= 1;
+        }
+        self.request_diff();
+    }
+
+    /// Stage the selected hunk, or unstage it when the diff shows staged changes
+    pub fn toggle_stage_hunk(&mut self) -> Result<()> {
+        let Some(diff) = self.get_diff().cloned() else {
+            return Ok(());
+        };
+        self.ensure_exact_diff(&diff)?;
+        let Some(hunk) = diff.hunks.get(self.selected_hunk) else {
+            return Ok(());
+        };
+
+        match diff.source {
+            DiffSource::Unstaged => self.repo.stage_hunk(&diff.path, hunk)?,
+            DiffSource::Staged => self.repo.unstage_hunk(&diff.path, hunk)?,
+        }
+        self.refresh_status()?;
+        self.clamp_selected_hunk();
+        Ok(())
+    }
+
+    /// Ask to discard the selected file (file list), or the selected lines
+    /// or hunk (diff panel) from the working tree
+    pub fn request_discard(&mut self) -> Result<()> {
+        let Some((file, source)) = self.selected_row() else {
+            return Ok(());
+        };
+        if source != DiffSource::Unstaged {
+            return Err(eyre!(
+                "only unstaged changes can be discarded; unstage them first"
+            ));
+        }
+        if file.worktree_status == Some(FileStatus::Conflicted) {
+            return Err(eyre!(
+                "resolve the conflict (Enter) instead of discarding it"
+            ));
+        }
+
+        let discard = if self.active_panel == ActivePanel::FileList {
+            Discard::File {
+                path: file.path.clone(),
+                renamed_from: file.old_path(source).map(str::to_string),
+                untracked: file.worktree_status == Some(FileStatus::Untracked),
+            }
+        } else {
+            let Some(diff) = self.get_diff().cloned() else {
+                return Ok(());
+            };
+            self.ensure_exact_diff(&diff)?;
+            let hunk_index = self
+                .line_selection
+                .as_ref()
+                .map_or(self.selected_hunk, |s| s.hunk);
+            let Some(hunk) = diff.hunks.get(hunk_index).cloned() else {
+                return Ok(());
+            };
+            match &self.line_selection {
+                Some(selection) => Discard::Lines {
+                    path: diff.path,
+                    hunk,
+                    lines: selection.range().collect(),
+                },
+                None => Discard::Hunk {
+                    path: diff.path,
+                    hunk,
+                },
+            }
+        };
+        self.pending_discard = Some(discard);
+        Ok(())
+    }
+
+    pub fn cancel_discard(&mut self) {
+        self.pending_discard = None;
+    }
+
+    /// Carry out the confirmed discard, keeping what it removes for undo
+    pub fn confirm_discard(&mut self) -> Result<()> {
+        let Some(discard) = self.pending_discard.take() else {
+            return Ok(());
+        };
+
+        // Save the old content first, so a failed discard loses nothing
+        let mut files = Vec::new();
+        for path in discard.paths() {
+            let before = self.repo.read_worktree_file(path)?;
+            let blob = match &before {
+                Some(content) => Some(self.repo.save_blob(content)?.to_string()),
+                None => None,
+            };
+            files.push(FileSnapshot {
+                path: path.to_string(),
+                before,
+                after: None,
+                blob,
+            });
+        }
+
+        let result = match &discard {
+            Discard::File { .. } => {
+                let mut result = Ok(());
+                for (i, path) in discard.paths().into_iter().enumerate() {
+                    if let Err(e) = self.repo.discard_file(path) {
+                        // The files before this one are discarded already
+                        files.truncate(i);
+                        result = Err(e);
+                        break;
+                    }
+                }
+                result
+            }
+            Discard::Hunk { path, hunk } => self.repo.discard_hunk(path, hunk),
+            Discard::Lines { path, hunk, lines } => self.repo.discard_lines(path, hunk, lines),
+        };
+        if result.is_err() && !matches!(discard, Discard::File { .. }) {
+            // Patches apply as a whole or not at all
+            files.clear();
+        }
+
+        if !files.is_empty() {
+            for file in &mut files {
+                file.after = self.repo.read_worktree_file(&file.path)?;
+            }
+            let description = match &result {
+                Ok(()) => discard.description(),
+                Err(_) => {
+                    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+                    format!("all changes to {}", paths.join(", "))
+                }
+            };
+            self.notifications
+                .info(format!("Discarded {} (u to undo)", description));
+            self.undo.push(UndoEntry { description, files });
+            self.line_selection = None;
+        }
+        self.refresh_status()?;
+        self.clamp_selected_hunk();
+        result
+    }
+
+    /// Put back the working tree content of the most recent discard, unless
+    /// the files changed since
+    pub fn undo_discard(&mut self) -> Result<()> {
+        // The entry stays until the restore succeeds, a refused undo can be
+        // retried once the file is back
+        let Some(entry) = self.undo.last() else {
+            self.notifications.info("Nothing to undo");
+            return Ok(());
+        };
+
+        let changed = entry
+            .files
+            .iter()
+            .find(|f| self.repo.read_worktree_file(&f.path).ok() != Some(f.after.clone()));
+        if let Some(file) = changed {
+            let saved = match &file.blob {
+                Some(blob) => format!("; the discarded content is blob {}", &blob[..7]),
+                None => String::new(),
+            };
+            return Err(eyre!("{} changed since the discard{}", file.path, saved));
+        }
+
+        for file in &entry.files {
+            self.repo
+                .write_worktree_file(&file.path, file.before.as_deref())?;
+        }
+        let description = entry.description.clone();
+        self.undo.pop();
+        self.notifications
+            .success(format!("Restored {}", description));
+        self.refresh_status()
+    }
+
+    fn clamp_selected_hunk(&mut self) {
+        let hunk_count = self.get_diff().map(|d| d.hunks.len()).unwrap_or(0);
+        self.selected_hunk = self.selected_hunk.min(hunk_count.saturating_sub(1));
     }
 
     pub fn is_recently_changed(&self, path: &str) -> bool {
         let now = Instant::now();
         self.recently_changed
             .iter()
-            .any(|(p, time)| p == path && now.duration_since(*time).as_millis() < 800)
+            .any(|(p, time)| p == path && now.duration_since(*time) < self.animation)
     }
 
     /// Get animation progress for a recently changed file (0.0 to 1.0)
@@ -197,9 +1380,9 @@ impl App {
         let now = Instant::now();
         self.recently_changed.iter().find_map(|(p, time)| {
             if p == path {
-                let elapsed = now.duration_since(*time).as_millis() as f64;
-                if elapsed < 800.0 {
-                    Some(elapsed / 800.0)
+                let elapsed = now.duration_since(*time);
+                if elapsed < self.animation {
+                    Some(elapsed.as_secs_f64() / self.animation.as_secs_f64())
                 } else {
                     None
                 }
@@ -210,20 +1393,23 @@ impl App {
     }
 
     pub fn staged_count(&self) -> usize {
-        self.files.iter().filter(|f| f.staged).count()
+        self.files
+            .iter()
+            .filter(|f| f.index_status.is_some())
+            .count()
     }
 
     pub fn modified_count(&self) -> usize {
         self.files
             .iter()
-            .filter(|f| f.status == FileStatus::Modified && !f.staged)
+            .filter(|f| f.worktree_status == Some(FileStatus::Modified))
             .count()
     }
 
     pub fn untracked_count(&self) -> usize {
         self.files
             .iter()
-            .filter(|f| f.status == FileStatus::Untracked)
+            .filter(|f| f.worktree_status == Some(FileStatus::Untracked))
             .count()
     }
 
@@ -231,3 +1417,30 @@ impl App {
         self.last_update.elapsed().as_secs_f64()
     }
 }
+
+/// Build the file list rows: every staged change first, then every unstaged one
+fn build_rows(files: &[FileChange]) -> Vec<FileRow> {
+    [DiffSource::Staged, DiffSource::Unstaged]
+        .into_iter()
+        .flat_map(|source| {
+            files
+                .iter()
+                .enumerate()
+                .filter(move |(_, f)| f.status(source).is_some())
+                .map(move |(file, _)| FileRow { file, source })
+        })
+        .collect()
+}
+
+/// Whether `path` is either side of a listed rename or copy, which a
+/// per-path status update would split up
+fn is_rename_path(files: &[FileChange], path: &str) -> bool {
+    files.iter().any(|file| {
+        let old_paths = [
+            file.index_old_path.as_deref(),
+            file.worktree_old_path.as_deref(),
+        ];
+        old_paths.contains(&Some(path))
+            || (file.path == path && old_paths.iter().any(Option::is_some))
+    })
+}
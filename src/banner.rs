@@ -22,12 +22,3 @@ pub fn print_banner() {
 
     eprintln!("{}", banner);
 }
-
-/// Print version info
-pub fn print_version() {
-    const VERSION: &str = env!("CARGO_PKG_VERSION");
-    const DIM: &str = "\x1b[38;2;108;112;134m";
-    const RESET: &str = "\x1b[0m";
-
-    eprintln!("{DIM}v{VERSION}{RESET}\n");
-}
@@ -0,0 +1,420 @@
+use crate::config::{self, ConfigFile, DiffConfig, UiConfig, WatcherConfig};
+use crate::snapshot::{Condition, StatusFormat};
+use crate::ui::theme::ColorDepth;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Key and mouse bindings of the TUI, appended to `--help`
+const BINDINGS: &str = "\
+Keybindings:
+  j/k, ↑/↓         Navigate files, or scroll the diff panel
+  Tab              Switch panels
+  Enter            Stage/unstage file, or resolve a conflicted file
+  n/p              Next/previous hunk (diff panel)
+  s                Stage/unstage hunk (diff panel)
+  v                Select lines; j/k extend, s stage, D discard (diff panel)
+  D                Discard file, or hunk/selected lines (diff panel), after confirmation
+  u                Undo the last discard
+  [ / ]            Expand context above/below hunk (diff panel)
+  x                Load the full diff of a large file
+  t                Toggle unified/side-by-side diff
+  w                Cycle word/character/line emphasis of changed lines
+  + / -            Show more/fewer context lines
+  W / B            Ignore whitespace changes / blank lines
+  a                Cycle diff algorithm (myers/minimal/patience)
+  z                Stash panel (s/S/f stash all/staged/file, a apply, p pop, d drop)
+  c                Commit staged changes (Ctrl+S commit, Ctrl+A amend, Esc cancel)
+  r                Refresh status
+  L                Show/hide message log
+  PageDown/PageUp  Scroll the diff 10 lines
+  Home/End         Go to first/last file
+  q, Esc           Quit (Ctrl+C force quit)
+
+Conflict view:
+  n/p              Next/previous conflict region
+  o/t/b            Keep ours/theirs/both, u undo the choice
+  Enter            Write the merged file and mark it resolved
+
+Mouse:
+  Wheel            Move through files, or scroll the diff 3 lines
+  Click            Select a file, or focus the diff panel";
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "git-monitor",
+    version,
+    about = "Real-time Git file change monitoring with animations",
+    args_conflicts_with_subcommands = true,
+    after_long_help = BINDINGS
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub tui: TuiArgs,
+}
+
+impl Cli {
+    /// The subcommand to run, the TUI when none was given
+    pub fn into_command(self) -> Command {
+        self.command.unwrap_or(Command::Tui(self.tui))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Interactive terminal UI (the default)
+    #[command(after_long_help = BINDINGS)]
+    Tui(TuiArgs),
+    /// Print the status once and exit
+    Status(StatusArgs),
+    /// Exit non-zero when the worktree is dirty, has untracked files or
+    /// conflicts
+    #[command(after_long_help = "\
+Exit codes add up when several conditions hold:
+  2  worktree dirty
+  4  untracked files present
+  8  conflicts present
+  1  git-monitor itself failed")]
+    Check(CheckArgs),
+    /// Print a JSON line on stdout for every file status change
+    Watch(WatchArgs),
+    /// Stream the JSON lines of `watch` to every client of a TCP socket
+    Serve(ServeArgs),
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// Repository and diff options shared by every subcommand
+#[derive(Debug, Clone, Args)]
+pub struct RepoArgs {
+    /// Path to the Git repository [default: current directory]
+    pub path: Option<PathBuf>,
+
+    /// Config file to use instead of ~/.config/git-monitor/config.toml
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Similarity needed to pair renames and copies; 0 disables detection
+    /// [default: 50]
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent)]
+    pub rename_threshold: Option<u16>,
+}
+
+impl RepoArgs {
+    /// The repository path, the current directory when none was given
+    pub fn path(&self) -> PathBuf {
+        self.path
+            .clone()
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+    }
+
+    /// The config values given on the command line
+    pub fn overrides(&self) -> ConfigFile {
+        ConfigFile {
+            diff: DiffConfig {
+                rename_threshold: self.rename_threshold,
+            },
+            ..ConfigFile::default()
+        }
+    }
+}
+
+/// File watcher options
+#[derive(Debug, Clone, Args)]
+pub struct WatcherArgs {
+    /// Ignore watcher events for matching paths, in addition to .gitignore
+    /// and the config files (repeatable)
+    #[arg(short, long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Wait this long for file events to settle before a refresh
+    /// [default: 200]
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(config::DEBOUNCE_MS))]
+    pub debounce: Option<u64>,
+}
+
+impl WatcherArgs {
+    fn override_config(&self, layer: &mut ConfigFile) {
+        layer.watcher = WatcherConfig {
+            debounce: self.debounce,
+            exclude: self.exclude.clone(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    pub repo: RepoArgs,
+
+    #[command(flatten)]
+    pub watcher: WatcherArgs,
+
+    /// Redraw and animation interval [default: 200]
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(config::TICK_RATE_MS))]
+    pub tick_rate: Option<u64>,
+
+    /// How long a changed file stays highlighted; 0 turns it off
+    /// [default: 800]
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(config::ANIMATION_MS))]
+    pub animation: Option<u64>,
+
+    /// Width of the file list, in percent of the screen [default: 50]
+    #[arg(long, value_name = "PERCENT", value_parser = parse_split)]
+    pub split: Option<u16>,
+
+    /// Color theme: auto (follows the terminal background), a built-in one
+    /// or a custom one from the config [default: auto]
+    #[arg(long, value_name = "NAME")]
+    pub theme: Option<String>,
+
+    /// Colors the terminal can show [default: auto]
+    #[arg(long, value_enum, value_name = "DEPTH")]
+    pub color: Option<ColorArg>,
+
+    /// Same as the `watch` subcommand
+    #[arg(long, hide = true)]
+    pub json: bool,
+}
+
+impl TuiArgs {
+    pub fn overrides(&self) -> ConfigFile {
+        let mut layer = self.repo.overrides();
+        self.watcher.override_config(&mut layer);
+        layer.ui = UiConfig {
+            tick_rate: self.tick_rate,
+            animation: self.animation,
+            split: self.split,
+            theme: self.theme.clone(),
+            color: self.color.map(Into::into),
+        };
+        layer
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct StatusArgs {
+    #[command(flatten)]
+    pub repo: RepoArgs,
+
+    #[arg(long, value_enum, default_value_t = FormatArg::Human)]
+    pub format: FormatArg,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FormatArg {
+    /// Grouped by staged, unstaged and untracked, with line counts
+    Human,
+    /// One JSON object per file side, as printed by `watch`
+    Json,
+    /// The short format of `git status --porcelain`
+    Porcelain,
+}
+
+impl From<FormatArg> for StatusFormat {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Human => StatusFormat::Human,
+            FormatArg::Json => StatusFormat::Json,
+            FormatArg::Porcelain => StatusFormat::Porcelain,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ColorArg {
+    /// Detect from COLORTERM and TERM
+    Auto,
+    /// 24-bit colors
+    Truecolor,
+    /// The 256-color palette
+    #[value(name = "256")]
+    Ansi256,
+    /// The 16 ANSI colors
+    #[value(name = "16")]
+    Ansi16,
+}
+
+impl From<ColorArg> for ColorDepth {
+    fn from(color: ColorArg) -> Self {
+        match color {
+            ColorArg::Auto => ColorDepth::Auto,
+            ColorArg::Truecolor => ColorDepth::TrueColor,
+            ColorArg::Ansi256 => ColorDepth::Ansi256,
+            ColorArg::Ansi16 => ColorDepth::Ansi16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub repo: RepoArgs,
+
+    /// Fail on staged or unstaged changes to tracked files
+    #[arg(long)]
+    pub dirty: bool,
+
+    /// Fail on untracked files
+    #[arg(long)]
+    pub untracked: bool,
+
+    /// Fail on unresolved conflicts
+    #[arg(long)]
+    pub conflicts: bool,
+}
+
+impl CheckArgs {
+    /// The conditions to check, all of them when none was picked
+    pub fn conditions(&self) -> Vec<Condition> {
+        let picked: Vec<Condition> = [
+            (self.dirty, Condition::Dirty),
+            (self.untracked, Condition::Untracked),
+            (self.conflicts, Condition::Conflicts),
+        ]
+        .into_iter()
+        .filter_map(|(picked, condition)| picked.then_some(condition))
+        .collect();
+        if picked.is_empty() {
+            Condition::ALL.to_vec()
+        } else {
+            picked
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct WatchArgs {
+    #[command(flatten)]
+    pub repo: RepoArgs,
+
+    #[command(flatten)]
+    pub watcher: WatcherArgs,
+}
+
+impl WatchArgs {
+    pub fn overrides(&self) -> ConfigFile {
+        let mut layer = self.repo.overrides();
+        self.watcher.override_config(&mut layer);
+        layer
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub repo: RepoArgs,
+
+    #[command(flatten)]
+    pub watcher: WatcherArgs,
+
+    /// Address to listen on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:7575")]
+    pub listen: SocketAddr,
+}
+
+impl ServeArgs {
+    pub fn overrides(&self) -> ConfigFile {
+        let mut layer = self.repo.overrides();
+        self.watcher.override_config(&mut layer);
+        layer
+    }
+}
+
+/// A percentage from 0 to 100, with or without the `%` sign
+fn parse_percent(percent: &str) -> Result<u16, String> {
+    match percent.trim_end_matches('%').parse::<u16>() {
+        Ok(percent) if config::RENAME_THRESHOLD_PERCENT.contains(&percent) => Ok(percent),
+        _ => Err("expected a percentage from 0 to 100".to_string()),
+    }
+}
+
+/// A file list width within `config::SPLIT_PERCENT`
+fn parse_split(percent: &str) -> Result<u16, String> {
+    let range = config::SPLIT_PERCENT;
+    match percent.trim_end_matches('%').parse::<u16>() {
+        Ok(percent) if range.contains(&percent) => Ok(percent),
+        _ => Err(format!(
+            "expected a percentage from {} to {}",
+            range.start(),
+            range.end()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_default_command_is_tui() {
+        let cli =
+            Cli::try_parse_from(["git-monitor", "repo", "-e", "*.log", "--split", "40"]).unwrap();
+        let Command::Tui(args) = cli.into_command() else {
+            panic!("expected the TUI");
+        };
+        assert_eq!(args.repo.path, Some(PathBuf::from("repo")));
+        assert_eq!(args.watcher.exclude, vec!["*.log".to_string()]);
+        assert_eq!(args.split, Some(40));
+        assert_eq!(args.tick_rate, None);
+        let overrides = args.overrides();
+        assert_eq!(overrides.ui.split, Some(40));
+        assert_eq!(overrides.watcher.exclude, vec!["*.log".to_string()]);
+        assert_eq!(overrides.diff.rename_threshold, None);
+    }
+
+    #[test]
+    fn test_subcommands_and_validation() {
+        let cli = Cli::try_parse_from(["git-monitor", "status", "--format", "porcelain"]).unwrap();
+        assert!(matches!(
+            cli.into_command(),
+            Command::Status(StatusArgs {
+                format: FormatArg::Porcelain,
+                ..
+            })
+        ));
+
+        let cli =
+            Cli::try_parse_from(["git-monitor", "check", "--rename-threshold", "0%"]).unwrap();
+        let Command::Check(args) = cli.into_command() else {
+            panic!("expected check");
+        };
+        assert_eq!(args.repo.rename_threshold, Some(0));
+        assert_eq!(args.conditions(), Condition::ALL.to_vec());
+
+        let cli = Cli::try_parse_from([
+            "git-monitor",
+            "--theme",
+            "solarized-light",
+            "--color",
+            "256",
+        ])
+        .unwrap();
+        let Command::Tui(args) = cli.into_command() else {
+            panic!("expected the TUI");
+        };
+        assert_eq!(
+            args.overrides().ui.theme.as_deref(),
+            Some("solarized-light")
+        );
+        assert_eq!(args.overrides().ui.color, Some(ColorDepth::Ansi256));
+
+        assert!(Cli::try_parse_from(["git-monitor", "--split", "95"]).is_err());
+        assert!(Cli::try_parse_from(["git-monitor", "--color", "8"]).is_err());
+        assert!(Cli::try_parse_from(["git-monitor", "--rename-threshold", "120"]).is_err());
+        assert!(Cli::try_parse_from(["git-monitor", "serve", "--listen", "nowhere"]).is_err());
+    }
+}
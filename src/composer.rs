@@ -0,0 +1,186 @@
+/// Multi-line commit message editor state
+#[derive(Debug, Clone, Default)]
+pub struct CommitComposer {
+    /// Message lines (always at least one)
+    pub lines: Vec<String>,
+    /// Cursor row
+    pub row: usize,
+    /// Cursor column, in characters
+    pub col: usize,
+    /// Amend HEAD instead of creating a new commit
+    pub amend: bool,
+    /// Last commit error, shown under the editor
+    pub error: Option<String>,
+}
+
+impl CommitComposer {
+    pub fn new() -> Self {
+        Self {
+            lines: vec![String::new()],
+            ..Self::default()
+        }
+    }
+
+    /// Full message text
+    pub fn message(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.iter().all(|l| l.trim().is_empty())
+    }
+
+    /// Replace the whole message, moving the cursor to its end
+    pub fn set_message(&mut self, message: &str) {
+        self.lines = message.lines().map(str::to_string).collect();
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.row = self.lines.len() - 1;
+        self.col = self.lines[self.row].chars().count();
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let offset = self.byte_offset();
+        self.lines[self.row].insert(offset, c);
+        self.col += 1;
+    }
+
+    pub fn insert_newline(&mut self) {
+        let offset = self.byte_offset();
+        let rest = self.lines[self.row].split_off(offset);
+        self.row += 1;
+        self.col = 0;
+        self.lines.insert(self.row, rest);
+    }
+
+    pub fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            let offset = self.byte_offset();
+            self.lines[self.row].remove(offset);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.lines[self.row].chars().count();
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.col < self.line_len() {
+            let offset = self.byte_offset();
+            self.lines[self.row].remove(offset);
+        } else if self.row + 1 < self.lines.len() {
+            let next = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&next);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if self.col < self.line_len() {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.line_len());
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.line_len());
+        }
+    }
+
+    pub fn move_home(&mut self) {
+        self.col = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.col = self.line_len();
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    /// Byte offset of the cursor within the current line
+    fn byte_offset(&self) -> usize {
+        self.lines[self.row]
+            .char_indices()
+            .nth(self.col)
+            .map(|(i, _)| i)
+            .unwrap_or(self.lines[self.row].len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_str(composer: &mut CommitComposer, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                composer.insert_newline();
+            } else {
+                composer.insert_char(c);
+            }
+        }
+    }
+
+    #[test]
+    fn test_typing_multiline_message() {
+        let mut composer = CommitComposer::new();
+        type_str(&mut composer, "Subject\n\nBody línea");
+        assert_eq!(composer.message(), "Subject\n\nBody línea");
+        assert_eq!((composer.row, composer.col), (2, 10));
+    }
+
+    #[test]
+    fn test_backspace_joins_lines() {
+        let mut composer = CommitComposer::new();
+        type_str(&mut composer, "ab\ncd");
+        composer.move_home();
+        composer.backspace();
+        assert_eq!(composer.message(), "abcd");
+        assert_eq!((composer.row, composer.col), (0, 2));
+    }
+
+    #[test]
+    fn test_insert_in_middle_of_unicode_line() {
+        let mut composer = CommitComposer::new();
+        type_str(&mut composer, "héllo");
+        composer.move_left();
+        composer.move_left();
+        composer.insert_char('X');
+        assert_eq!(composer.message(), "hélXlo");
+        composer.delete();
+        assert_eq!(composer.message(), "hélXo");
+    }
+
+    #[test]
+    fn test_set_message_and_is_empty() {
+        let mut composer = CommitComposer::new();
+        assert!(composer.is_empty());
+        composer.set_message("Fix bug\n\nDetails");
+        assert!(!composer.is_empty());
+        assert_eq!((composer.row, composer.col), (2, 7));
+    }
+}
@@ -0,0 +1,485 @@
+use crate::event::Event;
+use crate::git::DiffSettings;
+use crate::ui::theme::{Background, ColorDepth, Theme};
+use color_eyre::{eyre::eyre, Result};
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Name of the per-repository config file, in the repository root
+pub const REPO_CONFIG_FILE: &str = ".git-monitor.toml";
+
+/// Accepted values, shared with the command line options
+pub const TICK_RATE_MS: RangeInclusive<u64> = 16..=5_000;
+pub const ANIMATION_MS: RangeInclusive<u64> = 0..=5_000;
+pub const SPLIT_PERCENT: RangeInclusive<u16> = 20..=80;
+pub const DEBOUNCE_MS: RangeInclusive<u64> = 10..=10_000;
+pub const RENAME_THRESHOLD_PERCENT: RangeInclusive<u16> = 0..=100;
+
+/// Theme name that follows the terminal background
+pub const AUTO_THEME: &str = "auto";
+
+/// Colors of a custom theme by key, plus `base`, the theme it starts from,
+/// and `syntax`, the syntect theme for code
+pub type ThemeTable = BTreeMap<String, String>;
+
+/// Settings after merging the global config, the repository's config and
+/// the command line, in that order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Redraw and animation interval
+    pub tick_rate: Duration,
+    /// How long a changed file stays highlighted; zero turns it off
+    pub animation: Duration,
+    /// Width of the file list, in percent of the content area
+    pub split: u16,
+    /// Time file events are given to settle before a refresh
+    pub debounce: Duration,
+    /// Globs the watcher ignores on top of .gitignore
+    pub exclude: Vec<String>,
+    /// Similarity needed to pair renames and copies, `None` to not detect them
+    pub rename_threshold: Option<u16>,
+    /// Built-in or custom theme name, or `auto`
+    pub theme: String,
+    /// Colors the terminal can show
+    pub color: ColorDepth,
+    /// Custom themes by name
+    pub themes: BTreeMap<String, ThemeTable>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tick_rate: Duration::from_millis(200),
+            animation: Duration::from_millis(800),
+            split: 50,
+            debounce: Duration::from_millis(200),
+            exclude: Vec::new(),
+            rename_threshold: DiffSettings::default().rename_threshold,
+            theme: AUTO_THEME.to_string(),
+            color: ColorDepth::Auto,
+            themes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Options every diff is computed with at startup
+    pub fn diff_settings(&self) -> DiffSettings {
+        DiffSettings {
+            rename_threshold: self.rename_threshold,
+            ..DiffSettings::default()
+        }
+    }
+
+    /// The selected theme, with `auto` standing for a light or dark one
+    /// depending on `background`
+    pub fn theme(&self, background: Background) -> Result<Theme> {
+        self.resolve_theme(&self.theme, background)
+    }
+
+    fn resolve_theme(&self, name: &str, background: Background) -> Result<Theme> {
+        if name == AUTO_THEME {
+            return Ok(Theme::auto(background));
+        }
+        if let Some(table) = self.themes.get(name) {
+            // Bases are looked up among the built-ins only, so a table may
+            // tweak the built-in theme of the same name
+            let base = table.get("base").map_or(AUTO_THEME, String::as_str);
+            let mut theme = match base {
+                AUTO_THEME => Theme::auto(background),
+                _ => Theme::builtin(base).ok_or_else(|| {
+                    eyre!(
+                        "themes.{}.base: {:?} is not a built-in theme, expected one of: auto, {}",
+                        name,
+                        base,
+                        Theme::BUILTIN.join(", ")
+                    )
+                })?,
+            };
+            for (key, value) in table.iter().filter(|(key, _)| *key != "base") {
+                theme
+                    .set(key, value)
+                    .map_err(|e| eyre!("themes.{}.{}: {}", name, key, e))?;
+            }
+            return Ok(theme);
+        }
+        Theme::builtin(name).ok_or_else(|| {
+            eyre!(
+                "ui.theme: unknown theme {:?}, expected auto, one of {} or a [themes.{}] table",
+                name,
+                Theme::BUILTIN.join(", "),
+                name
+            )
+        })
+    }
+
+    /// Resolve the selected and every custom theme, so mistakes show up
+    /// when the config is loaded
+    fn validate_themes(&self) -> Result<()> {
+        self.theme(Background::Dark)?;
+        for name in self.themes.keys() {
+            self.resolve_theme(name, Background::Dark)?;
+        }
+        Ok(())
+    }
+
+    /// Override with the values `layer` sets; exclude globs add up, as do
+    /// the colors of custom themes of the same name
+    fn merge(&mut self, layer: &ConfigFile) {
+        if let Some(ms) = layer.ui.tick_rate {
+            self.tick_rate = Duration::from_millis(ms);
+        }
+        if let Some(ms) = layer.ui.animation {
+            self.animation = Duration::from_millis(ms);
+        }
+        if let Some(split) = layer.ui.split {
+            self.split = split;
+        }
+        if let Some(ms) = layer.watcher.debounce {
+            self.debounce = Duration::from_millis(ms);
+        }
+        self.exclude.extend(layer.watcher.exclude.iter().cloned());
+        if let Some(percent) = layer.diff.rename_threshold {
+            self.rename_threshold = (percent > 0).then_some(percent);
+        }
+        if let Some(theme) = &layer.ui.theme {
+            self.theme = theme.clone();
+        }
+        if let Some(color) = layer.ui.color {
+            self.color = color;
+        }
+        for (name, table) in &layer.themes {
+            self.themes.entry(name.clone()).or_default().extend(
+                table
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+        }
+    }
+}
+
+/// One layer of settings, as written in a config file. Unset values leave
+/// those of the layers below.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub ui: UiConfig,
+    pub watcher: WatcherConfig,
+    pub diff: DiffConfig,
+    pub themes: BTreeMap<String, ThemeTable>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// Milliseconds
+    pub tick_rate: Option<u64>,
+    /// Milliseconds
+    pub animation: Option<u64>,
+    /// Percent
+    pub split: Option<u16>,
+    /// Built-in or custom theme name, or `auto`
+    pub theme: Option<String>,
+    /// `auto`, `truecolor`, `256` or `16`
+    pub color: Option<ColorDepth>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatcherConfig {
+    /// Milliseconds
+    pub debounce: Option<u64>,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiffConfig {
+    /// Percent, 0 disables detection
+    pub rename_threshold: Option<u16>,
+}
+
+impl ConfigFile {
+    pub fn parse(text: &str) -> Result<Self> {
+        let layer: ConfigFile = toml::from_str(text).map_err(|e| eyre!("{}", e))?;
+        layer.validate()?;
+        Ok(layer)
+    }
+
+    fn validate(&self) -> Result<()> {
+        check_range("ui.tick_rate", self.ui.tick_rate, &TICK_RATE_MS)?;
+        check_range("ui.animation", self.ui.animation, &ANIMATION_MS)?;
+        check_range("ui.split", self.ui.split, &SPLIT_PERCENT)?;
+        check_range("watcher.debounce", self.watcher.debounce, &DEBOUNCE_MS)?;
+        check_range(
+            "diff.rename_threshold",
+            self.diff.rename_threshold,
+            &RENAME_THRESHOLD_PERCENT,
+        )?;
+        for glob in &self.watcher.exclude {
+            if let Err(e) = globset::Glob::new(glob) {
+                return Err(eyre!(
+                    "watcher.exclude: invalid glob {:?}: {}",
+                    glob,
+                    e.kind()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_range<T>(key: &str, value: Option<T>, range: &RangeInclusive<T>) -> Result<()>
+where
+    T: PartialOrd + std::fmt::Display,
+{
+    match value {
+        Some(value) if !range.contains(&value) => Err(eyre!(
+            "{} must be from {} to {}, got {}",
+            key,
+            range.start(),
+            range.end(),
+            value
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Where the settings come from: the global and repository config files,
+/// plus the command line on top
+#[derive(Debug, Clone)]
+pub struct ConfigSources {
+    /// `--config`, or the file in the XDG config directory
+    global: Option<PathBuf>,
+    /// Whether `global` was given explicitly, so it must exist
+    explicit: bool,
+    /// `.git-monitor.toml` in the repository root
+    repo: PathBuf,
+    overrides: ConfigFile,
+}
+
+impl ConfigSources {
+    pub fn new(repo_root: &Path, config: Option<PathBuf>, overrides: ConfigFile) -> Self {
+        Self {
+            explicit: config.is_some(),
+            global: config.or_else(default_config_path),
+            repo: repo_root.join(REPO_CONFIG_FILE),
+            overrides,
+        }
+    }
+
+    /// Read and merge every layer; errors name the file and the bad value
+    pub fn load(&self) -> Result<Config> {
+        let mut config = Config::default();
+        if let Some(global) = &self.global {
+            match read_layer(global)? {
+                Some(layer) => config.merge(&layer),
+                None if self.explicit => {
+                    return Err(eyre!("Config file {} not found", global.display()))
+                }
+                None => {}
+            }
+        }
+        if let Some(layer) = read_layer(&self.repo)? {
+            config.merge(&layer);
+        }
+        config.merge(&self.overrides);
+        config.validate_themes()?;
+        Ok(config)
+    }
+
+    /// The config files, whether they exist or not
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.global
+            .iter()
+            .cloned()
+            .chain(std::iter::once(self.repo.clone()))
+            .collect()
+    }
+}
+
+/// `$XDG_CONFIG_HOME/git-monitor/config.toml`, or `~/.config/...` when the
+/// variable is not set
+pub fn default_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("git-monitor").join("config.toml"))
+}
+
+/// One config file, `None` when it does not exist
+fn read_layer(path: &Path) -> Result<Option<ConfigFile>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(eyre!("Failed to read {}: {}", path.display(), e)),
+    };
+    ConfigFile::parse(&text)
+        .map(Some)
+        .map_err(|e| eyre!("{}: {}", path.display(), e))
+}
+
+/// Watches the config files, sending `Event::ConfigChange` when one of them
+/// is written, created or removed
+pub struct ConfigWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl ConfigWatcher {
+    pub fn new(paths: Vec<PathBuf>, sender: mpsc::UnboundedSender<Event>) -> Result<Self> {
+        let watched = paths.clone();
+        let mut debouncer = new_debouncer(
+            Duration::from_millis(200),
+            move |result: DebounceEventResult| {
+                let Ok(events) = result else {
+                    return;
+                };
+                if events.iter().any(|e| watched.contains(&e.path)) {
+                    let _ = sender.send(Event::ConfigChange);
+                }
+            },
+        )?;
+
+        // Watch the directories, so files created later are noticed too
+        let mut dirs: Vec<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+        dirs.dedup();
+        for dir in dirs.into_iter().filter(|dir| dir.is_dir()) {
+            debouncer
+                .watcher()
+                .watch(dir, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(Self {
+            _debouncer: debouncer,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_merge_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("config.toml");
+        std::fs::write(
+            &global,
+            "[ui]\ntick_rate = 100\nsplit = 30\n\n[watcher]\nexclude = [\"*.log\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join(REPO_CONFIG_FILE),
+            "[ui]\nsplit = 40\n\n[watcher]\nexclude = [\"target/**\"]\n\n[diff]\nrename_threshold = 0\n",
+        )
+        .unwrap();
+        let overrides = ConfigFile {
+            ui: UiConfig {
+                split: Some(60),
+                ..UiConfig::default()
+            },
+            ..ConfigFile::default()
+        };
+
+        let config = ConfigSources::new(dir.path(), Some(global), overrides)
+            .load()
+            .unwrap();
+        assert_eq!(config.tick_rate, Duration::from_millis(100));
+        assert_eq!(config.split, 60);
+        assert_eq!(
+            config.exclude,
+            vec!["*.log".to_string(), "target/**".to_string()]
+        );
+        assert_eq!(config.rename_threshold, None);
+        assert_eq!(config.animation, Config::default().animation);
+    }
+
+    #[test]
+    fn test_invalid_values_are_reported() {
+        let error = ConfigFile::parse("[ui]\nsplit = 95\n").unwrap_err();
+        assert_eq!(error.to_string(), "ui.split must be from 20 to 80, got 95");
+
+        let error = ConfigFile::parse("[ui]\ntickrate = 100\n").unwrap_err();
+        assert!(error.to_string().contains("unknown field `tickrate`"));
+
+        let error = ConfigFile::parse("[watcher]\nexclude = [\"a/{b\"]\n").unwrap_err();
+        assert!(error.to_string().contains("\"a/{b\""));
+    }
+
+    #[test]
+    fn test_custom_themes() {
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("config.toml");
+        std::fs::write(
+            &global,
+            "[ui]\ntheme = \"mine\"\ncolor = \"256\"\n\n[themes.mine]\nbase = \"solarized-light\"\ntext = \"#000000\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join(REPO_CONFIG_FILE),
+            "[themes.mine]\naccent = \"red\"\n",
+        )
+        .unwrap();
+
+        let sources = ConfigSources::new(dir.path(), Some(global.clone()), ConfigFile::default());
+        let config = sources.load().unwrap();
+        assert_eq!(config.color, ColorDepth::Ansi256);
+        let theme = config.theme(Background::Dark).unwrap();
+        let solarized = Theme::builtin("solarized-light").unwrap();
+        assert_eq!(theme.background, solarized.background);
+        assert_eq!(theme.text, ratatui::style::Color::Rgb(0, 0, 0));
+        assert_eq!(theme.accent, ratatui::style::Color::Red);
+
+        // A table named after a built-in tweaks that built-in
+        std::fs::write(
+            &global,
+            "[ui]\ntheme = \"catppuccin-mocha\"\n\n[themes.catppuccin-mocha]\nbase = \"catppuccin-mocha\"\ntext = \"#000000\"\n",
+        )
+        .unwrap();
+        let config = sources.load().unwrap();
+        let theme = config.theme(Background::Light).unwrap();
+        assert_eq!(theme.background, Theme::default().background);
+        assert_eq!(theme.text, ratatui::style::Color::Rgb(0, 0, 0));
+
+        let auto = Config::default();
+        assert_eq!(
+            auto.theme(Background::Light).unwrap(),
+            Theme::auto(Background::Light)
+        );
+
+        std::fs::write(&global, "[ui]\ntheme = \"nope\"\n").unwrap();
+        assert!(sources
+            .load()
+            .unwrap_err()
+            .to_string()
+            .contains("unknown theme \"nope\""));
+        std::fs::write(
+            &global,
+            "[ui]\ntheme = \"mine\"\n\n[themes.mine]\ntext = \"#12\"\n",
+        )
+        .unwrap();
+        assert!(sources
+            .load()
+            .unwrap_err()
+            .to_string()
+            .starts_with("themes.mine.text: invalid color"));
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let sources = ConfigSources::new(dir.path(), None, ConfigFile::default());
+        assert!(sources.paths().contains(&dir.path().join(REPO_CONFIG_FILE)));
+
+        let missing = dir.path().join("nope.toml");
+        let sources = ConfigSources::new(dir.path(), Some(missing), ConfigFile::default());
+        assert!(sources.load().is_err());
+    }
+}
@@ -1,9 +1,34 @@
-use crate::app::{ActivePanel, App};
+use crate::app::{ActivePanel, App, ListEntry};
+use crate::git::{Resolution, StashMode};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
 
 /// Handle keyboard events
 pub fn handle_key_event(app: &mut App, key: KeyEvent) {
+    if app.composer.is_some() {
+        handle_composer_key(app, key);
+        return;
+    }
+    if app.pending_discard.is_some() {
+        handle_confirm_discard_key(app, key);
+        return;
+    }
+    if app.conflict.is_some() {
+        handle_conflict_key(app, key);
+        return;
+    }
+    if app.stash_panel.is_some() {
+        handle_stash_key(app, key);
+        return;
+    }
+    if app.show_log {
+        handle_log_key(app, key);
+        return;
+    }
+
     match key.code {
+        // Cancel visual line selection before quitting
+        KeyCode::Esc if app.line_selection.is_some() => app.line_selection = None,
+
         // Quit
         KeyCode::Char('q') | KeyCode::Esc => app.quit(),
         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => app.quit(),
@@ -12,12 +37,18 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) {
         KeyCode::Char('j') | KeyCode::Down => {
             match app.active_panel {
                 ActivePanel::FileList => app.select_next(),
+                ActivePanel::DiffView if app.line_selection.is_some() => {
+                    app.move_line_selection(true)
+                }
                 ActivePanel::DiffView => app.scroll_diff_down(),
             }
         }
         KeyCode::Char('k') | KeyCode::Up => {
             match app.active_panel {
                 ActivePanel::FileList => app.select_previous(),
+                ActivePanel::DiffView if app.line_selection.is_some() => {
+                    app.move_line_selection(false)
+                }
                 ActivePanel::DiffView => app.scroll_diff_up(),
             }
         }
@@ -27,17 +58,81 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) {
 
         // Stage/unstage
         KeyCode::Enter => {
-            if let Err(e) = app.toggle_stage() {
-                // Log error but don't crash
-                eprintln!("Failed to toggle stage: {}", e);
-            }
+            let result = app.toggle_stage();
+            app.report_error("toggle stage", result);
+        }
+
+        // Hunk navigation and staging (diff panel)
+        KeyCode::Char('n') if app.active_panel == ActivePanel::DiffView => app.select_next_hunk(),
+        KeyCode::Char('p') if app.active_panel == ActivePanel::DiffView => {
+            app.select_previous_hunk()
+        }
+        KeyCode::Char('v') if app.active_panel == ActivePanel::DiffView => {
+            app.toggle_line_selection()
+        }
+        KeyCode::Char('s') if app.line_selection.is_some() => {
+            let result = app.toggle_stage_lines();
+            app.report_error("stage lines", result);
+        }
+        KeyCode::Char('s') if app.active_panel == ActivePanel::DiffView => {
+            let result = app.toggle_stage_hunk();
+            app.report_error("stage hunk", result);
+        }
+        KeyCode::Char('x') => app.load_full_diff(),
+        KeyCode::Char('[') if app.active_panel == ActivePanel::DiffView => {
+            app.expand_hunk_context(true)
+        }
+        KeyCode::Char(']') if app.active_panel == ActivePanel::DiffView => {
+            app.expand_hunk_context(false)
         }
+        KeyCode::Char('D') => {
+            let result = app.request_discard();
+            app.report_error("discard", result);
+        }
+        KeyCode::Char('u') => {
+            let result = app.undo_discard();
+            app.report_error("undo discard", result);
+        }
+
+        // Diff layout and intra-line emphasis
+        KeyCode::Char('t') => app.toggle_diff_layout(),
+        KeyCode::Char('w') => app.cycle_granularity(),
+
+        // Diff options
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            let result = app.expand_context();
+            app.report_error("change context", result);
+        }
+        KeyCode::Char('-') => {
+            let result = app.collapse_context();
+            app.report_error("change context", result);
+        }
+        KeyCode::Char('W') => {
+            let result = app.toggle_ignore_whitespace();
+            app.report_error("toggle whitespace", result);
+        }
+        KeyCode::Char('B') => {
+            let result = app.toggle_ignore_blank_lines();
+            app.report_error("toggle blank lines", result);
+        }
+        KeyCode::Char('a') => {
+            let result = app.cycle_diff_algorithm();
+            app.report_error("change diff algorithm", result);
+        }
+
+        // Commit
+        KeyCode::Char('c') => app.open_commit_composer(),
+
+        // Stash panel
+        KeyCode::Char('z') => app.toggle_stash_panel(),
+
+        // Message log
+        KeyCode::Char('L') => app.toggle_log(),
 
         // Refresh
         KeyCode::Char('r') => {
-            if let Err(e) = app.refresh_status() {
-                eprintln!("Failed to refresh: {}", e);
-            }
+            let result = app.refresh_status();
+            app.report_error("refresh", result);
         }
 
         // Page navigation for diff view
@@ -55,15 +150,150 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) {
         // Home/End for file list
         KeyCode::Home => {
             app.selected_index = 0;
-            app.diff_scroll = 0;
+            app.reset_diff_position();
+        }
+        KeyCode::End if !app.rows.is_empty() => {
+            app.selected_index = app.rows.len() - 1;
+            app.reset_diff_position();
+        }
+
+        _ => {}
+    }
+}
+
+/// Handle keyboard events while the commit message editor is open
+fn handle_composer_key(app: &mut App, key: KeyEvent) {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+    match key.code {
+        KeyCode::Esc => app.close_commit_composer(),
+        KeyCode::Char('c') if ctrl => app.quit(),
+        KeyCode::Char('s') if ctrl => {
+            let result = app.submit_commit();
+            app.report_error("refresh after commit", result);
+        }
+        KeyCode::Char('a') if ctrl => app.toggle_commit_amend(),
+        _ => {
+            let Some(composer) = app.composer.as_mut() else {
+                return;
+            };
+            composer.error = None;
+            match key.code {
+                KeyCode::Char(c) if !ctrl => composer.insert_char(c),
+                KeyCode::Enter => composer.insert_newline(),
+                KeyCode::Backspace => composer.backspace(),
+                KeyCode::Delete => composer.delete(),
+                KeyCode::Left => composer.move_left(),
+                KeyCode::Right => composer.move_right(),
+                KeyCode::Up => composer.move_up(),
+                KeyCode::Down => composer.move_down(),
+                KeyCode::Home => composer.move_home(),
+                KeyCode::End => composer.move_end(),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Handle keyboard events while a discard waits for confirmation
+fn handle_confirm_discard_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => app.quit(),
+        KeyCode::Char('y') | KeyCode::Enter => {
+            let result = app.confirm_discard();
+            app.report_error("discard", result);
+        }
+        KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => app.cancel_discard(),
+        _ => {}
+    }
+}
+
+/// Handle keyboard events while the conflict resolution view is open
+fn handle_conflict_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => app.quit(),
+        KeyCode::Esc | KeyCode::Char('q') => app.close_conflict_view(),
+        KeyCode::Char('n') | KeyCode::Tab => app.select_next_conflict(),
+        KeyCode::Char('p') | KeyCode::BackTab => app.select_previous_conflict(),
+        KeyCode::Char('j') | KeyCode::Down => app.scroll_conflict(true),
+        KeyCode::Char('k') | KeyCode::Up => app.scroll_conflict(false),
+        KeyCode::PageDown => {
+            for _ in 0..10 {
+                app.scroll_conflict(true);
+            }
         }
-        KeyCode::End => {
-            if !app.files.is_empty() {
-                app.selected_index = app.files.len() - 1;
-                app.diff_scroll = 0;
+        KeyCode::PageUp => {
+            for _ in 0..10 {
+                app.scroll_conflict(false);
             }
         }
+        KeyCode::Char('o') => app.resolve_selected_conflict(Some(Resolution::Ours)),
+        KeyCode::Char('t') => app.resolve_selected_conflict(Some(Resolution::Theirs)),
+        KeyCode::Char('b') => app.resolve_selected_conflict(Some(Resolution::Both)),
+        KeyCode::Char('u') => app.resolve_selected_conflict(None),
+        KeyCode::Enter => {
+            let result = app.mark_conflict_resolved();
+            app.report_error("resolve conflict", result);
+        }
+        _ => {}
+    }
+}
 
+/// Handle keyboard events while the stash panel is open
+fn handle_stash_key(app: &mut App, key: KeyEvent) {
+    let result = match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.quit();
+            Ok(())
+        }
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('z') => {
+            app.toggle_stash_panel();
+            Ok(())
+        }
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.select_stash(true);
+            Ok(())
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.select_stash(false);
+            Ok(())
+        }
+        KeyCode::PageDown => {
+            app.scroll_stash_patch(true, 10);
+            Ok(())
+        }
+        KeyCode::PageUp => {
+            app.scroll_stash_patch(false, 10);
+            Ok(())
+        }
+        KeyCode::Char('s') => app.stash(StashMode::All),
+        KeyCode::Char('S') => app.stash(StashMode::Staged),
+        KeyCode::Char('f') => app.stash_selected_file(),
+        KeyCode::Char('a') => app.apply_stash(false),
+        KeyCode::Char('p') => app.apply_stash(true),
+        KeyCode::Char('d') => app.drop_stash(),
+        _ => Ok(()),
+    };
+    app.report_error("update stash", result);
+}
+
+/// Handle keyboard events while the message log panel is open
+fn handle_log_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => app.quit(),
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('L') => app.toggle_log(),
+        KeyCode::Char('k') | KeyCode::Up => app.scroll_log_up(),
+        KeyCode::Char('j') | KeyCode::Down => app.scroll_log_down(),
+        KeyCode::PageUp => {
+            for _ in 0..10 {
+                app.scroll_log_up();
+            }
+        }
+        KeyCode::PageDown => {
+            for _ in 0..10 {
+                app.scroll_log_down();
+            }
+        }
         _ => {}
     }
 }
@@ -101,12 +331,12 @@ pub fn handle_mouse_event(
         MouseEventKind::Down(_) => {
             if in_file_list {
                 if let Some((_, y, _, _)) = file_list_area {
-                    // Calculate which file was clicked (accounting for border)
+                    // Calculate which row was clicked (accounting for border and section headers)
                     let relative_row = mouse.row.saturating_sub(y + 1); // +1 for border
-                    let index = relative_row as usize;
-                    if index < app.files.len() {
-                        app.selected_index = index;
-                        app.diff_scroll = 0;
+                    let entries = app.list_entries();
+                    if let Some(ListEntry::Row(index)) = entries.get(relative_row as usize) {
+                        app.selected_index = *index;
+                        app.reset_diff_position();
                     }
                 }
                 app.active_panel = ActivePanel::FileList;
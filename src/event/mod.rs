@@ -2,11 +2,13 @@ mod handler;
 
 pub use handler::{handle_key_event, handle_mouse_event};
 
+use crate::git::{DiffKey, FileDiff, HeadInfo, StashEntry, StatusUpdate};
+use crate::ui::highlight::DiffHighlights;
 use color_eyre::Result;
 use crossterm::event::{Event as CrosstermEvent, KeyEvent, MouseEvent};
 use futures::{FutureExt, StreamExt};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 
 /// Application events
 #[derive(Debug, Clone)]
@@ -17,8 +19,34 @@ pub enum Event {
     Key(KeyEvent),
     /// Mouse input
     Mouse(MouseEvent),
-    /// Git repository change detected with changed file paths
+    /// Working tree files changed (paths relative to the repository root)
     GitChange(Vec<String>),
+    /// `.git/index` changed (staging from another tool)
+    IndexChange,
+    /// `.git/HEAD` changed (branch switch, detach)
+    HeadChange,
+    /// Branch, tag or remote refs changed (commit, fetch, tag creation)
+    RefsChange,
+    /// `.git/refs/stash` changed
+    StashChange,
+    /// Merge, rebase, cherry-pick, revert or bisect state changed
+    OperationChange,
+    /// The file watcher reported an error
+    WatchError(String),
+    /// The git worker finished a status request
+    StatusLoaded(StatusUpdate),
+    /// The git worker re-read HEAD
+    HeadLoaded(HeadInfo),
+    /// The git worker computed and highlighted a diff
+    DiffLoaded(DiffKey, FileDiff, Box<DiffHighlights>),
+    /// The git worker re-read the stash list
+    StashesLoaded(Vec<StashEntry>),
+    /// The git worker computed a stash patch: (stash id, patch lines)
+    StashPatchLoaded(String, Vec<String>),
+    /// A git worker request failed: (action, error message)
+    GitError(&'static str, String),
+    /// A config file was written, created or removed
+    ConfigChange,
     /// Terminal resize
     #[allow(dead_code)]
     Resize(u16, u16),
@@ -30,54 +58,63 @@ pub struct EventHandler {
     receiver: mpsc::UnboundedReceiver<Event>,
     /// Event sender (for external events like git changes)
     sender: mpsc::UnboundedSender<Event>,
+    /// Interval between `Event::Tick`s, changeable while running
+    tick_rate: watch::Sender<Duration>,
 }
 
 impl EventHandler {
-    /// Create a new event handler with the specified tick rate in milliseconds
-    pub fn new(tick_rate: u64) -> Self {
-        let tick_rate = Duration::from_millis(tick_rate);
+    /// Create a new event handler with the specified tick rate
+    pub fn new(tick_rate: Duration) -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
+        let (tick_rate, mut tick_rate_changes) = watch::channel(tick_rate);
 
         let event_sender = sender.clone();
         tokio::spawn(async move {
             let mut reader = crossterm::event::EventStream::new();
-            let mut tick = tokio::time::interval(tick_rate);
+            let mut tick = tokio::time::interval(*tick_rate_changes.borrow());
 
             loop {
                 let tick_delay = tick.tick();
                 let crossterm_event = reader.next().fuse();
 
                 tokio::select! {
+                    Ok(()) = tick_rate_changes.changed() => {
+                        tick = tokio::time::interval(*tick_rate_changes.borrow());
+                    }
                     _ = tick_delay => {
                         if event_sender.send(Event::Tick).is_err() {
                             break;
                         }
                     }
                     Some(Ok(evt)) = crossterm_event => {
-                        match evt {
-                            CrosstermEvent::Key(key) => {
-                                if event_sender.send(Event::Key(key)).is_err() {
-                                    break;
-                                }
-                            }
-                            CrosstermEvent::Mouse(mouse) => {
-                                if event_sender.send(Event::Mouse(mouse)).is_err() {
-                                    break;
-                                }
-                            }
-                            CrosstermEvent::Resize(w, h) => {
-                                if event_sender.send(Event::Resize(w, h)).is_err() {
-                                    break;
-                                }
-                            }
-                            _ => {}
+                        let event = match evt {
+                            CrosstermEvent::Key(key) => Event::Key(key),
+                            CrosstermEvent::Mouse(mouse) => Event::Mouse(mouse),
+                            CrosstermEvent::Resize(w, h) => Event::Resize(w, h),
+                            _ => continue,
+                        };
+                        if event_sender.send(event).is_err() {
+                            break;
                         }
                     }
                 }
             }
         });
 
-        Self { receiver, sender }
+        Self {
+            receiver,
+            sender,
+            tick_rate,
+        }
+    }
+
+    /// Tick at a new interval from now on
+    pub fn set_tick_rate(&self, tick_rate: Duration) {
+        self.tick_rate.send_if_modified(|current| {
+            let changed = *current != tick_rate;
+            *current = tick_rate;
+            changed
+        });
     }
 
     /// Get the event sender for external events
@@ -0,0 +1,402 @@
+use color_eyre::Result;
+use git2::{DiffOptions, Patch};
+use std::ops::Range;
+
+/// Agreed-on lines kept visible next to each conflict region; longer clean
+/// runs are folded
+pub const CLEAN_CONTEXT: usize = 3;
+
+/// A base line range a side changed, and the side's lines replacing it
+type Change = (Range<usize>, Range<usize>);
+
+/// Which side of a conflict region ends up in the merged file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    /// Our lines followed by theirs
+    Both,
+}
+
+impl Resolution {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Resolution::Ours => "ours",
+            Resolution::Theirs => "theirs",
+            Resolution::Both => "both",
+        }
+    }
+}
+
+/// Lines both sides changed differently. Lines keep their line endings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictRegion {
+    pub ours: Vec<String>,
+    pub base: Vec<String>,
+    pub theirs: Vec<String>,
+    pub resolution: Option<Resolution>,
+}
+
+impl ConflictRegion {
+    /// Lines of the chosen resolution, `None` while undecided
+    fn resolved(&self) -> Option<Vec<String>> {
+        Some(match self.resolution? {
+            Resolution::Ours => self.ours.clone(),
+            Resolution::Theirs => self.theirs.clone(),
+            Resolution::Both => {
+                let mut lines = self.ours.clone();
+                // Keep our unterminated last line from running into theirs
+                if let Some(last) = lines.last_mut().filter(|l| !l.ends_with('\n')) {
+                    if !self.theirs.is_empty() {
+                        last.push('\n');
+                    }
+                }
+                lines.extend(self.theirs.iter().cloned());
+                lines
+            }
+        })
+    }
+}
+
+/// A run of the merged file: agreed on by both sides, or in conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeSegment {
+    Clean(Vec<String>),
+    Conflict(ConflictRegion),
+}
+
+/// A row of the side-by-side conflict view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictRow<'a> {
+    /// An agreed-on line
+    Clean(&'a str),
+    /// This many agreed-on lines left out
+    Fold(usize),
+    /// Start of the n-th conflict region
+    Header(usize),
+    /// Our and their n-th line of a conflict region
+    Pair {
+        conflict: usize,
+        ours: Option<&'a str>,
+        theirs: Option<&'a str>,
+    },
+}
+
+/// Three-way merge of a conflicted file, split into clean runs and the
+/// regions left to resolve
+#[derive(Debug, Clone)]
+pub struct ConflictFile {
+    pub path: String,
+    pub segments: Vec<MergeSegment>,
+}
+
+impl ConflictFile {
+    /// Merge our and their version of `path` against the common ancestor
+    /// (empty when both sides added the file)
+    pub fn new(path: &str, base: &str, ours: &str, theirs: &str) -> Result<Self> {
+        let our_changes = changes(base, ours)?;
+        let their_changes = changes(base, theirs)?;
+        Ok(Self {
+            path: path.to_string(),
+            segments: merge(
+                &split_lines(base),
+                (&split_lines(ours), &our_changes),
+                (&split_lines(theirs), &their_changes),
+            ),
+        })
+    }
+
+    pub fn conflict_count(&self) -> usize {
+        self.regions().count()
+    }
+
+    pub fn unresolved_count(&self) -> usize {
+        self.regions().filter(|r| r.resolution.is_none()).count()
+    }
+
+    pub fn region(&self, index: usize) -> Option<&ConflictRegion> {
+        self.regions().nth(index)
+    }
+
+    pub fn region_mut(&mut self, index: usize) -> Option<&mut ConflictRegion> {
+        self.segments
+            .iter_mut()
+            .filter_map(|segment| match segment {
+                MergeSegment::Conflict(region) => Some(region),
+                MergeSegment::Clean(_) => None,
+            })
+            .nth(index)
+    }
+
+    /// The merged file, once every region is resolved
+    pub fn merged(&self) -> Option<String> {
+        let mut text = String::new();
+        for segment in &self.segments {
+            match segment {
+                MergeSegment::Clean(lines) => text.extend(lines.iter().map(String::as_str)),
+                MergeSegment::Conflict(region) => text.extend(region.resolved()?),
+            }
+        }
+        Some(text)
+    }
+
+    /// Rows of the conflict view: clean runs folded down to `CLEAN_CONTEXT`
+    /// lines around each region, regions as our and their lines side by side
+    pub fn rows(&self) -> Vec<ConflictRow<'_>> {
+        let mut rows = Vec::new();
+        let mut conflict = 0;
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                MergeSegment::Clean(lines) => {
+                    let head = if i > 0 { CLEAN_CONTEXT } else { 0 };
+                    let tail = if i + 1 < self.segments.len() {
+                        CLEAN_CONTEXT
+                    } else {
+                        0
+                    };
+                    if lines.len() <= head + tail + 1 {
+                        rows.extend(lines.iter().map(|l| ConflictRow::Clean(display(l))));
+                    } else {
+                        rows.extend(lines[..head].iter().map(|l| ConflictRow::Clean(display(l))));
+                        rows.push(ConflictRow::Fold(lines.len() - head - tail));
+                        rows.extend(
+                            lines[lines.len() - tail..]
+                                .iter()
+                                .map(|l| ConflictRow::Clean(display(l))),
+                        );
+                    }
+                }
+                MergeSegment::Conflict(region) => {
+                    rows.push(ConflictRow::Header(conflict));
+                    let height = region.ours.len().max(region.theirs.len());
+                    rows.extend((0..height).map(|n| ConflictRow::Pair {
+                        conflict,
+                        ours: region.ours.get(n).map(|l| display(l)),
+                        theirs: region.theirs.get(n).map(|l| display(l)),
+                    }));
+                    conflict += 1;
+                }
+            }
+        }
+        rows
+    }
+
+    /// Row of the header of the given conflict region
+    pub fn conflict_offset(&self, index: usize) -> usize {
+        self.rows()
+            .iter()
+            .position(|row| *row == ConflictRow::Header(index))
+            .unwrap_or(0)
+    }
+
+    fn regions(&self) -> impl Iterator<Item = &ConflictRegion> {
+        self.segments.iter().filter_map(|segment| match segment {
+            MergeSegment::Conflict(region) => Some(region),
+            MergeSegment::Clean(_) => None,
+        })
+    }
+}
+
+/// A line without its line ending
+fn display(line: &str) -> &str {
+    line.trim_end_matches(['\n', '\r'])
+}
+
+fn split_lines(text: &str) -> Vec<String> {
+    text.split_inclusive('\n').map(str::to_string).collect()
+}
+
+/// Changes of `side` against `base`, in order
+fn changes(base: &str, side: &str) -> Result<Vec<Change>> {
+    let mut opts = DiffOptions::new();
+    opts.context_lines(0).force_text(true);
+    let patch = Patch::from_buffers(
+        base.as_bytes(),
+        None,
+        side.as_bytes(),
+        None,
+        Some(&mut opts),
+    )?;
+    (0..patch.num_hunks())
+        .map(|i| {
+            let (hunk, _) = patch.hunk(i)?;
+            Ok((
+                line_range(hunk.old_start(), hunk.old_lines()),
+                line_range(hunk.new_start(), hunk.new_lines()),
+            ))
+        })
+        .collect()
+}
+
+/// Zero-based range of a hunk side; an empty side starts after the line it
+/// names
+fn line_range(start: u32, lines: u32) -> Range<usize> {
+    let start = if lines == 0 { start } else { start - 1 } as usize;
+    start..start + lines as usize
+}
+
+/// diff3-style merge: changes of either side whose base ranges overlap or
+/// touch form one region, which is clean when only one side changed it or
+/// both changed it the same way
+fn merge(
+    base: &[String],
+    ours: (&[String], &[Change]),
+    theirs: (&[String], &[Change]),
+) -> Vec<MergeSegment> {
+    let mut changes: Vec<(bool, &Range<usize>, &Range<usize>)> = ours
+        .1
+        .iter()
+        .map(|(b, s)| (true, b, s))
+        .chain(theirs.1.iter().map(|(b, s)| (false, b, s)))
+        .collect();
+    changes.sort_by_key(|(_, b, _)| (b.start, b.end));
+
+    let mut segments = Vec::new();
+    let mut clean_from = 0;
+    let mut i = 0;
+    while i < changes.len() {
+        let lo = changes[i].1.start;
+        let mut hi = changes[i].1.end;
+        let mut j = i + 1;
+        while j < changes.len() && changes[j].1.start <= hi {
+            hi = hi.max(changes[j].1.end);
+            j += 1;
+        }
+        let group = &changes[i..j];
+        i = j;
+
+        // Lines a side has in place of base[lo..hi]
+        let side_lines = |is_ours: bool, lines: &[String]| -> Option<Vec<String>> {
+            let hunks: Vec<_> = group.iter().filter(|(o, _, _)| *o == is_ours).collect();
+            let (_, first_base, first_side) = hunks.first()?;
+            let (_, last_base, last_side) = hunks.last()?;
+            let start = first_side.start - (first_base.start - lo);
+            let end = last_side.end + (hi - last_base.end);
+            Some(lines[start..end].to_vec())
+        };
+
+        push_clean(&mut segments, &base[clean_from..lo]);
+        clean_from = hi;
+        match (side_lines(true, ours.0), side_lines(false, theirs.0)) {
+            (Some(ours), Some(theirs)) if ours != theirs => {
+                segments.push(MergeSegment::Conflict(ConflictRegion {
+                    ours,
+                    base: base[lo..hi].to_vec(),
+                    theirs,
+                    resolution: None,
+                }))
+            }
+            (Some(lines), _) | (None, Some(lines)) => push_clean(&mut segments, &lines),
+            (None, None) => unreachable!("every group has a change"),
+        }
+    }
+    push_clean(&mut segments, &base[clean_from..]);
+    segments
+}
+
+/// Append clean lines, joining them to a preceding clean run
+fn push_clean(segments: &mut Vec<MergeSegment>, lines: &[String]) {
+    if lines.is_empty() {
+        return;
+    }
+    match segments.last_mut() {
+        Some(MergeSegment::Clean(run)) => run.extend_from_slice(lines),
+        _ => segments.push(MergeSegment::Clean(lines.to_vec())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(edits: &[(usize, &str)]) -> String {
+        (1..=20)
+            .map(|i| match edits.iter().find(|(n, _)| *n == i) {
+                Some((_, text)) => format!("{}\n", text),
+                None => format!("line {}\n", i),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_one_sided_changes_merge_cleanly() {
+        let base = numbered(&[]);
+        let ours = numbered(&[(3, "ours 3")]);
+        let theirs = numbered(&[(15, "theirs 15")]);
+        let file = ConflictFile::new("f", &base, &ours, &theirs).unwrap();
+        assert_eq!(file.conflict_count(), 0);
+        assert_eq!(
+            file.merged().unwrap(),
+            numbered(&[(3, "ours 3"), (15, "theirs 15")])
+        );
+    }
+
+    #[test]
+    fn test_overlapping_changes_conflict_until_resolved() {
+        let base = numbered(&[]);
+        let ours = numbered(&[(5, "ours 5"), (12, "same")]);
+        let theirs = numbered(&[(5, "theirs 5"), (12, "same")]);
+        let mut file = ConflictFile::new("f", &base, &ours, &theirs).unwrap();
+
+        // The identical change to line 12 is not a conflict
+        assert_eq!(file.conflict_count(), 1);
+        let region = file.region(0).unwrap();
+        assert_eq!(region.ours, ["ours 5\n"]);
+        assert_eq!(region.base, ["line 5\n"]);
+        assert_eq!(region.theirs, ["theirs 5\n"]);
+        assert_eq!(file.merged(), None);
+
+        file.region_mut(0).unwrap().resolution = Some(Resolution::Theirs);
+        assert_eq!(file.unresolved_count(), 0);
+        assert_eq!(
+            file.merged().unwrap(),
+            numbered(&[(5, "theirs 5"), (12, "same")])
+        );
+
+        file.region_mut(0).unwrap().resolution = Some(Resolution::Both);
+        assert!(file
+            .merged()
+            .unwrap()
+            .contains("line 4\nours 5\ntheirs 5\nline 6\n"));
+    }
+
+    #[test]
+    fn test_both_added_without_trailing_newline() {
+        let mut file = ConflictFile::new("f", "", "ours", "theirs\n").unwrap();
+        file.region_mut(0).unwrap().resolution = Some(Resolution::Both);
+        assert_eq!(file.merged().unwrap(), "ours\ntheirs\n");
+    }
+
+    #[test]
+    fn test_rows_fold_clean_runs_around_conflicts() {
+        let base = numbered(&[]);
+        let ours = numbered(&[(10, "ours 10"), (11, "ours 11")]);
+        let theirs = numbered(&[(10, "theirs 10")]);
+        let file = ConflictFile::new("f", &base, &ours, &theirs).unwrap();
+        let rows = file.rows();
+
+        // Lines 1-9: fold of 6, then lines 7-9
+        assert_eq!(rows[0], ConflictRow::Fold(6));
+        assert_eq!(rows[1], ConflictRow::Clean("line 7"));
+        assert_eq!(file.conflict_offset(0), 4);
+        assert_eq!(
+            rows[5],
+            ConflictRow::Pair {
+                conflict: 0,
+                ours: Some("ours 10"),
+                theirs: Some("theirs 10"),
+            }
+        );
+        assert_eq!(
+            rows[6],
+            ConflictRow::Pair {
+                conflict: 0,
+                ours: Some("ours 11"),
+                theirs: Some("line 11"),
+            }
+        );
+        // Lines 12-14, then a fold of the remaining 6
+        assert_eq!(rows[10], ConflictRow::Fold(6));
+        assert_eq!(rows.len(), 11);
+    }
+}
@@ -0,0 +1,166 @@
+/// Git LFS pointer files are never larger than this
+pub const MAX_POINTER_SIZE: usize = 1024;
+
+/// Format and pixel size of an image, read from its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub format: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The object a Git LFS pointer file refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    /// sha256 of the stored object
+    pub oid: String,
+    pub size: u64,
+}
+
+/// Parse a Git LFS pointer ("version https://git-lfs.github.com/spec/v1",
+/// "oid sha256:...", "size ...")
+pub fn parse_lfs_pointer(content: &[u8]) -> Option<LfsPointer> {
+    if content.len() > MAX_POINTER_SIZE {
+        return None;
+    }
+    let text = std::str::from_utf8(content).ok()?;
+    let mut lines = text.lines();
+    if !lines
+        .next()?
+        .starts_with("version https://git-lfs.github.com/spec/")
+    {
+        return None;
+    }
+
+    let mut oid = None;
+    let mut size = None;
+    for line in lines {
+        if let Some(value) = line.strip_prefix("oid sha256:") {
+            oid = Some(value.to_string());
+        } else if let Some(value) = line.strip_prefix("size ") {
+            size = value.parse().ok();
+        }
+    }
+    Some(LfsPointer {
+        oid: oid?,
+        size: size?,
+    })
+}
+
+/// Recognize PNG, GIF, JPEG, BMP and WebP images from their first bytes
+pub fn image_info(content: &[u8]) -> Option<ImageInfo> {
+    let image = |format, width, height| {
+        Some(ImageInfo {
+            format,
+            width,
+            height,
+        })
+    };
+    let be16 =
+        |at: usize| Some(u16::from_be_bytes(content.get(at..at + 2)?.try_into().ok()?) as u32);
+    let le16 =
+        |at: usize| Some(u16::from_le_bytes(content.get(at..at + 2)?.try_into().ok()?) as u32);
+    let be32 = |at: usize| {
+        Some(u32::from_be_bytes(
+            content.get(at..at + 4)?.try_into().ok()?,
+        ))
+    };
+    let le32 = |at: usize| {
+        Some(u32::from_le_bytes(
+            content.get(at..at + 4)?.try_into().ok()?,
+        ))
+    };
+
+    if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return image("PNG", be32(16)?, be32(20)?);
+    }
+    if content.starts_with(b"GIF87a") || content.starts_with(b"GIF89a") {
+        return image("GIF", le16(6)?, le16(8)?);
+    }
+    if content.starts_with(b"BM") && content.len() >= 26 {
+        // Bottom-up bitmaps store a negative height
+        let height = (le32(22)? as i32).unsigned_abs();
+        return image("BMP", le32(18)?, height);
+    }
+    if content.starts_with(b"RIFF") && content.get(8..12) == Some(b"WEBP") {
+        return match content.get(12..16)? {
+            b"VP8X" => {
+                let width = le32(24)? & 0xFF_FFFF;
+                let height = le32(27)? & 0xFF_FFFF;
+                image("WebP", width + 1, height + 1)
+            }
+            b"VP8 " => image("WebP", le16(26)? & 0x3FFF, le16(28)? & 0x3FFF),
+            b"VP8L" => {
+                let bits = le32(21)?;
+                image("WebP", (bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1)
+            }
+            _ => None,
+        };
+    }
+    if content.starts_with(&[0xFF, 0xD8]) {
+        // Walk the segments up to the frame header
+        let mut at = 2;
+        while *content.get(at)? == 0xFF {
+            let marker = *content.get(at + 1)?;
+            match marker {
+                // Fill byte before the actual marker
+                0xFF => at += 1,
+                0xD0..=0xD9 | 0x01 => at += 2,
+                0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                    return image("JPEG", be16(at + 7)?, be16(at + 5)?);
+                }
+                _ => at += 2 + be16(at + 2)? as usize,
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lfs_pointer() {
+        let pointer = b"version https://git-lfs.github.com/spec/v1\n\
+            oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n\
+            size 12345\n";
+        let parsed = parse_lfs_pointer(pointer).unwrap();
+        assert!(parsed.oid.starts_with("4d7a2146"));
+        assert_eq!(parsed.size, 12345);
+
+        assert!(parse_lfs_pointer(b"version 1\noid sha256:ab\nsize 1\n").is_none());
+        assert!(parse_lfs_pointer(b"version https://git-lfs.github.com/spec/v1\n").is_none());
+    }
+
+    #[test]
+    fn test_image_info_reads_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&480u32.to_be_bytes());
+        assert_eq!(
+            image_info(&png),
+            Some(ImageInfo {
+                format: "PNG",
+                width: 640,
+                height: 480
+            })
+        );
+
+        let gif = b"GIF89a\x20\x00\x10\x00";
+        assert_eq!(image_info(gif).map(|i| (i.width, i.height)), Some((32, 16)));
+
+        // SOI, an APP0 segment of 4 bytes, then a baseline frame header
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01,
+            0x00, 0x02, 0x00,
+        ];
+        assert_eq!(
+            image_info(&jpeg).map(|i| (i.width, i.height)),
+            Some((512, 256))
+        );
+
+        assert!(image_info(b"plain text").is_none());
+        assert!(image_info(&png[..12]).is_none());
+    }
+}
@@ -0,0 +1,864 @@
+use super::content::ImageInfo;
+use std::ops::Range;
+
+/// Which side of a file's changes a diff describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffSource {
+    /// HEAD → index (changes that will be committed)
+    Staged,
+    /// Index → working tree (changes not yet staged)
+    Unstaged,
+}
+
+/// How the diff view lays out hunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffLayout {
+    /// Old and new lines interleaved in one column
+    #[default]
+    Unified,
+    /// Old lines on the left, new lines on the right
+    SideBySide,
+}
+
+impl DiffLayout {
+    pub fn toggle(self) -> Self {
+        match self {
+            DiffLayout::Unified => DiffLayout::SideBySide,
+            DiffLayout::SideBySide => DiffLayout::Unified,
+        }
+    }
+}
+
+/// Context lines shown around changes by default, like `git diff`
+pub const DEFAULT_CONTEXT_LINES: u32 = 3;
+
+/// Upper bound for `DiffSettings::context_lines`
+pub const MAX_CONTEXT_LINES: u32 = 50;
+
+/// Unchanged lines revealed per step when expanding context around a hunk
+pub const CONTEXT_EXPAND_STEP: u32 = 10;
+
+/// Minimum similarity in percent for a file to count as renamed or
+/// copied, like git's default for `-M`
+pub const DEFAULT_RENAME_THRESHOLD: u16 = 50;
+
+/// Diff lines kept when a diff is too large to show in full
+pub const DIFF_PREVIEW_LINES: usize = 2000;
+
+/// Git mode of a regular, non-executable file
+pub const REGULAR_FILE_MODE: u32 = 0o100644;
+
+/// Line matching algorithm. libgit2 has no histogram diff, so only its
+/// three algorithms are offered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffAlgorithm {
+    #[default]
+    Myers,
+    Minimal,
+    Patience,
+}
+
+impl DiffAlgorithm {
+    pub fn next(self) -> Self {
+        match self {
+            DiffAlgorithm::Myers => DiffAlgorithm::Minimal,
+            DiffAlgorithm::Minimal => DiffAlgorithm::Patience,
+            DiffAlgorithm::Patience => DiffAlgorithm::Myers,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DiffAlgorithm::Myers => "myers",
+            DiffAlgorithm::Minimal => "minimal",
+            DiffAlgorithm::Patience => "patience",
+        }
+    }
+}
+
+/// Options every diff and line count is computed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffSettings {
+    pub context_lines: u32,
+    /// Ignore changes in amount of whitespace (`git diff -b`)
+    pub ignore_whitespace: bool,
+    /// Ignore added or removed blank lines
+    pub ignore_blank_lines: bool,
+    pub algorithm: DiffAlgorithm,
+    /// Similarity in percent at which renames and copies are detected,
+    /// `None` to not detect them
+    pub rename_threshold: Option<u16>,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        Self {
+            context_lines: DEFAULT_CONTEXT_LINES,
+            ignore_whitespace: false,
+            ignore_blank_lines: false,
+            algorithm: DiffAlgorithm::default(),
+            rename_threshold: Some(DEFAULT_RENAME_THRESHOLD),
+        }
+    }
+}
+
+impl DiffSettings {
+    /// Whether some changes are hidden from diffs, making their hunks
+    /// unsafe to stage or discard
+    pub fn hides_changes(&self) -> bool {
+        self.ignore_whitespace || self.ignore_blank_lines
+    }
+
+    /// Short descriptions of the settings that differ from the defaults
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels = Vec::new();
+        if self.context_lines != DEFAULT_CONTEXT_LINES {
+            labels.push(format!("context {}", self.context_lines));
+        }
+        if self.ignore_whitespace {
+            labels.push("ignore whitespace".to_string());
+        }
+        if self.ignore_blank_lines {
+            labels.push("ignore blank lines".to_string());
+        }
+        if self.algorithm != DiffAlgorithm::default() {
+            labels.push(self.algorithm.label().to_string());
+        }
+        labels
+    }
+}
+
+/// Kind of a single diff line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Addition,
+    Deletion,
+    /// "\ No newline at end of file" marker for the preceding line
+    NoNewline,
+}
+
+impl LineKind {
+    pub fn from_origin(origin: char) -> Option<Self> {
+        match origin {
+            ' ' => Some(LineKind::Context),
+            '+' => Some(LineKind::Addition),
+            '-' => Some(LineKind::Deletion),
+            '=' | '>' | '<' => Some(LineKind::NoNewline),
+            _ => None,
+        }
+    }
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            LineKind::Context => " ",
+            LineKind::Addition => "+",
+            LineKind::Deletion => "-",
+            LineKind::NoNewline => "\\",
+        }
+    }
+}
+
+/// A single line inside a hunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    /// Line content without the trailing newline
+    pub content: String,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+    /// Whether the line ended with `\r\n`, written back in patches
+    pub crlf: bool,
+}
+
+/// One row of the side-by-side layout, as indices into `DiffHunk::lines`.
+/// Context lines appear on both sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitRow {
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+}
+
+/// A contiguous block of changes with its surrounding context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// The "@@ -a,b +c,d @@ ..." header line
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+impl DiffHunk {
+    /// Range tuple used to identify this hunk when re-diffing
+    pub fn range(&self) -> (u32, u32, u32, u32) {
+        (
+            self.old_start,
+            self.old_lines,
+            self.new_start,
+            self.new_lines,
+        )
+    }
+
+    /// First old line number the hunk covers. A side without lines is
+    /// anchored after `start`, as in "@@ -5,0 +6,2 @@".
+    fn first_old(&self) -> u32 {
+        if self.old_lines == 0 {
+            self.old_start + 1
+        } else {
+            self.old_start
+        }
+    }
+
+    fn first_new(&self) -> u32 {
+        if self.new_lines == 0 {
+            self.new_start + 1
+        } else {
+            self.new_start
+        }
+    }
+
+    /// New line number right after the hunk
+    fn end_new(&self) -> u32 {
+        self.first_new() + self.new_lines
+    }
+
+    /// Old line number minus new line number of the unchanged lines after the hunk
+    fn offset_after(&self) -> i64 {
+        (self.first_old() + self.old_lines) as i64 - self.end_new() as i64
+    }
+
+    /// Indices of the added and deleted lines
+    pub fn changed_lines(&self) -> Vec<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| matches!(line.kind, LineKind::Addition | LineKind::Deletion))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Recompute the line counts, starts and header after lines were added
+    fn recount(&mut self) {
+        let count = |side: fn(&DiffLine) -> Option<u32>| {
+            let numbers = self.lines.iter().filter_map(side);
+            (numbers.clone().count() as u32, numbers.min())
+        };
+        let (old_lines, old_first) = count(|l| l.old_lineno);
+        let (new_lines, new_first) = count(|l| l.new_lineno);
+        if let Some(first) = old_first {
+            self.old_start = first;
+        }
+        if let Some(first) = new_first {
+            self.new_start = first;
+        }
+        self.old_lines = old_lines;
+        self.new_lines = new_lines;
+
+        // Keep the enclosing function name git puts after the range
+        let section = self.header.splitn(3, "@@").nth(2).unwrap_or_default();
+        self.header = format!(
+            "@@ -{},{} +{},{} @@{}",
+            self.old_start, self.old_lines, self.new_start, self.new_lines, section
+        );
+    }
+
+    /// Pair up the hunk's lines for the side-by-side layout: each run of
+    /// deletions is aligned with the run of additions that follows it
+    pub fn split_rows(&self) -> Vec<SplitRow> {
+        let mut rows = Vec::with_capacity(self.lines.len());
+        let mut old_run: Vec<usize> = Vec::new();
+        let mut new_run: Vec<usize> = Vec::new();
+
+        let mut previous = LineKind::Context;
+        for (i, line) in self.lines.iter().enumerate() {
+            // A "no newline" marker belongs to the side of the line before it
+            let kind = match line.kind {
+                LineKind::NoNewline => previous,
+                kind => kind,
+            };
+            match kind {
+                LineKind::Deletion => {
+                    // Deletions after additions start a new change block
+                    if !new_run.is_empty() {
+                        pair_runs(&mut rows, &mut old_run, &mut new_run);
+                    }
+                    old_run.push(i);
+                }
+                LineKind::Addition => new_run.push(i),
+                LineKind::Context | LineKind::NoNewline => {
+                    pair_runs(&mut rows, &mut old_run, &mut new_run);
+                    rows.push(SplitRow {
+                        old: Some(i),
+                        new: Some(i),
+                    });
+                }
+            }
+            previous = kind;
+        }
+        pair_runs(&mut rows, &mut old_run, &mut new_run);
+
+        rows
+    }
+
+    /// Number of rendered rows of the hunk body in the given layout
+    pub fn row_count(&self, layout: DiffLayout) -> usize {
+        match layout {
+            DiffLayout::Unified => self.lines.len(),
+            DiffLayout::SideBySide => self.split_rows().len(),
+        }
+    }
+
+    /// Rendered row (within the hunk body) showing the given line
+    pub fn line_row(&self, line: usize, layout: DiffLayout) -> usize {
+        match layout {
+            DiffLayout::Unified => line,
+            DiffLayout::SideBySide => self
+                .split_rows()
+                .iter()
+                .position(|row| row.old == Some(line) || row.new == Some(line))
+                .unwrap_or(0),
+        }
+    }
+
+    /// Build a single-hunk patch containing only the selected changed lines.
+    ///
+    /// Unselected deletions are kept as context and unselected additions are
+    /// dropped, the same way `git add -p` edits a hunk. With `reverse` the
+    /// patch undoes the selected lines instead (for unstaging or discarding).
+    /// `modes` are the file's modes before and after the patch, `None` where
+    /// the file does not exist: the patch then creates the file, or deletes
+    /// it when every line goes. Returns `None` when the selection contains
+    /// no changed lines.
+    pub fn partial_patch(
+        &self,
+        path: &str,
+        selected: &[usize],
+        reverse: bool,
+        modes: (Option<u32>, Option<u32>),
+    ) -> Option<String> {
+        let (old_start, new_start) = if reverse {
+            (self.new_start, self.old_start)
+        } else {
+            (self.old_start, self.new_start)
+        };
+
+        let mut body = String::new();
+        let mut old_count = 0;
+        let mut new_count = 0;
+        let mut has_change = false;
+        let mut last_emitted = false;
+
+        for (i, line) in self.lines.iter().enumerate() {
+            let kind = match (line.kind, reverse) {
+                (LineKind::Addition, true) => LineKind::Deletion,
+                (LineKind::Deletion, true) => LineKind::Addition,
+                (kind, _) => kind,
+            };
+            let is_selected = selected.contains(&i);
+
+            let prefix = match kind {
+                LineKind::Context => ' ',
+                LineKind::Addition if is_selected => '+',
+                LineKind::Deletion if is_selected => '-',
+                LineKind::Deletion => ' ',
+                LineKind::Addition => {
+                    last_emitted = false;
+                    continue;
+                }
+                LineKind::NoNewline => {
+                    if last_emitted {
+                        body.push_str(&line.content);
+                        body.push('\n');
+                    }
+                    continue;
+                }
+            };
+
+            match prefix {
+                '+' => new_count += 1,
+                '-' => old_count += 1,
+                _ => {
+                    old_count += 1;
+                    new_count += 1;
+                }
+            }
+            has_change |= prefix != ' ';
+            last_emitted = true;
+
+            body.push(prefix);
+            body.push_str(&line.content);
+            body.push_str(if line.crlf { "\r\n" } else { "\n" });
+        }
+
+        if !has_change {
+            return None;
+        }
+
+        // A side the hunk emptied starts at line 0, which is only right if
+        // the selection left it empty
+        let start = |start: u32, count: u32| if count > 0 { start.max(1) } else { start };
+        let (old_start, new_start) = (start(old_start, old_count), start(new_start, new_count));
+        let header = match modes {
+            (None, mode) => {
+                let mode = mode.unwrap_or(REGULAR_FILE_MODE);
+                format!("new file mode {mode:o}\n--- /dev/null\n+++ b/{path}")
+            }
+            // Lines left over keep the file around
+            (Some(mode), None) if new_count == 0 => {
+                format!("deleted file mode {mode:o}\n--- a/{path}\n+++ /dev/null")
+            }
+            _ => format!("--- a/{path}\n+++ b/{path}"),
+        };
+        Some(format!(
+            "diff --git a/{path} b/{path}\n{header}\n@@ -{old_start},{old_count} +{new_start},{new_count} @@\n{body}"
+        ))
+    }
+}
+
+/// Emit rows pairing the pending deletion and addition runs, then clear them
+fn pair_runs(rows: &mut Vec<SplitRow>, old_run: &mut Vec<usize>, new_run: &mut Vec<usize>) {
+    for i in 0..old_run.len().max(new_run.len()) {
+        rows.push(SplitRow {
+            old: old_run.get(i).copied(),
+            new: new_run.get(i).copied(),
+        });
+    }
+    old_run.clear();
+    new_run.clear();
+}
+
+/// One side of a binary or Git LFS change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobSummary {
+    /// Blob id, or for LFS the sha256 of the stored object
+    pub oid: String,
+    pub size: u64,
+    /// Set when the content is a recognized image
+    pub image: Option<ImageInfo>,
+}
+
+/// What a diff shows besides (or instead of) its hunks. Sides are `None`
+/// where the file does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffNotice {
+    /// Binary content, which has no hunks
+    Binary {
+        old: Option<BlobSummary>,
+        new: Option<BlobSummary>,
+    },
+    /// Git LFS pointer files, summarized by the objects they point to
+    Lfs {
+        old: Option<BlobSummary>,
+        new: Option<BlobSummary>,
+    },
+    /// Only the first `shown` of `total` lines are kept
+    Truncated { shown: usize, total: usize },
+}
+
+/// Structured diff of a single file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    pub path: String,
+    pub source: DiffSource,
+    /// Path the file was renamed or copied from, diffed against instead of `path`
+    pub old_path: Option<String>,
+    /// How similar a renamed or copied file is to its source, in percent
+    pub similarity: Option<u16>,
+    pub hunks: Vec<DiffHunk>,
+    pub notice: Option<DiffNotice>,
+}
+
+impl FileDiff {
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty() && self.notice.is_none()
+    }
+
+    /// Whether only a preview of the diff was kept
+    pub fn is_truncated(&self) -> bool {
+        matches!(self.notice, Some(DiffNotice::Truncated { .. }))
+    }
+
+    /// Keep only the first `max_lines` hunk lines, so huge diffs stay cheap
+    /// to highlight and render
+    pub fn truncate(&mut self, max_lines: usize) {
+        let total: usize = self.hunks.iter().map(|h| h.lines.len()).sum();
+        if total <= max_lines {
+            return;
+        }
+
+        let mut budget = max_lines;
+        self.hunks.retain_mut(|hunk| {
+            if budget == 0 {
+                return false;
+            }
+            hunk.lines.truncate(budget);
+            budget -= hunk.lines.len();
+            true
+        });
+        self.notice = Some(DiffNotice::Truncated {
+            shown: max_lines,
+            total,
+        });
+    }
+
+    /// Rendered line offset of the given hunk's header
+    pub fn hunk_offset(&self, hunk_index: usize, layout: DiffLayout) -> usize {
+        self.hunks
+            .iter()
+            .take(hunk_index)
+            .map(|h| h.row_count(layout) + 1)
+            .sum()
+    }
+
+    /// Total number of rendered lines (hunk headers included)
+    pub fn line_count(&self, layout: DiffLayout) -> usize {
+        self.hunks.iter().map(|h| h.row_count(layout) + 1).sum()
+    }
+
+    /// New-side lines between the given hunk and its neighbor above or
+    /// below, at most `count` of them, nearest to the hunk first
+    pub fn context_range(&self, hunk_index: usize, above: bool, count: u32) -> Range<u32> {
+        let Some(hunk) = self.hunks.get(hunk_index) else {
+            return 0..0;
+        };
+        if above {
+            let floor = match hunk_index {
+                0 => 1,
+                i => self.hunks[i - 1].end_new(),
+            };
+            let first = hunk.first_new();
+            first.saturating_sub(count).max(floor)..first
+        } else {
+            let end = hunk.end_new();
+            let ceiling = self
+                .hunks
+                .get(hunk_index + 1)
+                .map_or(u32::MAX, DiffHunk::first_new);
+            end..end.saturating_add(count).min(ceiling)
+        }
+    }
+
+    /// Add the unchanged new-side lines whose numbers fall in `ranges` as
+    /// context, as far as they connect to a hunk. Hunks whose gap is
+    /// entirely revealed are merged. `new_side` holds the lines of the new
+    /// version of the file, split on `\n` with any `\r` kept, and
+    /// `newline_at_end` whether its last line ends with one.
+    pub fn expand_context(
+        &mut self,
+        ranges: &[Range<u32>],
+        new_side: &[String],
+        newline_at_end: bool,
+    ) {
+        let wanted = |n: u32| {
+            n >= 1 && n as usize <= new_side.len() && ranges.iter().any(|r| r.contains(&n))
+        };
+        let context = |n: u32, offset: i64| {
+            let text = &new_side[n as usize - 1];
+            let content = text.strip_suffix('\r');
+            DiffLine {
+                kind: LineKind::Context,
+                content: content.unwrap_or(text).to_string(),
+                old_lineno: Some((n as i64 + offset) as u32),
+                new_lineno: Some(n),
+                crlf: content.is_some(),
+            }
+        };
+
+        let mut expanded: Vec<DiffHunk> = Vec::with_capacity(self.hunks.len());
+        for mut hunk in std::mem::take(&mut self.hunks) {
+            let first = hunk.first_new();
+            let offset = hunk.first_old() as i64 - first as i64;
+
+            // Grow the previous hunk downwards into the gap
+            let mut below = expanded.last().map_or(1, DiffHunk::end_new);
+            let gap_start = below;
+            if let Some(previous) = expanded.last_mut() {
+                while below < first && wanted(below) {
+                    previous.lines.push(context(below, offset));
+                    below += 1;
+                }
+                if below == first && below > gap_start {
+                    previous.lines.append(&mut hunk.lines);
+                    previous.recount();
+                    continue;
+                }
+            }
+
+            // Grow this hunk upwards into what is left of the gap
+            let mut above = first;
+            while above > below && wanted(above - 1) {
+                above -= 1;
+            }
+            if above < first {
+                let mut lines: Vec<DiffLine> = (above..first).map(|n| context(n, offset)).collect();
+                lines.append(&mut hunk.lines);
+                hunk.lines = lines;
+            }
+            expanded.push(hunk);
+        }
+
+        if let Some(last) = expanded.last_mut() {
+            let offset = last.offset_after();
+            let mut below = last.end_new();
+            while wanted(below) {
+                last.lines.push(context(below, offset));
+                below += 1;
+            }
+            if below as usize > new_side.len() && below > last.end_new() && !newline_at_end {
+                last.lines.push(DiffLine {
+                    kind: LineKind::NoNewline,
+                    content: "\\ No newline at end of file".to_string(),
+                    old_lineno: None,
+                    new_lineno: None,
+                    crlf: false,
+                });
+            }
+        }
+
+        for hunk in &mut expanded {
+            hunk.recount();
+        }
+        self.hunks = expanded;
+    }
+
+    /// Find `line` again after the diff was recomputed, returning its
+    /// (hunk, line) position. Matches on kind and content, preferring the
+    /// candidate whose line numbers moved the least.
+    pub fn locate_line(&self, line: &DiffLine) -> Option<(usize, usize)> {
+        let distance = |a: Option<u32>, b: Option<u32>| match (a, b) {
+            (Some(a), Some(b)) => a.abs_diff(b),
+            _ => 0,
+        };
+
+        self.hunks
+            .iter()
+            .enumerate()
+            .flat_map(|(h, hunk)| hunk.lines.iter().enumerate().map(move |(l, c)| (h, l, c)))
+            .filter(|(_, _, c)| c.kind == line.kind && c.content == line.content)
+            .min_by_key(|(_, _, c)| {
+                distance(c.old_lineno, line.old_lineno) + distance(c.new_lineno, line.new_lineno)
+            })
+            .map(|(h, l, _)| (h, l))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(kind: LineKind, content: &str, old: Option<u32>, new: Option<u32>) -> DiffLine {
+        DiffLine {
+            kind,
+            content: content.to_string(),
+            old_lineno: old,
+            new_lineno: new,
+            crlf: false,
+        }
+    }
+
+    fn sample_hunk() -> DiffHunk {
+        DiffHunk {
+            header: "@@ -1,3 +1,3 @@".to_string(),
+            old_start: 1,
+            old_lines: 3,
+            new_start: 1,
+            new_lines: 3,
+            lines: vec![
+                line(LineKind::Context, "a", Some(1), Some(1)),
+                line(LineKind::Deletion, "b", Some(2), None),
+                line(LineKind::Deletion, "c", Some(3), None),
+                line(LineKind::Addition, "B", None, Some(2)),
+                line(LineKind::Addition, "C", None, Some(3)),
+            ],
+        }
+    }
+
+    /// Modes of a file that exists before and after a patch
+    const EXISTING: (Option<u32>, Option<u32>) = (Some(0o100644), Some(0o100644));
+
+    #[test]
+    fn test_partial_patch_creates_and_deletes_files() {
+        let hunk = DiffHunk {
+            header: "@@ -0,0 +1,2 @@".to_string(),
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: 2,
+            lines: vec![
+                line(LineKind::Addition, "a", None, Some(1)),
+                line(LineKind::Addition, "b", None, Some(2)),
+            ],
+        };
+        let patch = hunk
+            .partial_patch("f.txt", &[1], false, (None, Some(0o100755)))
+            .unwrap();
+        assert!(patch
+            .contains("new file mode 100755\n--- /dev/null\n+++ b/f.txt\n@@ -0,0 +1,1 @@\n+b\n"));
+
+        // Taking back every line deletes the file, some lines keep it
+        let created = (Some(REGULAR_FILE_MODE), None);
+        let patch = hunk.partial_patch("f.txt", &[0, 1], true, created).unwrap();
+        assert!(patch
+            .contains("deleted file mode 100644\n--- a/f.txt\n+++ /dev/null\n@@ -1,2 +0,0 @@\n"));
+        let patch = hunk.partial_patch("f.txt", &[0], true, created).unwrap();
+        assert!(patch.contains("--- a/f.txt\n+++ b/f.txt\n@@ -1,2 +1,1 @@\n-a\n b\n"));
+    }
+
+    #[test]
+    fn test_partial_patch_selected_pair() {
+        let patch = sample_hunk()
+            .partial_patch("f.txt", &[1, 3], false, EXISTING)
+            .unwrap();
+        assert!(patch.contains("@@ -1,3 +1,3 @@\n a\n-b\n c\n+B\n"));
+        assert!(!patch.contains("+C"));
+    }
+
+    #[test]
+    fn test_partial_patch_only_addition() {
+        let patch = sample_hunk()
+            .partial_patch("f.txt", &[4], false, EXISTING)
+            .unwrap();
+        assert!(patch.contains("@@ -1,3 +1,4 @@\n a\n b\n c\n+C\n"));
+    }
+
+    #[test]
+    fn test_partial_patch_reverse_swaps_sides() {
+        let patch = sample_hunk()
+            .partial_patch("f.txt", &[3], true, EXISTING)
+            .unwrap();
+        // Unselected "-b"/"-c" become additions in reverse and are dropped
+        assert!(patch.contains("@@ -1,3 +1,2 @@\n a\n-B\n C\n"));
+    }
+
+    #[test]
+    fn test_partial_patch_context_only_is_none() {
+        assert!(sample_hunk()
+            .partial_patch("f.txt", &[0], false, EXISTING)
+            .is_none());
+    }
+
+    #[test]
+    fn test_split_rows_pair_deletions_with_additions() {
+        let mut hunk = sample_hunk();
+        hunk.lines
+            .push(line(LineKind::Addition, "D", None, Some(4)));
+        hunk.lines.push(line(
+            LineKind::NoNewline,
+            "\\ No newline at end of file",
+            None,
+            None,
+        ));
+
+        let rows: Vec<(Option<usize>, Option<usize>)> =
+            hunk.split_rows().iter().map(|r| (r.old, r.new)).collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some(0), Some(0)),
+                (Some(1), Some(3)),
+                (Some(2), Some(4)),
+                (None, Some(5)),
+                (None, Some(6)),
+            ]
+        );
+        assert_eq!(hunk.line_row(5, DiffLayout::SideBySide), 3);
+        assert_eq!(hunk.row_count(DiffLayout::Unified), 7);
+    }
+
+    #[test]
+    fn test_expand_context_grows_and_merges_hunks() {
+        // Lines 2 and 9 of eleven changed, with one line of context each
+        let new_side: Vec<String> = (1..=11).map(|i| format!("l{}", i)).collect();
+        let same = |n: u32| line(LineKind::Context, &format!("l{}", n), Some(n), Some(n));
+        let added = |n: u32| line(LineKind::Addition, &format!("l{}", n), None, Some(n));
+        let hunk = |start: u32, changed: &str| DiffHunk {
+            header: format!("@@ -{},3 +{},3 @@ fn f()", start - 1, start - 1),
+            old_start: start - 1,
+            old_lines: 3,
+            new_start: start - 1,
+            new_lines: 3,
+            lines: vec![
+                same(start - 1),
+                line(LineKind::Deletion, changed, Some(start), None),
+                added(start),
+                same(start + 1),
+            ],
+        };
+        let mut diff = FileDiff {
+            path: "f.txt".to_string(),
+            source: DiffSource::Unstaged,
+            old_path: None,
+            similarity: None,
+            hunks: vec![hunk(2, "old2"), hunk(9, "old9")],
+            notice: None,
+        };
+
+        let above = diff.context_range(1, true, 2);
+        assert_eq!(above, 6..8);
+        assert_eq!(diff.context_range(0, false, 100), 4..8);
+        let mut grown = diff.clone();
+        grown.expand_context(&[above], &new_side, false);
+        assert_eq!(grown.hunks.len(), 2);
+        assert_eq!(grown.hunks[1].header, "@@ -6,5 +6,5 @@ fn f()");
+        assert_eq!(grown.hunks[1].lines[0].content, "l6");
+
+        // Revealing the whole gap and the end of the file
+        diff.expand_context(&[4..8, 10..20], &new_side, false);
+        assert_eq!(diff.hunks.len(), 1);
+        let merged = &diff.hunks[0];
+        assert_eq!(merged.range(), (1, 11, 1, 11));
+        assert_eq!(merged.lines.len(), 14);
+        assert_eq!(merged.lines[4].old_lineno, Some(4));
+        assert_eq!(merged.lines[12].content, "l11");
+        assert_eq!(merged.lines[13].kind, LineKind::NoNewline);
+    }
+
+    #[test]
+    fn test_truncate_keeps_a_preview() {
+        let mut diff = FileDiff {
+            path: "f.txt".to_string(),
+            source: DiffSource::Unstaged,
+            old_path: None,
+            similarity: None,
+            hunks: vec![sample_hunk(), sample_hunk(), sample_hunk()],
+            notice: None,
+        };
+        diff.truncate(20);
+        assert_eq!(diff.hunks.len(), 3);
+        assert!(!diff.is_truncated());
+
+        diff.truncate(7);
+        assert_eq!(diff.hunks.len(), 2);
+        assert_eq!(diff.hunks[1].lines.len(), 2);
+        assert_eq!(
+            diff.notice,
+            Some(DiffNotice::Truncated {
+                shown: 7,
+                total: 15
+            })
+        );
+    }
+
+    #[test]
+    fn test_locate_line_prefers_nearest() {
+        let mut hunk = sample_hunk();
+        hunk.lines
+            .push(line(LineKind::Addition, "B", None, Some(40)));
+        let diff = FileDiff {
+            path: "f.txt".to_string(),
+            source: DiffSource::Unstaged,
+            old_path: None,
+            similarity: None,
+            hunks: vec![hunk],
+            notice: None,
+        };
+
+        let target = line(LineKind::Addition, "B", None, Some(38));
+        assert_eq!(diff.locate_line(&target), Some((0, 5)));
+        let missing = line(LineKind::Addition, "Z", None, Some(2));
+        assert_eq!(diff.locate_line(&missing), None);
+    }
+}
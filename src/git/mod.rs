@@ -1,5 +1,19 @@
+mod conflict;
+mod content;
+mod diff;
 mod repository;
+mod stash;
 mod watcher;
+mod word_diff;
+mod worker;
 
-pub use repository::{FileChange, FileStatus, GitRepository};
+pub use conflict::{ConflictFile, ConflictRow, Resolution, CLEAN_CONTEXT};
+pub use diff::{
+    BlobSummary, DiffHunk, DiffLayout, DiffLine, DiffNotice, DiffSettings, DiffSource, FileDiff,
+    LineKind, CONTEXT_EXPAND_STEP, MAX_CONTEXT_LINES,
+};
+pub use repository::{sort_by_mtime, FileChange, FileStatus, GitRepository, HeadInfo};
+pub use stash::{StashEntry, StashMode};
 pub use watcher::GitWatcher;
+pub use word_diff::{hunk_emphasis, Granularity};
+pub use worker::{DiffCache, DiffKey, GitRequest, GitWorker, RefreshPlanner, StatusUpdate};
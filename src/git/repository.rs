@@ -1,15 +1,37 @@
+use super::conflict::ConflictFile;
+use super::content::{image_info, parse_lfs_pointer, MAX_POINTER_SIZE};
+use super::diff::{
+    BlobSummary, DiffAlgorithm, DiffHunk, DiffLine, DiffNotice, DiffSettings, DiffSource, FileDiff,
+    LineKind, REGULAR_FILE_MODE,
+};
+use super::stash::{stash_branch, StashEntry, StashMode};
+use color_eyre::eyre::eyre;
 use color_eyre::Result;
-use git2::{DiffOptions, Repository, Status, StatusOptions};
+use git2::build::CheckoutBuilder;
+use git2::{
+    ApplyLocation, ApplyOptions, Commit, Delta, Diff, DiffDelta, DiffFile, DiffFindOptions,
+    DiffFormat, DiffOptions, ErrorCode, IndexEntry, ObjectType, Oid, Repository, RepositoryState,
+    Signature, StashApplyOptions, StashFlags, StashSaveOptions, Status, StatusOptions, Tree,
+};
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
+/// Leading bytes of a file read to recognize images and LFS pointers
+const HEADER_BYTES: u64 = 64 * 1024;
+
 /// File status types
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FileStatus {
     Modified,
     Added,
     Deleted,
     Renamed,
+    Copied,
     Untracked,
     Conflicted,
 }
@@ -21,97 +43,368 @@ impl FileStatus {
             FileStatus::Added => "A",
             FileStatus::Deleted => "D",
             FileStatus::Renamed => "R",
+            FileStatus::Copied => "C",
             FileStatus::Untracked => "U",
             FileStatus::Conflicted => "!",
         }
     }
 }
 
+/// Number of lines added and deleted by one side of a change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineStats {
+    pub additions: i32,
+    pub deletions: i32,
+}
+
 /// Represents a changed file
 #[derive(Debug, Clone)]
 pub struct FileChange {
     pub path: String,
-    pub status: FileStatus,
-    pub staged: bool,
-    /// Number of lines added
-    pub additions: i32,
-    /// Number of lines deleted
-    pub deletions: i32,
+    /// Change between HEAD and the index, if any
+    pub index_status: Option<FileStatus>,
+    /// Change between the index and the working tree, if any
+    pub worktree_status: Option<FileStatus>,
+    /// Line counts of the staged change
+    pub staged_stats: LineStats,
+    /// Line counts of the unstaged change
+    pub unstaged_stats: LineStats,
     /// File modification time from filesystem
     pub modified_time: Option<SystemTime>,
+    /// Source of a staged rename or copy
+    pub index_old_path: Option<String>,
+    /// Source of a rename in the working tree
+    pub worktree_old_path: Option<String>,
+}
+
+impl FileChange {
+    /// Status of the given side of this file's changes
+    pub fn status(&self, source: DiffSource) -> Option<FileStatus> {
+        match source {
+            DiffSource::Staged => self.index_status,
+            DiffSource::Unstaged => self.worktree_status,
+        }
+    }
+
+    /// Path the given side was renamed or copied from
+    pub fn old_path(&self, source: DiffSource) -> Option<&str> {
+        match source {
+            DiffSource::Staged => self.index_old_path.as_deref(),
+            DiffSource::Unstaged => self.worktree_old_path.as_deref(),
+        }
+    }
+
+    pub fn stats(&self, source: DiffSource) -> LineStats {
+        match source {
+            DiffSource::Staged => self.staged_stats,
+            DiffSource::Unstaged => self.unstaged_stats,
+        }
+    }
+}
+
+/// Where HEAD points and how it relates to its upstream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeadInfo {
+    /// Checked out branch, or `None` when HEAD is detached
+    pub branch: Option<String>,
+    /// Short id of the HEAD commit (`None` on an unborn branch)
+    pub commit: Option<String>,
+    /// Summary line of the HEAD commit
+    pub summary: Option<String>,
+    /// Upstream tracking branch, e.g. "origin/main"
+    pub upstream: Option<String>,
+    /// Commits on HEAD not on the upstream
+    pub ahead: usize,
+    /// Commits on the upstream not on HEAD
+    pub behind: usize,
+    /// In-progress operation such as "MERGING" or "REBASING 2/5"
+    pub operation: Option<String>,
 }
 
 /// Git repository wrapper
 pub struct GitRepository {
     repo: Repository,
     path: PathBuf,
+    /// Options for every diff and line count
+    diff_settings: DiffSettings,
 }
 
 impl GitRepository {
     pub fn new(path: PathBuf) -> Result<Self> {
         let repo = Repository::discover(&path)?;
         let path = repo.workdir().unwrap_or(path.as_path()).to_path_buf();
-        Ok(Self { repo, path })
+        Ok(Self {
+            repo,
+            path,
+            diff_settings: DiffSettings::default(),
+        })
+    }
+
+    pub fn set_diff_settings(&mut self, settings: DiffSettings) {
+        self.diff_settings = settings;
     }
 
     pub fn path(&self) -> &Path {
         &self.path
     }
 
-    pub fn current_branch(&self) -> Result<String> {
-        let head = self.repo.head()?;
-        let name = head
-            .shorthand()
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| "HEAD".to_string());
-        Ok(name)
+    /// Describe HEAD: branch or detached commit, upstream tracking and any
+    /// operation (merge, rebase, cherry-pick, ...) in progress
+    pub fn head_info(&self) -> Result<HeadInfo> {
+        let mut info = HeadInfo {
+            operation: self.operation_state(),
+            ..HeadInfo::default()
+        };
+
+        let head = match self.repo.head() {
+            Ok(head) => head,
+            Err(e) if e.code() == ErrorCode::UnbornBranch => {
+                // No commits yet: read the branch name from the symbolic HEAD
+                let head_ref = self.repo.find_reference("HEAD")?;
+                info.branch = head_ref
+                    .symbolic_target()
+                    .map(|t| t.trim_start_matches("refs/heads/").to_string());
+                return Ok(info);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let head_oid = head.target();
+        info.commit = head_oid.map(|oid| oid.to_string()[..7].to_string());
+        info.summary = head
+            .peel_to_commit()
+            .ok()
+            .and_then(|c| c.summary().map(|s| s.to_string()));
+        if !head.is_branch() {
+            return Ok(info);
+        }
+        info.branch = head.shorthand().map(|s| s.to_string());
+
+        let branch = git2::Branch::wrap(head);
+        if let Ok(upstream) = branch.upstream() {
+            info.upstream = upstream.name().ok().flatten().map(|s| s.to_string());
+            // Without the upstream's history (shallow clone, pruned
+            // objects) the header just shows no counts
+            let counts = head_oid
+                .zip(upstream.get().target())
+                .and_then(|(local, remote)| self.repo.graph_ahead_behind(local, remote).ok());
+            if let Some((ahead, behind)) = counts {
+                info.ahead = ahead;
+                info.behind = behind;
+            }
+        }
+
+        Ok(info)
+    }
+
+    /// Label of the operation in progress, with rebase progress when known
+    fn operation_state(&self) -> Option<String> {
+        let label = match self.repo.state() {
+            RepositoryState::Clean => return None,
+            RepositoryState::Merge => "MERGING",
+            RepositoryState::Revert | RepositoryState::RevertSequence => "REVERTING",
+            RepositoryState::CherryPick | RepositoryState::CherryPickSequence => "CHERRY-PICKING",
+            RepositoryState::Bisect => "BISECTING",
+            RepositoryState::ApplyMailbox => "AM",
+            RepositoryState::ApplyMailboxOrRebase
+            | RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge => "REBASING",
+        };
+
+        // Rebase progress lives in rebase-merge/{msgnum,end} or rebase-apply/{next,last}
+        let git_dir = self.repo.path();
+        let read_number = |path: PathBuf| -> Option<usize> {
+            std::fs::read_to_string(path).ok()?.trim().parse().ok()
+        };
+        let progress = read_number(git_dir.join("rebase-merge/msgnum"))
+            .zip(read_number(git_dir.join("rebase-merge/end")))
+            .or_else(|| {
+                read_number(git_dir.join("rebase-apply/next"))
+                    .zip(read_number(git_dir.join("rebase-apply/last")))
+            });
+
+        Some(match progress {
+            Some((current, total)) => format!("{} {}/{}", label, current, total),
+            None => label.to_string(),
+        })
     }
 
     pub fn get_status(&self) -> Result<Vec<FileChange>> {
+        let mut files = self.scan_status(&[])?;
+        if files
+            .iter()
+            .any(|f| f.index_status == Some(FileStatus::Added))
+        {
+            self.detect_copies(&mut files)?;
+        }
+
+        sort_by_mtime(&mut files);
+        Ok(files)
+    }
+
+    /// Status of the whole repository, or only of the exact `paths` when
+    /// given, with renames paired up among the scanned paths
+    fn scan_status(&self, paths: &[String]) -> Result<Vec<FileChange>> {
         let mut opts = StatusOptions::new();
         opts.include_untracked(true)
             .recurse_untracked_dirs(true)
             .include_ignored(false);
+        if let Some(threshold) = self.diff_settings.rename_threshold {
+            opts.renames_head_to_index(true)
+                .renames_index_to_workdir(true)
+                .rename_threshold(threshold);
+        }
+        if !paths.is_empty() {
+            opts.disable_pathspec_match(true);
+            for path in paths {
+                opts.pathspec(path);
+            }
+        }
 
         let statuses = self.repo.statuses(Some(&mut opts))?;
-        let mut files = Vec::new();
-
-        for entry in statuses.iter() {
-            let path = entry.path().unwrap_or("").to_string();
-            let status = entry.status();
-
-            let (file_status, staged) = Self::parse_status(status);
-
-            if let Some(file_status) = file_status {
-                let (additions, deletions) = self.get_line_changes(&path).unwrap_or((0, 0));
-                // Get file modification time from filesystem
-                let modified_time = self.get_file_mtime(&path);
-                files.push(FileChange {
-                    path,
-                    status: file_status,
-                    staged,
-                    additions,
-                    deletions,
-                    modified_time,
-                });
+        let files = statuses
+            .iter()
+            .filter_map(|entry| {
+                // `entry.path()` is the old path of renamed entries
+                let new_path = |delta: Option<DiffDelta<'_>>| {
+                    delta.and_then(|d| d.new_file().path().map(path_string))
+                };
+                let old_path = |delta: Option<DiffDelta<'_>>| {
+                    delta
+                        .filter(|d| d.status() == Delta::Renamed)
+                        .and_then(|d| d.old_file().path().map(path_string))
+                };
+                let path = new_path(entry.index_to_workdir())
+                    .or_else(|| new_path(entry.head_to_index()))
+                    .unwrap_or_else(|| entry.path().unwrap_or("").to_string());
+                let old_paths = (
+                    old_path(entry.head_to_index()),
+                    old_path(entry.index_to_workdir()),
+                );
+                self.file_change(path, entry.status(), old_paths)
+            })
+            .collect();
+        Ok(files)
+    }
+
+    /// Mark staged new files that are copies of a file changed in the same
+    /// commit, like `git diff -C` (status itself only detects renames)
+    fn detect_copies(&self, files: &mut [FileChange]) -> Result<()> {
+        let Some(threshold) = self.diff_settings.rename_threshold else {
+            return Ok(());
+        };
+
+        let head = self.head_tree()?;
+        let mut diff = self.repo.diff_tree_to_index(head.as_ref(), None, None)?;
+        let mut find = DiffFindOptions::new();
+        find.copies(true).copy_threshold(threshold);
+        diff.find_similar(Some(&mut find))?;
+
+        for delta in diff.deltas().filter(|d| d.status() == Delta::Copied) {
+            let (Some(old), Some(new)) = (delta.old_file().path(), delta.new_file().path()) else {
+                continue;
+            };
+            let new = path_string(new);
+            if let Some(file) = files.iter_mut().find(|f| f.path == new) {
+                let old = path_string(old);
+                file.staged_stats = self
+                    .get_line_changes(&file.path, Some(&old), DiffSource::Staged)
+                    .unwrap_or_default();
+                file.index_status = Some(FileStatus::Copied);
+                file.index_old_path = Some(old);
             }
         }
+        Ok(())
+    }
 
-        // Sort by modification time (newest first), then by path for files without mtime
-        files.sort_by(|a, b| {
-            match (&a.modified_time, &b.modified_time) {
-                // Both have modification times: sort by time descending (newest first)
-                (Some(t_a), Some(t_b)) => t_b.cmp(t_a),
-                // Only a has time: a comes first
-                (Some(_), None) => std::cmp::Ordering::Less,
-                // Only b has time: b comes first
-                (None, Some(_)) => std::cmp::Ordering::Greater,
-                // Neither has time: sort by path
-                (None, None) => a.path.cmp(&b.path),
-            }
-        });
+    /// Re-query the status of just the given paths (relative to the repo root).
+    ///
+    /// Returns the new state of each path, `None` meaning it no longer has
+    /// changes. Fails for paths `status_file` cannot answer for (directories
+    /// and paths outside the repository), in which case callers should fall
+    /// back to a full `get_status`.
+    ///
+    /// With rename detection on, a deleted file is paired with a new file
+    /// among the same paths, as happens when both sides of a move are
+    /// reported together. A partner outside of them is only found by the
+    /// next full status.
+    pub fn get_status_for_paths(
+        &self,
+        paths: &[String],
+    ) -> Result<Vec<(String, Option<FileChange>)>> {
+        let mut changes = Vec::with_capacity(paths.len());
+        let mut deleted = false;
+        for path in paths {
+            let status = match self.repo.status_file(Path::new(path)) {
+                Ok(status) => status,
+                // Deleted untracked files simply have no status anymore
+                Err(e) if e.code() == ErrorCode::NotFound && !self.path.join(path).exists() => {
+                    Status::CURRENT
+                }
+                Err(e) => return Err(e.into()),
+            };
+            deleted |= status.intersects(Status::INDEX_DELETED | Status::WT_DELETED);
+            changes.push((path.clone(), status));
+        }
 
-        Ok(files)
+        if deleted && self.diff_settings.rename_threshold.is_some() {
+            // The source of a rename is listed under its new path only
+            let mut scanned = self.scan_status(paths)?;
+            return Ok(paths
+                .iter()
+                .map(|path| {
+                    let index = scanned.iter().position(|f| &f.path == path);
+                    (path.clone(), index.map(|i| scanned.swap_remove(i)))
+                })
+                .collect());
+        }
+        Ok(changes
+            .into_iter()
+            .map(|(path, status)| {
+                let change = self.file_change(path.clone(), status, (None, None));
+                (path, change)
+            })
+            .collect())
+    }
+
+    /// Build the `FileChange` for a path, or `None` if it has no changes.
+    /// `old_paths` are the (index, working tree) rename sources.
+    fn file_change(
+        &self,
+        path: String,
+        status: Status,
+        (index_old_path, worktree_old_path): (Option<String>, Option<String>),
+    ) -> Option<FileChange> {
+        let (index_status, worktree_status) = Self::parse_status(status);
+        if index_status.is_none() && worktree_status.is_none() {
+            return None;
+        }
+
+        let staged_stats = match index_status {
+            Some(_) => self
+                .get_line_changes(&path, index_old_path.as_deref(), DiffSource::Staged)
+                .unwrap_or_default(),
+            None => LineStats::default(),
+        };
+        let unstaged_stats = match worktree_status {
+            Some(_) => self
+                .get_line_changes(&path, worktree_old_path.as_deref(), DiffSource::Unstaged)
+                .unwrap_or_default(),
+            None => LineStats::default(),
+        };
+        // Get file modification time from filesystem
+        let modified_time = self.get_file_mtime(&path);
+        Some(FileChange {
+            path,
+            index_status,
+            worktree_status,
+            staged_stats,
+            unstaged_stats,
+            modified_time,
+            index_old_path,
+            worktree_old_path,
+        })
     }
 
     /// Get file modification time from filesystem
@@ -122,115 +415,823 @@ impl GitRepository {
             .and_then(|m| m.modified().ok())
     }
 
-    fn parse_status(status: Status) -> (Option<FileStatus>, bool) {
-        // Check for conflicts first
+    /// Split a git2 status into its (index, working tree) states
+    fn parse_status(status: Status) -> (Option<FileStatus>, Option<FileStatus>) {
+        // Conflicts are reported on the working tree side only
         if status.is_conflicted() {
-            return (Some(FileStatus::Conflicted), false);
+            return (None, Some(FileStatus::Conflicted));
         }
 
-        // Check staged changes
-        if status.is_index_new() {
-            return (Some(FileStatus::Added), true);
+        let index = if status.is_index_new() {
+            Some(FileStatus::Added)
+        } else if status.is_index_renamed() {
+            Some(FileStatus::Renamed)
+        } else if status.is_index_modified() || status.is_index_typechange() {
+            Some(FileStatus::Modified)
+        } else if status.is_index_deleted() {
+            Some(FileStatus::Deleted)
+        } else {
+            None
+        };
+
+        let worktree = if status.is_wt_renamed() {
+            Some(FileStatus::Renamed)
+        } else if status.is_wt_new() {
+            Some(FileStatus::Untracked)
+        } else if status.is_wt_modified() || status.is_wt_typechange() {
+            Some(FileStatus::Modified)
+        } else if status.is_wt_deleted() {
+            Some(FileStatus::Deleted)
+        } else {
+            None
+        };
+
+        (index, worktree)
+    }
+
+    fn get_line_changes(
+        &self,
+        path: &str,
+        old_path: Option<&str>,
+        source: DiffSource,
+    ) -> Result<LineStats> {
+        let diff = self.diff_for(path, old_path, source)?;
+        let stats = diff.stats()?;
+
+        Ok(LineStats {
+            additions: stats.insertions() as i32,
+            deletions: stats.deletions() as i32,
+        })
+    }
+
+    /// Get the structured diff of one side of a file's changes, relative to
+    /// `old_path` for renamed and copied files
+    pub fn get_file_diff(
+        &self,
+        path: &str,
+        old_path: Option<&str>,
+        source: DiffSource,
+    ) -> Result<FileDiff> {
+        let diff = self.diff_for(path, old_path, source)?;
+        let mut file_diff = Self::collect_hunks(&diff, path, source)?;
+        let delta = diff
+            .deltas()
+            .find(|d| d.new_file().path() == Some(Path::new(path)));
+        if let Some(delta) = delta {
+            file_diff.notice = self.content_notice(&delta, source);
+            if matches!(delta.status(), Delta::Renamed | Delta::Copied) {
+                file_diff.old_path = delta.old_file().path().map(path_string);
+                file_diff.similarity = Self::similarity(&diff, path);
+            }
         }
-        if status.is_index_modified() {
-            return (Some(FileStatus::Modified), true);
+        Ok(file_diff)
+    }
+
+    /// Similarity of a renamed or copied file to its source, which git2 only
+    /// exposes through the patch header
+    fn similarity(diff: &Diff<'_>, path: &str) -> Option<u16> {
+        let mut similarity = None;
+        diff.print(DiffFormat::PatchHeader, |delta, _hunk, line| {
+            if delta.new_file().path() == Some(Path::new(path)) {
+                let header = String::from_utf8_lossy(line.content());
+                similarity = similarity.or_else(|| {
+                    header.lines().find_map(|l| {
+                        l.strip_prefix("similarity index ")?
+                            .strip_suffix('%')?
+                            .parse()
+                            .ok()
+                    })
+                });
+            }
+            true
+        })
+        .ok()?;
+        similarity
+    }
+
+    /// Summarize binary changes, which have no hunks, and changes of Git
+    /// LFS pointer files, whose hunks only show hashes
+    fn content_notice(&self, delta: &DiffDelta<'_>, source: DiffSource) -> Option<DiffNotice> {
+        // The new side of unstaged changes is the working tree file
+        let new_in_worktree = source == DiffSource::Unstaged;
+
+        if delta.flags().is_binary() {
+            let summary = |(id, size, head): (Oid, u64, Vec<u8>)| BlobSummary {
+                oid: id.to_string(),
+                size,
+                image: image_info(&head),
+            };
+            return Some(DiffNotice::Binary {
+                old: self
+                    .side_content(&delta.old_file(), false, true)
+                    .map(summary),
+                new: self
+                    .side_content(&delta.new_file(), new_in_worktree, true)
+                    .map(summary),
+            });
         }
-        if status.is_index_deleted() {
-            return (Some(FileStatus::Deleted), true);
+
+        // Pointers are tiny, larger files are not worth reading
+        let small = |file: DiffFile<'_>| file.size() <= MAX_POINTER_SIZE as u64;
+        if !small(delta.old_file()) || !small(delta.new_file()) {
+            return None;
         }
-        if status.is_index_renamed() {
-            return (Some(FileStatus::Renamed), true);
+        let old = self.side_content(&delta.old_file(), false, false);
+        let new = self.side_content(&delta.new_file(), new_in_worktree, false);
+        if old.is_none() && new.is_none() {
+            return None;
         }
+        // Every existing side has to be a pointer
+        let pointer = |side: Option<(Oid, u64, Vec<u8>)>| match side {
+            Some((_, _, head)) => parse_lfs_pointer(&head).map(|pointer| {
+                Some(BlobSummary {
+                    oid: pointer.oid,
+                    size: pointer.size,
+                    image: None,
+                })
+            }),
+            None => Some(None),
+        };
+        Some(DiffNotice::Lfs {
+            old: pointer(old)?,
+            new: pointer(new)?,
+        })
+    }
 
-        // Check working tree changes
-        if status.is_wt_new() {
-            return (Some(FileStatus::Untracked), false);
+    /// Id, size and leading bytes of one side of a delta, or `None` if the
+    /// file does not exist on that side. Working tree files are only hashed
+    /// when `need_id` is set.
+    fn side_content(
+        &self,
+        file: &DiffFile<'_>,
+        in_worktree: bool,
+        need_id: bool,
+    ) -> Option<(Oid, u64, Vec<u8>)> {
+        if !file.exists() {
+            return None;
         }
-        if status.is_wt_modified() {
-            return (Some(FileStatus::Modified), false);
+
+        if !in_worktree {
+            let blob = self.repo.find_blob(file.id()).ok()?;
+            let head = blob
+                .content()
+                .iter()
+                .take(HEADER_BYTES as usize)
+                .copied()
+                .collect();
+            return Some((file.id(), blob.size() as u64, head));
         }
-        if status.is_wt_deleted() {
-            return (Some(FileStatus::Deleted), false);
+
+        let full_path = self.path.join(file.path()?);
+        let size = std::fs::metadata(&full_path).ok()?.len();
+        let mut head = Vec::new();
+        File::open(&full_path)
+            .ok()?
+            .take(HEADER_BYTES)
+            .read_to_end(&mut head)
+            .ok()?;
+        let id = if need_id && file.id().is_zero() {
+            Oid::hash_file(ObjectType::Blob, &full_path).ok()?
+        } else {
+            file.id()
+        };
+        Some((id, size, head))
+    }
+
+    /// Reveal the new-side lines in `ranges` as extra context around the
+    /// hunks of `diff`, reading them from the working tree or the index
+    pub fn expand_context(&self, diff: &mut FileDiff, ranges: &[Range<u32>]) -> Result<()> {
+        let content = match diff.source {
+            DiffSource::Unstaged => std::fs::read(self.path.join(&diff.path))?,
+            DiffSource::Staged => {
+                let index = self.repo.index()?;
+                let entry = index
+                    .get_path(Path::new(&diff.path), 0)
+                    .ok_or_else(|| eyre!("{} is not in the index", diff.path))?;
+                self.repo.find_blob(entry.id)?.content().to_vec()
+            }
+        };
+
+        let text = String::from_utf8_lossy(&content);
+        let lines: Vec<String> = text
+            .split_inclusive('\n')
+            .map(|line| line.strip_suffix('\n').unwrap_or(line).to_string())
+            .collect();
+        diff.expand_context(ranges, &lines, text.is_empty() || text.ends_with('\n'));
+        Ok(())
+    }
+
+    /// Build the git2 diff of one side of a file's changes
+    fn diff_for(&self, path: &str, old_path: Option<&str>, source: DiffSource) -> Result<Diff<'_>> {
+        let mut opts = DiffOptions::new();
+        opts.pathspec(path)
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true)
+            .context_lines(self.diff_settings.context_lines)
+            .ignore_whitespace_change(self.diff_settings.ignore_whitespace)
+            .ignore_blank_lines(self.diff_settings.ignore_blank_lines)
+            .minimal(self.diff_settings.algorithm == DiffAlgorithm::Minimal)
+            .patience(self.diff_settings.algorithm == DiffAlgorithm::Patience);
+
+        // Both paths of a rename, the copy source even if unchanged
+        if let Some(old_path) = old_path {
+            opts.pathspec(old_path).include_unmodified(true);
         }
-        if status.is_wt_renamed() {
-            return (Some(FileStatus::Renamed), false);
+
+        let mut diff = match source {
+            DiffSource::Unstaged => self.repo.diff_index_to_workdir(None, Some(&mut opts))?,
+            DiffSource::Staged => {
+                let head = self.head_tree()?;
+                self.repo
+                    .diff_tree_to_index(head.as_ref(), None, Some(&mut opts))?
+            }
+        };
+
+        if let (Some(_), Some(threshold)) = (old_path, self.diff_settings.rename_threshold) {
+            let mut find = DiffFindOptions::new();
+            find.renames(true)
+                .copies(true)
+                .copies_from_unmodified(true)
+                .for_untracked(true)
+                .rename_threshold(threshold)
+                .copy_threshold(threshold);
+            diff.find_similar(Some(&mut find))?;
         }
 
-        (None, false)
+        Ok(diff)
     }
 
-    fn get_line_changes(&self, path: &str) -> Result<(i32, i32)> {
-        let mut opts = DiffOptions::new();
-        opts.pathspec(path);
+    fn collect_hunks(diff: &Diff<'_>, path: &str, source: DiffSource) -> Result<FileDiff> {
+        let mut hunks: Vec<DiffHunk> = Vec::new();
 
-        let diff = self.repo.diff_index_to_workdir(None, Some(&mut opts))?;
-        let stats = diff.stats()?;
+        diff.foreach(
+            &mut |_delta, _progress| true,
+            None,
+            None,
+            Some(&mut |delta, hunk, line| {
+                let Some(hunk) = hunk else {
+                    return true;
+                };
+                // Skip the other side of a rename that was not paired up
+                if delta.new_file().path() != Some(Path::new(path)) {
+                    return true;
+                }
+                let Some(kind) = LineKind::from_origin(line.origin()) else {
+                    return true;
+                };
+
+                let range = (
+                    hunk.old_start(),
+                    hunk.old_lines(),
+                    hunk.new_start(),
+                    hunk.new_lines(),
+                );
+                if hunks.last().map(|h| h.range()) != Some(range) {
+                    hunks.push(DiffHunk {
+                        header: String::from_utf8_lossy(hunk.header())
+                            .trim_end()
+                            .to_string(),
+                        old_start: range.0,
+                        old_lines: range.1,
+                        new_start: range.2,
+                        new_lines: range.3,
+                        lines: Vec::new(),
+                    });
+                }
 
-        Ok((stats.insertions() as i32, stats.deletions() as i32))
+                let content = String::from_utf8_lossy(line.content());
+                let crlf = kind != LineKind::NoNewline && content.ends_with("\r\n");
+                let content = match kind {
+                    LineKind::NoNewline => "\\ No newline at end of file".to_string(),
+                    _ => content.trim_end_matches(['\n', '\r']).to_string(),
+                };
+
+                if let Some(current) = hunks.last_mut() {
+                    current.lines.push(DiffLine {
+                        kind,
+                        content,
+                        old_lineno: line.old_lineno(),
+                        new_lineno: line.new_lineno(),
+                        crlf,
+                    });
+                }
+
+                true
+            }),
+        )?;
+
+        Ok(FileDiff {
+            path: path.to_string(),
+            source,
+            old_path: None,
+            similarity: None,
+            hunks,
+            notice: None,
+        })
     }
 
-    pub fn get_diff(&self, path: &str) -> Result<String> {
-        let mut opts = DiffOptions::new();
-        opts.pathspec(path);
-
-        // Try to get diff from index to workdir first
-        let diff = self.repo.diff_index_to_workdir(None, Some(&mut opts))?;
-
-        let mut output = String::new();
-
-        diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-            let prefix = match line.origin() {
-                '+' => "+",
-                '-' => "-",
-                ' ' => " ",
-                '@' => "@",
-                '>' => ">",
-                '<' => "<",
-                'F' => "", // File header
-                'H' => "", // Hunk header
-                'B' => "", // Binary
-                _ => "",
-            };
+    /// Message of the HEAD commit, used to pre-fill an amend
+    pub fn head_message(&self) -> Result<String> {
+        let head = self.repo.head()?.peel_to_commit()?;
+        Ok(head.message().unwrap_or_default().trim_end().to_string())
+    }
+
+    /// Commit the index with the user's configured signature, optionally
+    /// amending HEAD instead of creating a new commit on top of it
+    pub fn commit(&self, message: &str, amend: bool) -> Result<Oid> {
+        if message.trim().is_empty() {
+            return Err(eyre!("Commit message is empty"));
+        }
 
-            if !prefix.is_empty() || line.origin() == 'F' || line.origin() == 'H' {
-                if let Ok(content) = std::str::from_utf8(line.content()) {
-                    output.push_str(prefix);
-                    output.push_str(content);
+        let signature = self
+            .repo
+            .signature()
+            .map_err(|_| eyre!("Missing user.name or user.email in git config"))?;
+
+        let mut index = self.repo.index()?;
+        if index.has_conflicts() {
+            return Err(eyre!("Resolve merge conflicts before committing"));
+        }
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+        let head_tree = self.head_tree()?;
+        let merging = self.repo.state() == RepositoryState::Merge;
+
+        if amend {
+            if merging {
+                return Err(eyre!("Cannot amend in the middle of a merge"));
+            }
+            let head = self
+                .repo
+                .head()
+                .map_err(|_| eyre!("Nothing to amend: HEAD has no commits"))?
+                .peel_to_commit()?;
+            let oid = head.amend(
+                Some("HEAD"),
+                None,
+                Some(&signature),
+                None,
+                Some(message),
+                Some(&tree),
+            )?;
+            return Ok(oid);
+        }
+
+        let unchanged = match &head_tree {
+            Some(head_tree) => head_tree.id() == tree.id(),
+            None => index.is_empty(),
+        };
+        // A merge resolved to our side records no changes but still commits
+        if unchanged && !merging {
+            return Err(eyre!("Nothing staged to commit"));
+        }
+
+        let mut parents = Vec::new();
+        if head_tree.is_some() {
+            parents.push(self.repo.head()?.peel_to_commit()?);
+        }
+        if merging {
+            // One commit per line, several for an octopus merge
+            let merge_heads = std::fs::read_to_string(self.repo.path().join("MERGE_HEAD"))?;
+            for line in merge_heads.lines().filter(|line| !line.trim().is_empty()) {
+                parents.push(self.repo.find_commit(Oid::from_str(line.trim())?)?);
+            }
+        }
+        let parents: Vec<&Commit> = parents.iter().collect();
+        let oid = self.repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?;
+        // Drop MERGE_HEAD, CHERRY_PICK_HEAD and the prepared message
+        self.repo.cleanup_state()?;
+        Ok(oid)
+    }
+
+    /// Stage a single hunk of the working tree changes
+    pub fn stage_hunk(&self, path: &str, hunk: &DiffHunk) -> Result<()> {
+        self.apply_partial(
+            path,
+            hunk,
+            &hunk.changed_lines(),
+            false,
+            ApplyLocation::Index,
+        )
+    }
+
+    /// Remove a single hunk from the index, keeping it in the working tree
+    pub fn unstage_hunk(&self, path: &str, hunk: &DiffHunk) -> Result<()> {
+        self.apply_partial(
+            path,
+            hunk,
+            &hunk.changed_lines(),
+            true,
+            ApplyLocation::Index,
+        )
+    }
+
+    /// Revert a single hunk of the working tree back to the index version
+    pub fn discard_hunk(&self, path: &str, hunk: &DiffHunk) -> Result<()> {
+        self.apply_partial(
+            path,
+            hunk,
+            &hunk.changed_lines(),
+            true,
+            ApplyLocation::WorkDir,
+        )
+    }
+
+    /// Revert only the selected lines of a working tree hunk
+    pub fn discard_lines(&self, path: &str, hunk: &DiffHunk, selected: &[usize]) -> Result<()> {
+        self.apply_partial(path, hunk, selected, true, ApplyLocation::WorkDir)
+    }
+
+    /// Revert a file in the working tree to its index version, deleting it
+    /// when it is untracked
+    pub fn discard_file(&self, path: &str) -> Result<()> {
+        let mut index = self.repo.index()?;
+        if index.get_path(Path::new(path), 0).is_none() {
+            return self.write_worktree_file(path, None);
+        }
+        let mut checkout = CheckoutBuilder::new();
+        checkout.force().path(path);
+        self.repo
+            .checkout_index(Some(&mut index), Some(&mut checkout))?;
+        Ok(())
+    }
+
+    /// Current content of a working tree file, `None` when it does not exist
+    pub fn read_worktree_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path.join(path)) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace a working tree file with `content`, or delete it for `None`
+    pub fn write_worktree_file(&self, path: &str, content: Option<&[u8]>) -> Result<()> {
+        let full_path = self.path.join(path);
+        match content {
+            Some(content) => {
+                if let Some(parent) = full_path.parent() {
+                    std::fs::create_dir_all(parent)?;
                 }
+                std::fs::write(full_path, content)?;
+            }
+            None => match std::fs::remove_file(full_path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+
+    /// Store content in the object database without referencing it, so
+    /// `git fsck --lost-found` can still recover it later
+    pub fn save_blob(&self, content: &[u8]) -> Result<Oid> {
+        Ok(self.repo.blob(content)?)
+    }
+
+    /// Stage only the selected lines of a working tree hunk
+    pub fn stage_lines(&self, path: &str, hunk: &DiffHunk, selected: &[usize]) -> Result<()> {
+        self.apply_partial(path, hunk, selected, false, ApplyLocation::Index)
+    }
+
+    /// Remove only the selected lines of a staged hunk from the index
+    pub fn unstage_lines(&self, path: &str, hunk: &DiffHunk, selected: &[usize]) -> Result<()> {
+        self.apply_partial(path, hunk, selected, true, ApplyLocation::Index)
+    }
+
+    /// Apply the `selected` lines of `hunk` as a patch built from the hunk's
+    /// own lines. Whole hunks go through here too, rather than being picked
+    /// out of a fresh diff by range, so hunks with expanded context can be
+    /// staged as shown.
+    fn apply_partial(
+        &self,
+        path: &str,
+        hunk: &DiffHunk,
+        selected: &[usize],
+        reverse: bool,
+        location: ApplyLocation,
+    ) -> Result<()> {
+        // New, deleted and emptied files need the patch to say so, or it
+        // would patch a file that is not there or leave an empty one behind
+        let before = match location {
+            ApplyLocation::WorkDir => self.worktree_mode(path),
+            _ => self.index_mode(path)?,
+        };
+        let after = match (reverse, location) {
+            (false, _) => self.worktree_mode(path),
+            (true, ApplyLocation::WorkDir) => self.index_mode(path)?,
+            (true, _) => self.head_mode(path)?,
+        };
+        let patch = hunk
+            .partial_patch(path, selected, reverse, (before, after))
+            .ok_or_else(|| eyre!("No changed lines selected"))?;
+        let diff = Diff::from_buffer(patch.as_bytes())?;
+        self.repo.apply(&diff, location, None)?;
+        Ok(())
+    }
+
+    /// Git mode of `path` in HEAD, `None` when it is not there
+    fn head_mode(&self, path: &str) -> Result<Option<u32>> {
+        let Some(tree) = self.head_tree()? else {
+            return Ok(None);
+        };
+        Ok(tree
+            .get_path(Path::new(path))
+            .ok()
+            .map(|entry| entry.filemode() as u32))
+    }
+
+    /// Git mode of `path` in the index, `None` when it is not there
+    fn index_mode(&self, path: &str) -> Result<Option<u32>> {
+        let index = self.repo.index()?;
+        Ok(index.get_path(Path::new(path), 0).map(|entry| entry.mode))
+    }
+
+    /// Git mode of a working tree file, `None` when it does not exist
+    fn worktree_mode(&self, path: &str) -> Option<u32> {
+        let metadata = std::fs::symlink_metadata(self.path.join(path)).ok()?;
+        if metadata.file_type().is_symlink() {
+            return Some(0o120000);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if metadata.permissions().mode() & 0o111 != 0 {
+                return Some(0o100755);
             }
+        }
+        Some(REGULAR_FILE_MODE)
+    }
 
+    /// Stashes, newest first, with the files each one changes
+    pub fn stash_list(&mut self) -> Result<Vec<StashEntry>> {
+        let mut stashes = Vec::new();
+        self.repo.stash_foreach(|index, message, id| {
+            stashes.push((index, message.to_string(), *id));
             true
         })?;
 
-        // If no workdir diff, try HEAD to index
-        if output.is_empty() {
-            let head = self.repo.head()?.peel_to_tree()?;
-            let diff = self.repo.diff_tree_to_index(Some(&head), None, Some(&mut opts))?;
-
-            diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-                let prefix = match line.origin() {
-                    '+' => "+",
-                    '-' => "-",
-                    ' ' => " ",
-                    '@' => "@",
-                    _ => "",
-                };
-
-                if !prefix.is_empty() {
-                    if let Ok(content) = std::str::from_utf8(line.content()) {
-                        output.push_str(prefix);
-                        output.push_str(content);
+        stashes
+            .into_iter()
+            .map(|(index, message, id)| {
+                let commit = self.repo.find_commit(id)?;
+                let mut files: Vec<String> = Vec::new();
+                for diff in self.stash_diffs(&commit)? {
+                    for delta in diff.deltas() {
+                        let path = delta.new_file().path().or(delta.old_file().path());
+                        if let Some(path) = path.map(path_string) {
+                            if !files.contains(&path) {
+                                files.push(path);
+                            }
+                        }
                     }
                 }
+                Ok(StashEntry {
+                    index,
+                    id: id.to_string(),
+                    branch: stash_branch(&message),
+                    message,
+                    time: commit.time().seconds(),
+                    files,
+                })
+            })
+            .collect()
+    }
 
+    /// Patch text of a stash, one line per entry
+    pub fn stash_patch(&self, id: &str) -> Result<Vec<String>> {
+        let commit = self.repo.find_commit(Oid::from_str(id)?)?;
+        let mut lines = Vec::new();
+        for diff in self.stash_diffs(&commit)? {
+            diff.print(DiffFormat::Patch, |_, _, line| {
+                let content = String::from_utf8_lossy(line.content());
+                match line.origin() {
+                    origin @ ('+' | '-' | ' ') => lines.push(format!(
+                        "{}{}",
+                        origin,
+                        content.trim_end_matches(['\n', '\r'])
+                    )),
+                    // File and hunk headers, binary notes and end-of-file markers
+                    _ => lines.extend(content.lines().map(str::to_string)),
+                }
                 true
             })?;
         }
+        Ok(lines)
+    }
+
+    /// Stash changes of the working tree and index
+    pub fn stash_save(&mut self, mode: &StashMode) -> Result<()> {
+        let signature = self
+            .repo
+            .signature()
+            .map_err(|_| eyre!("Missing user.name or user.email in git config"))?;
+        match mode {
+            StashMode::All => {
+                self.repo
+                    .stash_save2(&signature, None, Some(StashFlags::INCLUDE_UNTRACKED))?;
+            }
+            StashMode::Paths(paths) => {
+                // libgit2 resets the whole working tree after a path-limited
+                // stash, so keep everything and revert just those paths
+                let mut opts = StashSaveOptions::new(signature);
+                opts.flags(Some(StashFlags::INCLUDE_UNTRACKED | StashFlags::KEEP_ALL));
+                for path in paths {
+                    opts.pathspec(path.as_str());
+                }
+                self.repo.stash_save_ext(Some(&mut opts))?;
+                for path in paths {
+                    self.unstage_file(path)?;
+                    self.discard_file(path)?;
+                }
+            }
+            StashMode::Staged => self.stash_staged(&signature)?,
+        }
+        Ok(())
+    }
+
+    /// Apply a stash, restoring what was staged, and drop it when `pop`
+    pub fn stash_apply(&mut self, id: &str, pop: bool) -> Result<()> {
+        let index = self.stash_position(id)?;
+        let mut opts = StashApplyOptions::new();
+        opts.reinstantiate_index();
+        if pop {
+            self.repo.stash_pop(index, Some(&mut opts))?;
+        } else {
+            self.repo.stash_apply(index, Some(&mut opts))?;
+        }
+        Ok(())
+    }
+
+    pub fn stash_drop(&mut self, id: &str) -> Result<()> {
+        let index = self.stash_position(id)?;
+        self.repo.stash_drop(index)?;
+        Ok(())
+    }
+
+    /// Current list position of a stash
+    fn stash_position(&mut self, id: &str) -> Result<usize> {
+        let mut position = None;
+        self.repo.stash_foreach(|index, _, oid| {
+            if oid.to_string() == id {
+                position = Some(index);
+            }
+            true
+        })?;
+        position.ok_or_else(|| eyre!("stash {} no longer exists", &id[..id.len().min(7)]))
+    }
+
+    /// Diffs of a stash commit: tracked changes against the commit it was
+    /// made on, then untracked files when they were stashed too
+    fn stash_diffs(&self, stash: &Commit) -> Result<Vec<Diff<'_>>> {
+        let base = stash.parent(0)?.tree()?;
+        let tracked = self
+            .repo
+            .diff_tree_to_tree(Some(&base), Some(&stash.tree()?), None)?;
+        let mut diffs = vec![tracked];
+        if let Ok(untracked) = stash.parent(2) {
+            diffs.push(
+                self.repo
+                    .diff_tree_to_tree(None, Some(&untracked.tree()?), None)?,
+            );
+        }
+        Ok(diffs)
+    }
+
+    /// `git stash --staged`, which libgit2 lacks: record the index as both
+    /// the index and the working tree state of a stash, then take the staged
+    /// changes out of the index and the working tree
+    fn stash_staged(&mut self, signature: &Signature) -> Result<()> {
+        let head = self
+            .repo
+            .head()
+            .and_then(|h| h.peel_to_commit())
+            .map_err(|_| eyre!("Nothing to stash on a branch without commits"))?;
+        let mut index = self.repo.index()?;
+        if index.has_conflicts() {
+            return Err(eyre!("Resolve merge conflicts before stashing"));
+        }
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+        if tree.id() == head.tree_id() {
+            return Err(eyre!("No staged changes to stash"));
+        }
+
+        // Check the staged changes come out cleanly before recording anything
+        let mut opts = DiffOptions::new();
+        opts.reverse(true).show_binary(true);
+        let unstage =
+            self.repo
+                .diff_tree_to_index(Some(&head.tree()?), Some(&index), Some(&mut opts))?;
+        for location in [ApplyLocation::Index, ApplyLocation::WorkDir] {
+            self.repo
+                .apply(&unstage, location, Some(ApplyOptions::new().check(true)))?;
+        }
+
+        let branch = self
+            .head_info()?
+            .branch
+            .unwrap_or_else(|| "(no branch)".to_string());
+        let subject = format!(
+            "{}: {} {}",
+            branch,
+            &head.id().to_string()[..7],
+            head.summary().unwrap_or_default()
+        );
+        let index_commit = self.repo.commit(
+            None,
+            signature,
+            signature,
+            &format!("index on {}", subject),
+            &tree,
+            &[&head],
+        )?;
+        let index_commit = self.repo.find_commit(index_commit)?;
+        let message = format!("WIP on {}", subject);
+        let stash = self.repo.commit(
+            None,
+            signature,
+            signature,
+            &message,
+            &tree,
+            &[&head, &index_commit],
+        )?;
+        self.repo.reference_ensure_log("refs/stash")?;
+        self.repo.reference("refs/stash", stash, true, &message)?;
 
-        Ok(output)
+        self.repo.apply(&unstage, ApplyLocation::Index, None)?;
+        self.repo.apply(&unstage, ApplyLocation::WorkDir, None)?;
+        Ok(())
+    }
+
+    /// Tree of the HEAD commit, or `None` on an unborn branch
+    fn head_tree(&self) -> Result<Option<Tree<'_>>> {
+        match self.repo.head() {
+            Ok(head) => Ok(Some(head.peel_to_tree()?)),
+            Err(e) if e.code() == ErrorCode::UnbornBranch => Ok(None),
+            Err(e) => Err(e.into()),
+        }
     }
 
     pub fn stage_file(&self, path: &str) -> Result<()> {
+        let mut index = self.repo.index()?;
+        if self.path.join(path).exists() {
+            index.add_path(Path::new(path))?;
+        } else {
+            index.remove_path(Path::new(path))?;
+        }
+        index.write()?;
+        Ok(())
+    }
+
+    /// Merge the ours/theirs index stages of a conflicted path against their
+    /// common ancestor
+    pub fn conflict_file(&self, path: &str) -> Result<ConflictFile> {
+        let index = self.repo.index()?;
+        let entry_path = |entry: &Option<IndexEntry>| entry.as_ref().map(|e| e.path.clone());
+        let conflict = index
+            .conflicts()?
+            .filter_map(|c| c.ok())
+            .find(|c| {
+                [&c.ancestor, &c.our, &c.their]
+                    .into_iter()
+                    .any(|e| entry_path(e).as_deref() == Some(path.as_bytes()))
+            })
+            .ok_or_else(|| eyre!("{} has no conflict to resolve", path))?;
+
+        let content = |entry: &Option<IndexEntry>| -> Result<Option<String>> {
+            let Some(entry) = entry else {
+                return Ok(None);
+            };
+            let blob = self.repo.find_blob(entry.id)?;
+            if blob.is_binary() {
+                return Err(eyre!("{} is binary; resolve it with git", path));
+            }
+            let text = String::from_utf8(blob.content().to_vec())
+                .map_err(|_| eyre!("{} is not UTF-8 text; resolve it with git", path))?;
+            Ok(Some(text))
+        };
+        let (Some(ours), Some(theirs)) = (content(&conflict.our)?, content(&conflict.their)?)
+        else {
+            return Err(eyre!(
+                "{} was deleted on one side; resolve it with git",
+                path
+            ));
+        };
+        let base = content(&conflict.ancestor)?.unwrap_or_default();
+        ConflictFile::new(path, &base, &ours, &theirs)
+    }
+
+    /// Write the merged content of a conflicted path and stage it, which
+    /// clears the conflict
+    pub fn resolve_conflict(&self, path: &str, content: &str) -> Result<()> {
+        std::fs::write(self.path.join(path), content)?;
         let mut index = self.repo.index()?;
         index.add_path(Path::new(path))?;
         index.write()?;
@@ -238,9 +1239,868 @@ impl GitRepository {
     }
 
     pub fn unstage_file(&self, path: &str) -> Result<()> {
-        let head = self.repo.head()?.peel_to_commit()?;
-        self.repo
-            .reset_default(Some(head.as_object()), [Path::new(path)])?;
+        match self.head_tree()? {
+            Some(_) => {
+                let head = self.repo.head()?.peel_to_commit()?;
+                self.repo
+                    .reset_default(Some(head.as_object()), [Path::new(path)])?;
+            }
+            None => {
+                let mut index = self.repo.index()?;
+                index.remove_path(Path::new(path))?;
+                index.write()?;
+            }
+        }
         Ok(())
     }
 }
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Sort by modification time (newest first), then by path for files without mtime
+pub fn sort_by_mtime(files: &mut [FileChange]) {
+    files.sort_by(|a, b| {
+        match (&a.modified_time, &b.modified_time) {
+            // Both have modification times: sort by time descending (newest first)
+            (Some(t_a), Some(t_b)) => t_b.cmp(t_a),
+            // Only a has time: a comes first
+            (Some(_), None) => std::cmp::Ordering::Less,
+            // Only b has time: b comes first
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            // Neither has time: sort by path
+            (None, None) => a.path.cmp(&b.path),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::Resolution;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Create a repository with one committed file of `lines` numbered lines
+    fn repo_with_file(lines: usize) -> (TempDir, GitRepository) {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let content: String = (1..=lines).map(|i| format!("line {}\n", i)).collect();
+        fs::write(dir.path().join("file.txt"), content).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("file.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "initial", &tree, &[])
+            .unwrap();
+
+        let git_repo = GitRepository::new(dir.path().to_path_buf()).unwrap();
+        (dir, git_repo)
+    }
+
+    /// Edit the first and last line so the diff has two separate hunks
+    fn edit_two_hunks(dir: &TempDir) {
+        let content: String = (1..=20)
+            .map(|i| match i {
+                1 => "first changed\n".to_string(),
+                20 => "last changed\n".to_string(),
+                _ => format!("line {}\n", i),
+            })
+            .collect();
+        fs::write(dir.path().join("file.txt"), content).unwrap();
+    }
+
+    fn hunk_count(repo: &GitRepository, source: DiffSource) -> usize {
+        repo.get_file_diff("file.txt", None, source)
+            .unwrap()
+            .hunks
+            .len()
+    }
+
+    #[test]
+    fn test_get_file_diff_splits_hunks() {
+        let (dir, repo) = repo_with_file(20);
+        edit_two_hunks(&dir);
+
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        assert_eq!(diff.source, DiffSource::Unstaged);
+        assert_eq!(diff.hunks.len(), 2);
+
+        let first = &diff.hunks[0];
+        assert!(first.header.starts_with("@@ -1,"));
+        assert!(first
+            .lines
+            .iter()
+            .any(|l| l.kind == LineKind::Addition && l.content == "first changed"));
+    }
+
+    #[test]
+    fn test_diff_settings_apply_to_hunks_and_line_counts() {
+        let (dir, mut repo) = repo_with_file(20);
+        edit_two_hunks(&dir);
+
+        // Enough context merges the two hunks into one
+        repo.set_diff_settings(DiffSettings {
+            context_lines: 10,
+            ..DiffSettings::default()
+        });
+        assert_eq!(hunk_count(&repo, DiffSource::Unstaged), 1);
+
+        // Whitespace-only edits disappear from diffs and line counts alike
+        let content: String = (1..=20).map(|i| format!("line  {}\n", i)).collect();
+        fs::write(dir.path().join("file.txt"), content).unwrap();
+        repo.set_diff_settings(DiffSettings {
+            ignore_whitespace: true,
+            ..DiffSettings::default()
+        });
+        assert_eq!(hunk_count(&repo, DiffSource::Unstaged), 0);
+        let stats = repo
+            .get_line_changes("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        assert_eq!(stats.additions + stats.deletions, 0);
+    }
+
+    #[test]
+    fn test_status_reports_staged_and_unstaged_separately() {
+        let (dir, repo) = repo_with_file(20);
+        edit_two_hunks(&dir);
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.stage_hunk("file.txt", &diff.hunks[0]).unwrap();
+
+        let files = repo.get_status().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].index_status, Some(FileStatus::Modified));
+        assert_eq!(files[0].worktree_status, Some(FileStatus::Modified));
+        assert_eq!(files[0].staged_stats.additions, 1);
+        assert_eq!(files[0].unstaged_stats.additions, 1);
+    }
+
+    #[test]
+    fn test_status_for_paths_matches_full_status() {
+        let (dir, repo) = repo_with_file(3);
+        fs::write(dir.path().join("file.txt"), "changed\n").unwrap();
+        fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+
+        let paths = vec![
+            "file.txt".to_string(),
+            "new.txt".to_string(),
+            "missing.txt".to_string(),
+        ];
+        let results = repo.get_status_for_paths(&paths).unwrap();
+        let file = results[0].1.as_ref().unwrap();
+        assert_eq!(file.worktree_status, Some(FileStatus::Modified));
+        assert_eq!(file.unstaged_stats.deletions, 3);
+        let new = results[1].1.as_ref().unwrap();
+        assert_eq!(new.worktree_status, Some(FileStatus::Untracked));
+        assert!(results[2].1.is_none());
+
+        // Reverting the edit makes the path clean again
+        fs::write(dir.path().join("file.txt"), "line 1\nline 2\nline 3\n").unwrap();
+        let results = repo.get_status_for_paths(&paths[..1]).unwrap();
+        assert!(results[0].1.is_none());
+    }
+
+    #[test]
+    fn test_status_for_paths_pairs_moved_files() {
+        let (dir, repo) = repo_with_file(3);
+        fs::rename(dir.path().join("file.txt"), dir.path().join("moved.txt")).unwrap();
+
+        let paths = vec!["file.txt".to_string(), "moved.txt".to_string()];
+        let results = repo.get_status_for_paths(&paths).unwrap();
+        assert!(results[0].1.is_none());
+        let moved = results[1].1.as_ref().unwrap();
+        assert_eq!(moved.worktree_status, Some(FileStatus::Renamed));
+        assert_eq!(moved.worktree_old_path.as_deref(), Some("file.txt"));
+        // Like the full status, which lists the move under its new path only
+        let full = repo.get_status().unwrap();
+        assert_eq!(full.len(), 1);
+        assert_eq!(full[0].path, "moved.txt");
+        assert_eq!(full[0].worktree_old_path, moved.worktree_old_path);
+
+        // A lone deletion is answered too
+        let results = repo.get_status_for_paths(&paths[..1]).unwrap();
+        let deleted = results[0].1.as_ref().unwrap();
+        assert_eq!(deleted.worktree_status, Some(FileStatus::Deleted));
+    }
+
+    #[test]
+    fn test_status_for_paths_rejects_directories() {
+        let (dir, repo) = repo_with_file(3);
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/a.txt"), "a\n").unwrap();
+
+        assert!(repo.get_status_for_paths(&["sub".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_stage_and_unstage_single_hunk() {
+        let (dir, repo) = repo_with_file(20);
+        edit_two_hunks(&dir);
+
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.stage_hunk("file.txt", &diff.hunks[0]).unwrap();
+        assert_eq!(hunk_count(&repo, DiffSource::Staged), 1);
+        assert_eq!(hunk_count(&repo, DiffSource::Unstaged), 1);
+
+        let staged = repo
+            .get_file_diff("file.txt", None, DiffSource::Staged)
+            .unwrap();
+        repo.unstage_hunk("file.txt", &staged.hunks[0]).unwrap();
+        assert_eq!(hunk_count(&repo, DiffSource::Staged), 0);
+        assert_eq!(hunk_count(&repo, DiffSource::Unstaged), 2);
+    }
+
+    /// Content of `path` in the index, `None` when it is not there
+    fn index_content(repo: &GitRepository, path: &str) -> Option<Vec<u8>> {
+        let mut index = repo.repo.index().unwrap();
+        index.read(true).unwrap();
+        let entry = index.get_path(Path::new(path), 0)?;
+        Some(repo.repo.find_blob(entry.id).unwrap().content().to_vec())
+    }
+
+    #[test]
+    fn test_stage_and_unstage_hunk_of_new_file() {
+        let (dir, repo) = repo_with_file(3);
+        fs::write(dir.path().join("new.txt"), "a\nb\n").unwrap();
+
+        let diff = repo
+            .get_file_diff("new.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.stage_hunk("new.txt", &diff.hunks[0]).unwrap();
+        assert_eq!(
+            index_content(&repo, "new.txt").as_deref(),
+            Some(&b"a\nb\n"[..])
+        );
+
+        // Unstaging the whole file takes it out of the index again
+        let staged = repo
+            .get_file_diff("new.txt", None, DiffSource::Staged)
+            .unwrap();
+        repo.unstage_hunk("new.txt", &staged.hunks[0]).unwrap();
+        assert_eq!(index_content(&repo, "new.txt"), None);
+        assert!(dir.path().join("new.txt").exists());
+    }
+
+    #[test]
+    fn test_stage_unstage_and_discard_hunk_of_deleted_file() {
+        let (dir, repo) = repo_with_file(3);
+        fs::remove_file(dir.path().join("file.txt")).unwrap();
+
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.stage_hunk("file.txt", &diff.hunks[0]).unwrap();
+        assert_eq!(index_content(&repo, "file.txt"), None);
+
+        let staged = repo
+            .get_file_diff("file.txt", None, DiffSource::Staged)
+            .unwrap();
+        repo.unstage_hunk("file.txt", &staged.hunks[0]).unwrap();
+        let content = b"line 1\nline 2\nline 3\n";
+        assert_eq!(
+            index_content(&repo, "file.txt").as_deref(),
+            Some(&content[..])
+        );
+
+        // Discarding the deletion brings the file back
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.discard_hunk("file.txt", &diff.hunks[0]).unwrap();
+        assert_eq!(fs::read(dir.path().join("file.txt")).unwrap(), content);
+    }
+
+    #[test]
+    fn test_stage_and_unstage_selected_lines() {
+        let (dir, repo) = repo_with_file(3);
+        fs::write(
+            dir.path().join("file.txt"),
+            "line 1\nnew a\nnew b\nline 2\nline 3\n",
+        )
+        .unwrap();
+
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        let hunk = &diff.hunks[0];
+        let second = hunk
+            .lines
+            .iter()
+            .position(|l| l.content == "new b")
+            .unwrap();
+        repo.stage_lines("file.txt", hunk, &[second]).unwrap();
+
+        let mut index = repo.repo.index().unwrap();
+        index.read(true).unwrap();
+        let entry = index.get_path(Path::new("file.txt"), 0).unwrap();
+        let blob = repo.repo.find_blob(entry.id).unwrap();
+        assert_eq!(blob.content(), b"line 1\nnew b\nline 2\nline 3\n");
+
+        let staged = repo
+            .get_file_diff("file.txt", None, DiffSource::Staged)
+            .unwrap();
+        let added = staged.hunks[0]
+            .lines
+            .iter()
+            .position(|l| l.kind == LineKind::Addition)
+            .unwrap();
+        repo.unstage_lines("file.txt", &staged.hunks[0], &[added])
+            .unwrap();
+        assert_eq!(hunk_count(&repo, DiffSource::Staged), 0);
+    }
+
+    #[test]
+    fn test_stage_selected_lines_of_new_file() {
+        let (dir, repo) = repo_with_file(3);
+        fs::write(dir.path().join("new.txt"), "a\nb\n").unwrap();
+
+        let diff = repo
+            .get_file_diff("new.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.stage_lines("new.txt", &diff.hunks[0], &[1]).unwrap();
+        assert_eq!(
+            index_content(&repo, "new.txt").as_deref(),
+            Some(&b"b\n"[..])
+        );
+
+        // Unstaging every staged line leaves the file out of the index
+        let staged = repo
+            .get_file_diff("new.txt", None, DiffSource::Staged)
+            .unwrap();
+        repo.unstage_lines("new.txt", &staged.hunks[0], &[0])
+            .unwrap();
+        assert_eq!(index_content(&repo, "new.txt"), None);
+    }
+
+    #[test]
+    fn test_stage_and_discard_selected_lines_of_deleted_file() {
+        let (dir, repo) = repo_with_file(3);
+        fs::remove_file(dir.path().join("file.txt")).unwrap();
+
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.stage_lines("file.txt", &diff.hunks[0], &[0]).unwrap();
+        assert_eq!(
+            index_content(&repo, "file.txt").as_deref(),
+            Some(&b"line 2\nline 3\n"[..])
+        );
+
+        // Restoring one line recreates the file with just that line
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.discard_lines("file.txt", &diff.hunks[0], &[1])
+            .unwrap();
+        assert_eq!(fs::read(dir.path().join("file.txt")).unwrap(), b"line 3\n");
+
+        // Staging the remaining deletion takes the file out of the index
+        fs::remove_file(dir.path().join("file.txt")).unwrap();
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.stage_lines("file.txt", &diff.hunks[0], &[0, 1])
+            .unwrap();
+        assert_eq!(index_content(&repo, "file.txt"), None);
+    }
+
+    #[test]
+    fn test_commit_requires_message_and_staged_changes() {
+        let (dir, repo) = repo_with_file(3);
+        let mut config = repo.repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+
+        assert!(repo.commit("  ", false).is_err());
+        let err = repo.commit("nothing", false).unwrap_err();
+        assert_eq!(err.to_string(), "Nothing staged to commit");
+
+        fs::write(dir.path().join("file.txt"), "changed\n").unwrap();
+        repo.stage_file("file.txt").unwrap();
+        let oid = repo.commit("change file", false).unwrap();
+        assert_eq!(repo.head_message().unwrap(), "change file");
+
+        let amended = repo.commit("reworded", true).unwrap();
+        assert_ne!(oid, amended);
+        assert_eq!(repo.head_message().unwrap(), "reworded");
+    }
+
+    #[test]
+    fn test_head_info_branch_and_detached() {
+        let (_dir, repo) = repo_with_file(3);
+        let info = repo.head_info().unwrap();
+        assert!(info.branch.is_some());
+        assert_eq!(info.commit.as_ref().map(|c| c.len()), Some(7));
+        assert_eq!(info.upstream, None);
+        assert_eq!(info.operation, None);
+
+        let head = repo.repo.head().unwrap().target().unwrap();
+        repo.repo.set_head_detached(head).unwrap();
+        let info = repo.head_info().unwrap();
+        assert_eq!(info.branch, None);
+        assert_eq!(info.commit, Some(head.to_string()[..7].to_string()));
+    }
+
+    #[test]
+    fn test_head_info_upstream_without_history() {
+        let (dir, repo) = repo_with_file(3);
+        let branch = repo.head_info().unwrap().branch.unwrap();
+        // A remote-tracking ref to a commit that is not in the repository
+        let refs = dir.path().join(".git/refs/remotes/origin");
+        fs::create_dir_all(&refs).unwrap();
+        fs::write(refs.join(&branch), format!("{}\n", "1".repeat(40))).unwrap();
+        repo.repo
+            .remote("origin", "https://example.invalid/repo.git")
+            .unwrap();
+        let mut config = repo.repo.config().unwrap();
+        config
+            .set_str(&format!("branch.{}.remote", branch), "origin")
+            .unwrap();
+        config
+            .set_str(
+                &format!("branch.{}.merge", branch),
+                &format!("refs/heads/{}", branch),
+            )
+            .unwrap();
+
+        let info = repo.head_info().unwrap();
+        assert_eq!(info.upstream, Some(format!("origin/{}", branch)));
+        assert_eq!((info.ahead, info.behind), (0, 0));
+    }
+
+    #[test]
+    fn test_head_info_unborn_branch() {
+        let dir = TempDir::new().unwrap();
+        Repository::init(dir.path()).unwrap();
+        let repo = GitRepository::new(dir.path().to_path_buf()).unwrap();
+
+        let info = repo.head_info().unwrap();
+        assert!(info.branch.is_some());
+        assert_eq!(info.commit, None);
+    }
+
+    #[test]
+    fn test_stage_hunk_with_expanded_context() {
+        let (dir, repo) = repo_with_file(20);
+        edit_two_hunks(&dir);
+
+        let mut diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        let range = diff.context_range(1, true, 5);
+        repo.expand_context(&mut diff, &[range]).unwrap();
+        let hunk = &diff.hunks[1];
+        assert_eq!(hunk.lines[0].content, "line 12");
+        assert_eq!(hunk.lines[0].old_lineno, Some(12));
+
+        repo.stage_hunk("file.txt", hunk).unwrap();
+        assert_eq!(hunk_count(&repo, DiffSource::Unstaged), 1);
+
+        // Staged context comes from the index blob
+        let mut staged = repo
+            .get_file_diff("file.txt", None, DiffSource::Staged)
+            .unwrap();
+        let range = staged.context_range(0, true, 7);
+        repo.expand_context(&mut staged, &[range]).unwrap();
+        assert_eq!(staged.hunks[0].lines[0].content, "line 10");
+        repo.unstage_hunk("file.txt", &staged.hunks[0]).unwrap();
+        assert_eq!(hunk_count(&repo, DiffSource::Staged), 0);
+    }
+
+    #[test]
+    fn test_stage_hunk_keeps_crlf_line_endings() {
+        let (dir, repo) = repo_with_file(1);
+        let crlf = |edits: &[(usize, &str)]| -> String {
+            (1..=20)
+                .map(|i| match edits.iter().find(|(n, _)| *n == i) {
+                    Some((_, text)) => format!("{}\r\n", text),
+                    None => format!("line {}\r\n", i),
+                })
+                .collect()
+        };
+        let path = dir.path().join("file.txt");
+        fs::write(&path, crlf(&[])).unwrap();
+        repo.stage_file("file.txt").unwrap();
+        fs::write(&path, crlf(&[(1, "first changed"), (20, "last changed")])).unwrap();
+
+        let mut diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        let range = diff.context_range(1, true, 3);
+        repo.expand_context(&mut diff, &[range]).unwrap();
+        assert_eq!(diff.hunks[1].lines[0].content, "line 14");
+        assert!(diff.hunks[1].lines[0].crlf);
+        repo.stage_hunk("file.txt", &diff.hunks[1]).unwrap();
+
+        let mut index = repo.repo.index().unwrap();
+        index.read(true).unwrap();
+        let entry = index.get_path(Path::new("file.txt"), 0).unwrap();
+        let blob = repo.repo.find_blob(entry.id).unwrap();
+        assert_eq!(blob.content(), crlf(&[(20, "last changed")]).as_bytes());
+    }
+
+    #[test]
+    fn test_binary_diff_reports_sizes_and_image() {
+        let (dir, repo) = repo_with_file(1);
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&16u32.to_be_bytes());
+        png.extend_from_slice(&8u32.to_be_bytes());
+        png.extend_from_slice(&[0; 40]);
+        fs::write(dir.path().join("image.png"), &png).unwrap();
+
+        let diff = repo
+            .get_file_diff("image.png", None, DiffSource::Unstaged)
+            .unwrap();
+        assert!(diff.hunks.is_empty());
+        let Some(DiffNotice::Binary { old, new }) = diff.notice else {
+            panic!("expected a binary notice, got {:?}", diff.notice);
+        };
+        assert!(old.is_none());
+        let new = new.unwrap();
+        assert_eq!(new.size, png.len() as u64);
+        assert_eq!(
+            new.oid,
+            Oid::hash_object(ObjectType::Blob, &png)
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(new.image.map(|i| (i.width, i.height)), Some((16, 8)));
+    }
+
+    #[test]
+    fn test_lfs_pointer_diff_reports_objects() {
+        let (dir, repo) = repo_with_file(1);
+        let pointer = |oid: &str, size: u64| {
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\nsize {size}\n")
+        };
+        fs::write(dir.path().join("file.txt"), pointer("aaaa", 10)).unwrap();
+        repo.stage_file("file.txt").unwrap();
+        fs::write(dir.path().join("file.txt"), pointer("bbbb", 20)).unwrap();
+
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        let Some(DiffNotice::Lfs { old, new }) = diff.notice else {
+            panic!("expected an LFS notice, got {:?}", diff.notice);
+        };
+        assert_eq!(old.map(|o| (o.oid, o.size)), Some(("aaaa".to_string(), 10)));
+        assert_eq!(new.map(|n| (n.oid, n.size)), Some(("bbbb".to_string(), 20)));
+
+        // The staged side replaces plain text, so it is not an LFS change
+        let staged = repo
+            .get_file_diff("file.txt", None, DiffSource::Staged)
+            .unwrap();
+        assert_eq!(staged.notice, None);
+    }
+
+    #[test]
+    fn test_renames_are_paired_with_similarity() {
+        let (dir, repo) = repo_with_file(20);
+        let content = fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        fs::remove_file(dir.path().join("file.txt")).unwrap();
+        fs::write(
+            dir.path().join("moved.txt"),
+            content.replace("line 5\n", "five\n"),
+        )
+        .unwrap();
+
+        // Worktree rename: the deleted file and the untracked one are a pair
+        let files = repo.get_status().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "moved.txt");
+        assert_eq!(files[0].worktree_status, Some(FileStatus::Renamed));
+        assert_eq!(files[0].old_path(DiffSource::Unstaged), Some("file.txt"));
+
+        let diff = repo
+            .get_file_diff("moved.txt", Some("file.txt"), DiffSource::Unstaged)
+            .unwrap();
+        assert_eq!(diff.old_path.as_deref(), Some("file.txt"));
+        assert!(diff.similarity.is_some_and(|s| (50..100).contains(&s)));
+        let changed: Vec<&str> = diff.hunks[0]
+            .lines
+            .iter()
+            .filter(|l| l.kind != LineKind::Context)
+            .map(|l| l.content.as_str())
+            .collect();
+        assert_eq!(changed, ["line 5", "five"]);
+
+        // Staging both paths turns it into a staged rename
+        repo.stage_file("moved.txt").unwrap();
+        repo.stage_file("file.txt").unwrap();
+        let files = repo.get_status().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].index_status, Some(FileStatus::Renamed));
+        assert_eq!(files[0].old_path(DiffSource::Staged), Some("file.txt"));
+    }
+
+    #[test]
+    fn test_copies_and_disabled_detection() {
+        let (dir, mut repo) = repo_with_file(20);
+        // Like `git diff -C`, only files changed in the same commit are sources
+        fs::copy(dir.path().join("file.txt"), dir.path().join("copy.txt")).unwrap();
+        edit_two_hunks(&dir);
+        repo.stage_file("file.txt").unwrap();
+        repo.stage_file("copy.txt").unwrap();
+
+        let files = repo.get_status().unwrap();
+        let copy = files.iter().find(|f| f.path == "copy.txt").unwrap();
+        assert_eq!(copy.index_status, Some(FileStatus::Copied));
+        assert_eq!(copy.old_path(DiffSource::Staged), Some("file.txt"));
+
+        repo.set_diff_settings(DiffSettings {
+            rename_threshold: None,
+            ..DiffSettings::default()
+        });
+        let files = repo.get_status().unwrap();
+        let copy = files.iter().find(|f| f.path == "copy.txt").unwrap();
+        assert_eq!(copy.index_status, Some(FileStatus::Added));
+        assert_eq!(copy.old_path(DiffSource::Staged), None);
+    }
+
+    #[test]
+    fn test_resolve_merge_conflict() {
+        let (dir, repo) = repo_with_file(20);
+        let raw = Repository::open(dir.path()).unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        let edited = |edits: &[(usize, &str)]| -> String {
+            (1..=20)
+                .map(|i| match edits.iter().find(|(n, _)| *n == i) {
+                    Some((_, text)) => format!("{}\n", text),
+                    None => format!("line {}\n", i),
+                })
+                .collect()
+        };
+        let commit = |content: &str, update_ref: Option<&str>| {
+            let head = raw.head().unwrap().peel_to_commit().unwrap();
+            let blob = raw.blob(content.as_bytes()).unwrap();
+            let mut builder = raw.treebuilder(None).unwrap();
+            builder.insert("file.txt", blob, 0o100644).unwrap();
+            let tree = raw.find_tree(builder.write().unwrap()).unwrap();
+            raw.commit(update_ref, &sig, &sig, "edit", &tree, &[&head])
+                .unwrap()
+        };
+
+        let theirs = commit(&edited(&[(2, "theirs 2"), (15, "theirs 15")]), None);
+        commit(&edited(&[(2, "ours 2"), (8, "ours 8")]), Some("HEAD"));
+        raw.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+            .unwrap();
+        let theirs = raw.find_annotated_commit(theirs).unwrap();
+        raw.merge(&[&theirs], None, None).unwrap();
+
+        let files = repo.get_status().unwrap();
+        assert_eq!(files[0].worktree_status, Some(FileStatus::Conflicted));
+
+        let mut conflict = repo.conflict_file("file.txt").unwrap();
+        assert_eq!(conflict.conflict_count(), 1);
+        conflict.region_mut(0).unwrap().resolution = Some(Resolution::Both);
+        let merged = conflict.merged().unwrap();
+        assert_eq!(
+            merged,
+            edited(&[(2, "ours 2\ntheirs 2"), (8, "ours 8"), (15, "theirs 15")])
+        );
+
+        repo.resolve_conflict("file.txt", &merged).unwrap();
+        let files = repo.get_status().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].index_status, Some(FileStatus::Modified));
+        assert_eq!(files[0].worktree_status, None);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("file.txt")).unwrap(),
+            merged
+        );
+
+        let mut config = raw.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        assert!(repo.commit("merge", true).is_err());
+        let oid = repo.commit("merge", false).unwrap();
+        assert_eq!(raw.find_commit(oid).unwrap().parent_count(), 2);
+        assert_eq!(raw.state(), RepositoryState::Clean);
+    }
+
+    #[test]
+    fn test_commit_merge_resolved_to_ours() {
+        let (dir, repo) = repo_with_file(3);
+        let raw = Repository::open(dir.path()).unwrap();
+        let mut config = raw.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+
+        // A side commit changing the file, merged but resolved to HEAD's version
+        let head = raw.head().unwrap().peel_to_commit().unwrap();
+        let blob = raw.blob(b"theirs\n").unwrap();
+        let mut builder = raw.treebuilder(None).unwrap();
+        builder.insert("file.txt", blob, 0o100644).unwrap();
+        let tree = raw.find_tree(builder.write().unwrap()).unwrap();
+        let theirs = raw
+            .commit(None, &sig, &sig, "theirs", &tree, &[&head])
+            .unwrap();
+        let theirs = raw.find_annotated_commit(theirs).unwrap();
+        raw.merge(&[&theirs], None, None).unwrap();
+        raw.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+            .unwrap();
+        let mut index = raw.index().unwrap();
+        index.read_tree(&head.tree().unwrap()).unwrap();
+        index.write().unwrap();
+
+        let oid = repo.commit("merge, keeping ours", false).unwrap();
+        let merge = raw.find_commit(oid).unwrap();
+        assert_eq!(merge.parent_count(), 2);
+        assert_eq!(merge.tree_id(), head.tree_id());
+        assert_eq!(raw.state(), RepositoryState::Clean);
+        assert_eq!(
+            repo.commit("again", false).unwrap_err().to_string(),
+            "Nothing staged to commit"
+        );
+    }
+
+    #[test]
+    fn test_discard_file_reverts_or_deletes() {
+        let (dir, repo) = repo_with_file(5);
+        fs::write(dir.path().join("file.txt"), "changed\n").unwrap();
+        fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+        let before = repo.read_worktree_file("file.txt").unwrap();
+
+        repo.discard_file("file.txt").unwrap();
+        repo.discard_file("new.txt").unwrap();
+        assert!(repo.get_status().unwrap().is_empty());
+        assert_eq!(repo.read_worktree_file("new.txt").unwrap(), None);
+
+        // What was discarded can be written back
+        repo.write_worktree_file("file.txt", before.as_deref())
+            .unwrap();
+        let content = fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        assert_eq!(content, "changed\n");
+    }
+
+    #[test]
+    fn test_discard_lines_reverts_only_selection() {
+        let (dir, repo) = repo_with_file(5);
+        fs::write(
+            dir.path().join("file.txt"),
+            "line 1\nA\nB\nline 4\nline 5\n",
+        )
+        .unwrap();
+
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        let hunk = &diff.hunks[0];
+        let selected: Vec<usize> = hunk
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| matches!(l.content.as_str(), "line 2" | "A"))
+            .map(|(i, _)| i)
+            .collect();
+        repo.discard_lines("file.txt", hunk, &selected).unwrap();
+
+        let content = fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        assert_eq!(content, "line 1\nline 2\nB\nline 4\nline 5\n");
+    }
+
+    fn set_identity(dir: &TempDir) {
+        let raw = Repository::open(dir.path()).unwrap();
+        let mut config = raw.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+    }
+
+    #[test]
+    fn test_stash_all_list_and_pop() {
+        let (dir, mut repo) = repo_with_file(5);
+        set_identity(&dir);
+        fs::write(dir.path().join("file.txt"), "changed\n").unwrap();
+        fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+
+        repo.stash_save(&StashMode::All).unwrap();
+        assert!(repo.get_status().unwrap().is_empty());
+
+        let stashes = repo.stash_list().unwrap();
+        assert_eq!(stashes.len(), 1);
+        assert!(stashes[0].branch.is_some());
+        assert!(stashes[0].message.starts_with("WIP on "));
+        let mut files = stashes[0].files.clone();
+        files.sort();
+        assert_eq!(files, ["file.txt", "new.txt"]);
+        let patch = repo.stash_patch(&stashes[0].id).unwrap();
+        assert!(patch.iter().any(|l| l == "+changed"));
+        assert!(patch.iter().any(|l| l == "+new"));
+
+        repo.stash_apply(&stashes[0].id, true).unwrap();
+        assert_eq!(repo.get_status().unwrap().len(), 2);
+        assert!(repo.stash_list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stash_staged_keeps_unstaged_changes() {
+        let (dir, mut repo) = repo_with_file(20);
+        set_identity(&dir);
+        let staged: String = (1..=20)
+            .map(|i| match i {
+                1 => "first changed\n".to_string(),
+                _ => format!("line {}\n", i),
+            })
+            .collect();
+        fs::write(dir.path().join("file.txt"), staged).unwrap();
+        repo.stage_file("file.txt").unwrap();
+        edit_two_hunks(&dir);
+
+        repo.stash_save(&StashMode::Staged).unwrap();
+        let content = fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        assert!(content.starts_with("line 1\n"));
+        assert!(content.ends_with("last changed\n"));
+        let files = repo.get_status().unwrap();
+        assert_eq!(files[0].index_status, None);
+        assert_eq!(files[0].worktree_status, Some(FileStatus::Modified));
+
+        let stash = repo.stash_list().unwrap().remove(0);
+        let patch = repo.stash_patch(&stash.id).unwrap();
+        assert!(patch.iter().any(|l| l == "+first changed"));
+        assert!(!patch.iter().any(|l| l == "+last changed"));
+
+        repo.stash_drop(&stash.id).unwrap();
+        assert!(repo.stash_list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stash_selected_paths() {
+        let (dir, mut repo) = repo_with_file(5);
+        set_identity(&dir);
+        fs::write(dir.path().join("file.txt"), "changed\n").unwrap();
+        fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+
+        repo.stash_save(&StashMode::Paths(vec!["new.txt".to_string()]))
+            .unwrap();
+        let files = repo.get_status().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "file.txt");
+        assert_eq!(repo.stash_list().unwrap()[0].files, ["new.txt"]);
+    }
+
+    #[test]
+    fn test_discard_hunk_reverts_worktree() {
+        let (dir, repo) = repo_with_file(20);
+        edit_two_hunks(&dir);
+
+        let diff = repo
+            .get_file_diff("file.txt", None, DiffSource::Unstaged)
+            .unwrap();
+        repo.discard_hunk("file.txt", &diff.hunks[1]).unwrap();
+
+        let content = fs::read_to_string(dir.path().join("file.txt")).unwrap();
+        assert!(content.starts_with("first changed\n"));
+        assert!(content.ends_with("line 20\n"));
+        assert_eq!(hunk_count(&repo, DiffSource::Unstaged), 1);
+    }
+}
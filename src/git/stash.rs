@@ -0,0 +1,84 @@
+/// How much of the working tree a new stash takes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StashMode {
+    /// Staged, unstaged and untracked changes
+    All,
+    /// Staged changes only, leaving unstaged ones in place
+    Staged,
+    /// Every change to these paths
+    Paths(Vec<String>),
+}
+
+/// One entry of the stash list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StashEntry {
+    /// Position in the list, 0 being the newest
+    pub index: usize,
+    /// Stash commit id, which unlike the index stays valid as stashes come
+    /// and go
+    pub id: String,
+    pub message: String,
+    /// Branch the stash was made on
+    pub branch: Option<String>,
+    /// Creation time, seconds since the epoch
+    pub time: i64,
+    /// Paths the stash changes, untracked files included
+    pub files: Vec<String>,
+}
+
+impl StashEntry {
+    /// The `stash@{n}` name git uses for it
+    pub fn name(&self) -> String {
+        format!("stash@{{{}}}", self.index)
+    }
+
+    /// The message without the "WIP on <branch>:" or "On <branch>:" prefix
+    pub fn summary(&self) -> &str {
+        match self.message.split_once(": ") {
+            Some((prefix, rest)) if stash_branch(prefix).is_some() => rest,
+            _ => &self.message,
+        }
+    }
+}
+
+/// Branch a stash was made on, from its "WIP on <branch>: ..." (default
+/// message) or "On <branch>: ..." (custom message) message
+pub fn stash_branch(message: &str) -> Option<String> {
+    let rest = message
+        .strip_prefix("WIP on ")
+        .or_else(|| message.strip_prefix("On "))?;
+    let branch = rest.split(':').next()?;
+    (!branch.is_empty()).then(|| branch.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stash_branch_from_message() {
+        assert_eq!(
+            stash_branch("WIP on main: 1a2b3c4 Fix parser").as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            stash_branch("On feature/login: try another layout").as_deref(),
+            Some("feature/login")
+        );
+        assert_eq!(stash_branch("autostash"), None);
+    }
+
+    #[test]
+    fn test_summary_drops_branch_prefix() {
+        let entry = StashEntry {
+            index: 2,
+            id: "abc".to_string(),
+            message: "On main: halfway there".to_string(),
+            branch: Some("main".to_string()),
+            time: 0,
+            files: Vec::new(),
+        };
+        assert_eq!(entry.name(), "stash@{2}");
+        assert_eq!(entry.summary(), "halfway there");
+    }
+}
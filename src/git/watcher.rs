@@ -1,23 +1,44 @@
 use crate::event::Event;
+use color_eyre::eyre::WrapErr;
 use color_eyre::Result;
+use git2::Repository;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use notify::{RecommendedWatcher, RecursiveMode};
 use notify_debouncer_mini::{new_debouncer, Debouncer};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::mem::discriminant;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc as std_mpsc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
+/// Globs that are always excluded: editor swap and backup files
+const DEFAULT_EXCLUDES: &[&str] = &["*.swp", "*.swo", "*~"];
+
+/// Cached directory ignore decisions kept before starting over, bounding
+/// the cache in repositories with huge untracked trees
+const MAX_IGNORED_DIRS: usize = 4096;
+
 /// Git file watcher using notify-rs with debouncing
 pub struct GitWatcher {
     _watcher: Debouncer<RecommendedWatcher>,
 }
 
 impl GitWatcher {
-    pub fn new(repo_path: PathBuf, sender: mpsc::UnboundedSender<Event>) -> Result<Self> {
+    /// Watch `repo_path`, dropping paths matched by the repository's ignore
+    /// rules or by any of the `excludes` globs (relative to the repo root).
+    /// Events are batched until none arrived for `debounce`.
+    pub fn new(
+        repo_path: PathBuf,
+        sender: mpsc::UnboundedSender<Event>,
+        excludes: &[String],
+        debounce: Duration,
+    ) -> Result<Self> {
+        let mut filter = PathFilter::new(&repo_path, excludes)?;
         let (tx, rx) = std_mpsc::channel();
 
-        // Create debounced watcher with 200ms debounce
-        let mut debouncer = new_debouncer(Duration::from_millis(200), tx)?;
+        // Create debounced watcher
+        let mut debouncer = new_debouncer(debounce, tx)?;
 
         // Watch the repository directory
         debouncer
@@ -27,55 +48,70 @@ impl GitWatcher {
         // Spawn a thread to forward events
         let sender_clone = sender.clone();
         let repo_path_clone = repo_path.clone();
+        let git_dir = repo_path.join(".git");
         std::thread::spawn(move || {
             loop {
                 match rx.recv() {
                     Ok(Ok(events)) => {
-                        // Collect changed file paths
+                        // Collect changed file paths and distinct git-dir changes
                         let mut changed_paths: Vec<String> = Vec::new();
-                        let mut has_git_change = false;
+                        let mut git_events: Vec<Event> = Vec::new();
 
                         for e in events.iter() {
                             let path = &e.path;
                             let path_str = path.to_string_lossy();
 
-                            // Skip directories we don't care about
-                            if path_str.contains(".git/objects")
-                                || path_str.contains(".git/logs")
-                                || path_str.contains(".git/FETCH_HEAD")
-                                || path_str.contains("/target/")
-                                || path_str.contains("/.git/hooks")
-                                || path_str.ends_with(".swp")
-                                || path_str.ends_with(".swo")
-                                || path_str.ends_with("~")
-                            {
+                            // Classify changes inside the .git directory
+                            let git_rel_path = match path.strip_prefix(&git_dir) {
+                                Ok(rel_path) => Some(rel_path.to_string_lossy().to_string()),
+                                Err(_) => path_str
+                                    .split_once("/.git/")
+                                    .map(|(_, rel_path)| rel_path.to_string()),
+                            };
+                            if let Some(rel_path) = git_rel_path {
+                                if rel_path == "info/exclude" {
+                                    filter.forget_ignored_dirs();
+                                }
+                                if let Some(event) = classify_git_path(&rel_path) {
+                                    if !git_events
+                                        .iter()
+                                        .any(|e| discriminant(e) == discriminant(&event))
+                                    {
+                                        git_events.push(event);
+                                    }
+                                }
                                 continue;
                             }
 
-                            // Check if it's a git metadata change
-                            if path_str.contains(".git/index") || path_str.contains(".git/HEAD") {
-                                has_git_change = true;
+                            // It's a working directory file
+                            let Ok(rel_path) = path.strip_prefix(&repo_path_clone) else {
+                                changed_paths.push(path_str.to_string());
                                 continue;
-                            }
+                            };
 
-                            // It's a working directory file
-                            if !path_str.contains("/.git/") {
-                                // Get relative path from repo root
-                                if let Ok(rel_path) = path.strip_prefix(&repo_path_clone) {
-                                    changed_paths.push(rel_path.to_string_lossy().to_string());
-                                } else {
-                                    changed_paths.push(path_str.to_string());
-                                }
-                                has_git_change = true;
+                            // Skip ignored and excluded files
+                            if filter.is_excluded(rel_path) {
+                                continue;
                             }
+                            changed_paths.push(rel_path.to_string_lossy().to_string());
                         }
 
-                        if has_git_change {
-                            let _ = sender_clone.send(Event::GitChange(changed_paths));
+                        if !changed_paths.is_empty()
+                            && sender_clone.send(Event::GitChange(changed_paths)).is_err()
+                        {
+                            break;
+                        }
+                        if git_events
+                            .into_iter()
+                            .any(|event| sender_clone.send(event).is_err())
+                        {
+                            break;
                         }
                     }
-                    Ok(Err(_e)) => {
-                        // Ignore watch errors silently
+                    Ok(Err(e)) => {
+                        if sender_clone.send(Event::WatchError(e.to_string())).is_err() {
+                            break;
+                        }
                     }
                     Err(_) => break,
                 }
@@ -87,3 +123,207 @@ impl GitWatcher {
         })
     }
 }
+
+/// Decides which working tree paths are worth a status refresh, using the
+/// repository's ignore rules plus extra exclude globs
+struct PathFilter {
+    repo: Option<Repository>,
+    excludes: GlobSet,
+    /// Ignore decision per directory, so event storms inside ignored
+    /// directories (node_modules, __pycache__, ...) are resolved from cache
+    ignored_dirs: HashMap<PathBuf, bool>,
+}
+
+impl PathFilter {
+    fn new(repo_path: &Path, excludes: &[String]) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in DEFAULT_EXCLUDES
+            .iter()
+            .copied()
+            .chain(excludes.iter().map(String::as_str))
+        {
+            let glob = Glob::new(pattern)
+                .wrap_err_with(|| format!("Invalid exclude glob '{}'", pattern))?;
+            builder.add(glob);
+        }
+
+        Ok(Self {
+            repo: Repository::open(repo_path).ok(),
+            excludes: builder.build()?,
+            ignored_dirs: HashMap::new(),
+        })
+    }
+
+    /// Check a path relative to the repository root
+    fn is_excluded(&mut self, rel_path: &Path) -> bool {
+        if self.excludes.is_match(rel_path) {
+            return true;
+        }
+
+        // Ignore rules may have changed: forget cached directory decisions
+        if rel_path
+            .file_name()
+            .is_some_and(|name| name == ".gitignore")
+        {
+            self.forget_ignored_dirs();
+            return false;
+        }
+
+        let Some(repo) = &self.repo else {
+            return false;
+        };
+
+        // Check ancestor directories from the top, stopping at the first ignored one
+        let mut ancestors: Vec<&Path> = rel_path
+            .ancestors()
+            .skip(1)
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            if self.ignored_dirs.len() >= MAX_IGNORED_DIRS && !self.ignored_dirs.contains_key(dir) {
+                self.ignored_dirs.clear();
+            }
+            let ignored = *self
+                .ignored_dirs
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| {
+                    // A trailing slash makes git match directory-only patterns
+                    let dir_path = format!("{}/", dir.to_string_lossy());
+                    repo.is_path_ignored(dir_path).unwrap_or(false)
+                });
+            if ignored {
+                return true;
+            }
+        }
+
+        repo.is_path_ignored(rel_path).unwrap_or(false)
+    }
+
+    /// Drop cached directory decisions after a change to the ignore rules
+    fn forget_ignored_dirs(&mut self) {
+        self.ignored_dirs.clear();
+    }
+}
+
+/// Map a path relative to the .git directory to the event it signals.
+/// Objects, logs, hooks, lock files and other internals map to `None`.
+fn classify_git_path(rel_path: &str) -> Option<Event> {
+    let path = rel_path.replace('\\', "/");
+
+    if path.ends_with(".lock") {
+        return None;
+    }
+
+    match path.as_str() {
+        "index" => Some(Event::IndexChange),
+        "HEAD" => Some(Event::HeadChange),
+        "packed-refs" => Some(Event::RefsChange),
+        "refs/stash" => Some(Event::StashChange),
+        "MERGE_HEAD" | "CHERRY_PICK_HEAD" | "REVERT_HEAD" | "BISECT_LOG" => {
+            Some(Event::OperationChange)
+        }
+        _ if path.starts_with("refs/") => Some(Event::RefsChange),
+        _ if path.starts_with("rebase-merge") || path.starts_with("rebase-apply") => {
+            Some(Event::OperationChange)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(path: &str) -> Option<String> {
+        classify_git_path(path).map(|e| format!("{:?}", e))
+    }
+
+    #[test]
+    fn test_classify_metadata_files() {
+        assert_eq!(classify("index").as_deref(), Some("IndexChange"));
+        assert_eq!(classify("HEAD").as_deref(), Some("HeadChange"));
+        assert_eq!(classify("packed-refs").as_deref(), Some("RefsChange"));
+        assert_eq!(classify("refs/heads/main").as_deref(), Some("RefsChange"));
+        assert_eq!(classify("refs/tags/v1.0").as_deref(), Some("RefsChange"));
+        assert_eq!(classify("refs/stash").as_deref(), Some("StashChange"));
+    }
+
+    #[test]
+    fn test_classify_operation_state() {
+        assert_eq!(classify("MERGE_HEAD").as_deref(), Some("OperationChange"));
+        assert_eq!(classify("rebase-merge").as_deref(), Some("OperationChange"));
+        assert_eq!(
+            classify("rebase-apply/next").as_deref(),
+            Some("OperationChange")
+        );
+    }
+
+    fn filter_for(gitignore: &str, excludes: &[&str]) -> (tempfile::TempDir, PathFilter) {
+        let dir = tempfile::TempDir::new().unwrap();
+        Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join(".gitignore"), gitignore).unwrap();
+        let excludes: Vec<String> = excludes.iter().map(|s| s.to_string()).collect();
+        let filter = PathFilter::new(dir.path(), &excludes).unwrap();
+        (dir, filter)
+    }
+
+    #[test]
+    fn test_filter_respects_gitignore() {
+        let (_dir, mut filter) = filter_for("node_modules/\n__pycache__/\n*.log\n", &[]);
+
+        assert!(filter.is_excluded(Path::new("node_modules/pkg/index.js")));
+        assert!(filter.is_excluded(Path::new("app/__pycache__/mod.cpython-312.pyc")));
+        assert!(filter.is_excluded(Path::new("debug.log")));
+        assert!(!filter.is_excluded(Path::new("src/main.rs")));
+        assert_eq!(
+            filter.ignored_dirs.get(Path::new("node_modules")),
+            Some(&true)
+        );
+    }
+
+    #[test]
+    fn test_filter_forgets_and_bounds_ignored_dirs() {
+        let (dir, mut filter) = filter_for("", &[]);
+        assert!(!filter.is_excluded(Path::new("build/out.o")));
+
+        // The exclude file is not in the working tree, the watcher has the
+        // filter forget its decisions when it changes
+        std::fs::write(dir.path().join(".git/info/exclude"), "build/\n").unwrap();
+        filter.forget_ignored_dirs();
+        assert!(filter.is_excluded(Path::new("build/out.o")));
+
+        for i in 0..MAX_IGNORED_DIRS + 10 {
+            filter.is_excluded(&Path::new("src").join(i.to_string()).join("a.rs"));
+        }
+        assert!(filter.ignored_dirs.len() <= MAX_IGNORED_DIRS);
+    }
+
+    #[test]
+    fn test_filter_extra_and_default_excludes() {
+        let (_dir, mut filter) = filter_for("", &["generated/**", "**/*.snap"]);
+
+        assert!(filter.is_excluded(Path::new("generated/api.rs")));
+        assert!(filter.is_excluded(Path::new("tests/snapshots/a.snap")));
+        assert!(filter.is_excluded(Path::new("src/.main.rs.swp")));
+        assert!(!filter.is_excluded(Path::new("src/generated.rs")));
+    }
+
+    #[test]
+    fn test_filter_rejects_invalid_glob() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let excludes = vec!["[unclosed".to_string()];
+        assert!(PathFilter::new(dir.path(), &excludes).is_err());
+    }
+
+    #[test]
+    fn test_classify_ignores_internals() {
+        assert_eq!(classify("objects/ab/cdef"), None);
+        assert_eq!(classify("logs/HEAD"), None);
+        assert_eq!(classify("index.lock"), None);
+        assert_eq!(classify("refs/heads/main.lock"), None);
+        assert_eq!(classify("FETCH_HEAD"), None);
+        assert_eq!(classify("ORIG_HEAD"), None);
+        assert_eq!(classify("hooks/pre-commit"), None);
+    }
+}
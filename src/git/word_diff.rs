@@ -0,0 +1,235 @@
+use super::diff::DiffHunk;
+use std::ops::Range;
+
+/// Above this many token pairs a line pair is too costly to compare and is
+/// shown without emphasis
+const MAX_COMPARISONS: usize = 250_000;
+
+/// How finely changed lines are compared with their counterpart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Granularity {
+    /// Whole lines only, no intra-line emphasis
+    Line,
+    /// Words, runs of whitespace and single punctuation characters
+    #[default]
+    Word,
+    /// Single characters
+    Char,
+}
+
+impl Granularity {
+    pub fn next(self) -> Self {
+        match self {
+            Granularity::Line => Granularity::Word,
+            Granularity::Word => Granularity::Char,
+            Granularity::Char => Granularity::Line,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Granularity::Line => "line",
+            Granularity::Word => "word",
+            Granularity::Char => "char",
+        }
+    }
+}
+
+/// Byte ranges of each hunk line's content that differ from the line it is
+/// paired with (the n-th deletion of a change block with its n-th addition).
+/// Unpaired and context lines get no ranges.
+pub fn hunk_emphasis(hunk: &DiffHunk, granularity: Granularity) -> Vec<Vec<Range<usize>>> {
+    let mut emphasis = vec![Vec::new(); hunk.lines.len()];
+    if granularity == Granularity::Line {
+        return emphasis;
+    }
+
+    for row in hunk.split_rows() {
+        let (Some(old), Some(new)) = (row.old, row.new) else {
+            continue;
+        };
+        if old == new {
+            continue;
+        }
+        let (old_ranges, new_ranges) = changed_ranges(
+            &hunk.lines[old].content,
+            &hunk.lines[new].content,
+            granularity,
+        );
+        emphasis[old] = old_ranges;
+        emphasis[new] = new_ranges;
+    }
+    emphasis
+}
+
+/// Compare two versions of a line token by token, returning the byte ranges
+/// of each side not part of their longest common subsequence
+fn changed_ranges(
+    old: &str,
+    new: &str,
+    granularity: Granularity,
+) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let old_tokens = tokenize(old, granularity);
+    let new_tokens = tokenize(new, granularity);
+    if old_tokens.len() * new_tokens.len() > MAX_COMPARISONS {
+        return (Vec::new(), Vec::new());
+    }
+
+    let text = |s: &str, r: &Range<usize>| s[r.clone()].to_string();
+    let a: Vec<String> = old_tokens.iter().map(|r| text(old, r)).collect();
+    let b: Vec<String> = new_tokens.iter().map(|r| text(new, r)).collect();
+
+    // lengths[i][j]: LCS length of a[i..] and b[j..]
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut old_kept = vec![false; a.len()];
+    let mut new_kept = vec![false; b.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            old_kept[i] = true;
+            new_kept[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    // Lines sharing nothing but whitespace are a rewrite, not an edit
+    let shares_content = a
+        .iter()
+        .zip(&old_kept)
+        .any(|(token, kept)| *kept && !token.trim().is_empty());
+    if !shares_content {
+        return (Vec::new(), Vec::new());
+    }
+
+    (
+        merge_unkept(&old_tokens, &old_kept),
+        merge_unkept(&new_tokens, &new_kept),
+    )
+}
+
+/// Join adjacent tokens outside the common subsequence into ranges
+fn merge_unkept(tokens: &[Range<usize>], kept: &[bool]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (token, kept) in tokens.iter().zip(kept) {
+        if *kept {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.end == token.start => last.end = token.end,
+            _ => ranges.push(token.clone()),
+        }
+    }
+    ranges
+}
+
+/// Split a line into token byte ranges
+fn tokenize(line: &str, granularity: Granularity) -> Vec<Range<usize>> {
+    let mut tokens: Vec<Range<usize>> = Vec::new();
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            1
+        } else if c.is_whitespace() {
+            2
+        } else {
+            3
+        }
+    };
+
+    let mut previous = 0;
+    for (offset, c) in line.char_indices() {
+        let current = class(c);
+        let end = offset + c.len_utf8();
+        // Words and whitespace runs grow; punctuation is always its own token
+        let extends = granularity == Granularity::Word && current == previous && current != 3;
+        match tokens.last_mut() {
+            Some(last) if extends => last.end = end,
+            _ => tokens.push(offset..end),
+        }
+        previous = current;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::diff::{DiffLine, LineKind};
+
+    fn emphasized<'a>(line: &'a str, ranges: &[Range<usize>]) -> Vec<&'a str> {
+        ranges.iter().map(|r| &line[r.clone()]).collect()
+    }
+
+    #[test]
+    fn test_word_granularity_marks_changed_words() {
+        let old = "let total = count + 1;";
+        let new = "let total = amount + 1;";
+        let (old_ranges, new_ranges) = changed_ranges(old, new, Granularity::Word);
+        assert_eq!(emphasized(old, &old_ranges), vec!["count"]);
+        assert_eq!(emphasized(new, &new_ranges), vec!["amount"]);
+    }
+
+    #[test]
+    fn test_char_granularity_marks_changed_characters() {
+        let old = "fn füo(x: u8)";
+        let new = "fn foo(x: u16)";
+        let (old_ranges, new_ranges) = changed_ranges(old, new, Granularity::Char);
+        assert_eq!(emphasized(old, &old_ranges), vec!["ü", "8"]);
+        assert_eq!(emphasized(new, &new_ranges), vec!["o", "16"]);
+    }
+
+    #[test]
+    fn test_unrelated_lines_get_no_emphasis() {
+        let (old_ranges, new_ranges) =
+            changed_ranges("alpha beta", "gamma delta", Granularity::Word);
+        assert!(old_ranges.is_empty() && new_ranges.is_empty());
+    }
+
+    #[test]
+    fn test_hunk_emphasis_pairs_lines() {
+        let line = |kind, content: &str| DiffLine {
+            kind,
+            content: content.to_string(),
+            old_lineno: None,
+            new_lineno: None,
+            crlf: false,
+        };
+        let hunk = DiffHunk {
+            header: "@@ -1,2 +1,3 @@".to_string(),
+            old_start: 1,
+            old_lines: 2,
+            new_start: 1,
+            new_lines: 3,
+            lines: vec![
+                line(LineKind::Context, "a b"),
+                line(LineKind::Deletion, "x = 1"),
+                line(LineKind::Addition, "x = 2"),
+                line(LineKind::Addition, "y = 3"),
+            ],
+        };
+
+        let emphasis = hunk_emphasis(&hunk, Granularity::Word);
+        assert!(emphasis[0].is_empty());
+        assert_eq!(emphasized("x = 1", &emphasis[1]), vec!["1"]);
+        assert_eq!(emphasized("x = 2", &emphasis[2]), vec!["2"]);
+        // The second addition has no deletion to pair with
+        assert!(emphasis[3].is_empty());
+        assert!(hunk_emphasis(&hunk, Granularity::Line)
+            .iter()
+            .all(Vec::is_empty));
+    }
+}
@@ -0,0 +1,634 @@
+use super::diff::DIFF_PREVIEW_LINES;
+use super::{DiffSettings, DiffSource, FileChange, FileDiff, GitRepository, Granularity};
+use crate::event::Event;
+use crate::ui::highlight::{DiffHighlights, SyntaxHighlighter};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::mpsc as std_mpsc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc;
+
+/// Safety-net full status rescan interval, catching anything the
+/// incremental path refresh missed
+const FULL_RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Above this many changed paths a full rescan is cheaper than per-path queries
+const MAX_INCREMENTAL_PATHS: usize = 200;
+
+/// Read-only git work handed to the background worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitRequest {
+    /// Re-read the status of `paths`, or of the whole repository when `full`
+    Status { paths: Vec<String>, full: bool },
+    /// Re-read branch, upstream and operation state
+    Head,
+    /// Compute the diff of one side of a file
+    Diff(DiffKey),
+    /// Use these options for all following diffs and line counts
+    Settings(DiffSettings),
+    /// Highlight all following diffs with this syntect theme
+    SyntaxTheme(&'static str),
+    /// Re-read the stash list
+    Stashes,
+    /// Compute the patch of the stash with this commit id
+    StashPatch(String),
+}
+
+/// Result of a status request
+#[derive(Debug, Clone)]
+pub enum StatusUpdate {
+    /// Status of the whole repository, sorted newest first, plus the paths
+    /// that triggered the rescan
+    Full {
+        files: Vec<FileChange>,
+        changed_paths: Vec<String>,
+    },
+    /// New state of individual paths, `None` meaning they have no changes anymore
+    Paths(Vec<(String, Option<FileChange>)>),
+}
+
+/// Identifies one computed diff: the file side plus the state it was computed for
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DiffKey {
+    pub path: String,
+    /// Rename or copy source the diff is relative to
+    pub old_path: Option<String>,
+    pub source: DiffSource,
+    /// Modification time of the file when the diff was requested
+    pub mtime: Option<SystemTime>,
+    /// `DiffCache` generation when the diff was requested
+    pub generation: u64,
+    /// New-side lines to show as extra context, sorted and disjoint
+    pub expanded: Vec<Range<u32>>,
+    /// Load the whole diff even when it is too large to show by default
+    pub full: bool,
+    /// Intra-line emphasis to compute along with the diff
+    pub granularity: Granularity,
+}
+
+/// Turns file watcher events into worker requests, the same way for the TUI
+/// and the headless modes
+#[derive(Debug)]
+pub struct RefreshPlanner {
+    /// Last time the whole repository status was rescanned
+    last_full_scan: Instant,
+}
+
+impl Default for RefreshPlanner {
+    fn default() -> Self {
+        Self {
+            last_full_scan: Instant::now(),
+        }
+    }
+}
+
+impl RefreshPlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests re-reading what a watcher event may have changed, none for
+    /// other events. `is_rename_path` tells whether a path is either side of
+    /// a known rename or copy.
+    pub fn requests(
+        &mut self,
+        event: &Event,
+        is_rename_path: impl Fn(&str) -> bool,
+    ) -> Vec<GitRequest> {
+        match event {
+            Event::GitChange(paths) => vec![self.status(paths.clone(), is_rename_path)],
+            // Staged state of files may differ
+            Event::IndexChange => vec![self.status(Vec::new(), is_rename_path)],
+            // Branch switch, checkout or merge/rebase state change: files
+            // and conflicts may differ too. A commit only moves the branch
+            // ref, yet what is staged against HEAD changes with it
+            Event::HeadChange | Event::OperationChange | Event::RefsChange => {
+                vec![GitRequest::Head, self.status(Vec::new(), is_rename_path)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Status request for `paths`. Only the changed paths are re-queried;
+    /// an empty list, one too large to be worth it, paths a per-path update
+    /// would split off a rename, or an overdue safety-net rescan make it a
+    /// rescan of the whole repository.
+    pub fn status(
+        &mut self,
+        paths: Vec<String>,
+        is_rename_path: impl Fn(&str) -> bool,
+    ) -> GitRequest {
+        let full = paths.is_empty()
+            || paths.len() > MAX_INCREMENTAL_PATHS
+            || self.last_full_scan.elapsed() >= FULL_RESCAN_INTERVAL
+            || paths.iter().any(|path| is_rename_path(path));
+        if full {
+            self.last_full_scan = Instant::now();
+        }
+        GitRequest::Status { paths, full }
+    }
+
+    /// Time left until the safety-net rescan is due
+    pub fn until_rescan(&self) -> Duration {
+        FULL_RESCAN_INTERVAL.saturating_sub(self.last_full_scan.elapsed())
+    }
+
+    /// The safety-net rescan, once it is due
+    pub fn rescan(&mut self) -> Option<GitRequest> {
+        self.until_rescan()
+            .is_zero()
+            .then(|| self.status(Vec::new(), |_| false))
+    }
+}
+
+/// Background thread owning its own repository handle, so status scans and
+/// diffs never block the UI loop. Results come back as `Event`s.
+pub struct GitWorker {
+    sender: std_mpsc::Sender<GitRequest>,
+}
+
+impl GitWorker {
+    pub fn new(repo_path: PathBuf, events: mpsc::UnboundedSender<Event>) -> Result<Self> {
+        let mut repo = GitRepository::new(repo_path)?;
+        let mut highlighter = SyntaxHighlighter::new();
+        let (sender, receiver) = std_mpsc::channel::<GitRequest>();
+
+        std::thread::spawn(move || {
+            while let Ok(request) = receiver.recv() {
+                // Fold everything queued up meanwhile into as little work as possible
+                let mut requests = vec![request];
+                requests.extend(receiver.try_iter());
+
+                for request in coalesce(requests) {
+                    let Some(event) = run(&mut repo, &mut highlighter, request) else {
+                        continue;
+                    };
+                    if events.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Self { sender })
+    }
+
+    pub fn send(&self, request: GitRequest) -> Result<()> {
+        self.sender
+            .send(request)
+            .map_err(|_| eyre!("Git worker stopped"))
+    }
+}
+
+fn run(
+    repo: &mut GitRepository,
+    highlighter: &mut SyntaxHighlighter,
+    request: GitRequest,
+) -> Option<Event> {
+    let event = match request {
+        GitRequest::Status { paths, full } => {
+            // Paths status_file cannot answer for fall back to a full rescan
+            let incremental = if full {
+                None
+            } else {
+                repo.get_status_for_paths(&paths).ok()
+            };
+            let update = match incremental {
+                Some(changes) => Ok(StatusUpdate::Paths(changes)),
+                None => repo.get_status().map(|files| StatusUpdate::Full {
+                    files,
+                    changed_paths: paths,
+                }),
+            };
+            match update {
+                Ok(update) => Event::StatusLoaded(update),
+                Err(e) => Event::GitError("refresh", e.to_string()),
+            }
+        }
+        GitRequest::Head => match repo.head_info() {
+            Ok(head) => Event::HeadLoaded(head),
+            Err(e) => Event::GitError("read HEAD", e.to_string()),
+        },
+        GitRequest::Diff(key) => {
+            // Unreadable diffs are shown as empty rather than retried forever
+            let mut diff = repo
+                .get_file_diff(&key.path, key.old_path.as_deref(), key.source)
+                .unwrap_or_else(|_| FileDiff {
+                    path: key.path.clone(),
+                    source: key.source,
+                    old_path: None,
+                    similarity: None,
+                    hunks: Vec::new(),
+                    notice: None,
+                });
+            if !key.expanded.is_empty() {
+                // Without the file's content the diff keeps its own context
+                let _ = repo.expand_context(&mut diff, &key.expanded);
+            }
+            if !key.full {
+                diff.truncate(DIFF_PREVIEW_LINES);
+            }
+            let highlights = highlighter.highlight(&diff, key.granularity);
+            Event::DiffLoaded(key, diff, Box::new(highlights))
+        }
+        GitRequest::Settings(settings) => {
+            repo.set_diff_settings(settings);
+            return None;
+        }
+        GitRequest::SyntaxTheme(theme) => {
+            highlighter.set_theme(theme);
+            return None;
+        }
+        GitRequest::Stashes => match repo.stash_list() {
+            Ok(stashes) => Event::StashesLoaded(stashes),
+            Err(e) => Event::GitError("list stashes", e.to_string()),
+        },
+        GitRequest::StashPatch(id) => match repo.stash_patch(&id) {
+            Ok(lines) => Event::StashPatchLoaded(id, lines),
+            Err(e) => Event::GitError("show stash", e.to_string()),
+        },
+    };
+    Some(event)
+}
+
+/// Merge queued requests: the latest settings and syntax theme first, then
+/// HEAD, so status results never come with an outdated branch, then one
+/// status request (full if any asked for it), then each distinct diff, then
+/// the stash list and each distinct stash patch
+fn coalesce(requests: Vec<GitRequest>) -> Vec<GitRequest> {
+    let mut settings: Option<DiffSettings> = None;
+    let mut syntax_theme: Option<&'static str> = None;
+    let mut status: Option<(Vec<String>, bool)> = None;
+    let mut head = false;
+    let mut diffs: Vec<DiffKey> = Vec::new();
+    let mut stashes = false;
+    let mut stash_patches: Vec<String> = Vec::new();
+
+    for request in requests {
+        match request {
+            GitRequest::Status { paths, full } => {
+                let (merged, merged_full) = status.get_or_insert_with(Default::default);
+                for path in paths {
+                    if !merged.contains(&path) {
+                        merged.push(path);
+                    }
+                }
+                *merged_full |= full;
+            }
+            GitRequest::Head => head = true,
+            GitRequest::Settings(new_settings) => settings = Some(new_settings),
+            GitRequest::SyntaxTheme(theme) => syntax_theme = Some(theme),
+            GitRequest::Diff(key) => {
+                if !diffs.contains(&key) {
+                    diffs.push(key);
+                }
+            }
+            GitRequest::Stashes => stashes = true,
+            GitRequest::StashPatch(id) => {
+                if !stash_patches.contains(&id) {
+                    stash_patches.push(id);
+                }
+            }
+        }
+    }
+
+    let mut merged: Vec<GitRequest> = settings.into_iter().map(GitRequest::Settings).collect();
+    merged.extend(syntax_theme.map(GitRequest::SyntaxTheme));
+    if head {
+        merged.push(GitRequest::Head);
+    }
+    if let Some((paths, full)) = status {
+        merged.push(GitRequest::Status { paths, full });
+    }
+    merged.extend(diffs.into_iter().map(GitRequest::Diff));
+    if stashes {
+        merged.push(GitRequest::Stashes);
+    }
+    merged.extend(stash_patches.into_iter().map(GitRequest::StashPatch));
+    merged
+}
+
+/// Diffs computed by the worker, per file side. Entries outlive their
+/// freshness so the view keeps showing the last diff while a new one loads.
+#[derive(Debug, Default)]
+pub struct DiffCache {
+    entries: HashMap<(String, DiffSource), (DiffKey, FileDiff, DiffHighlights)>,
+    /// Requested from the worker and not answered yet
+    pending: HashSet<DiffKey>,
+    /// Context revealed around hunks, per file side
+    expansions: HashMap<(String, DiffSource), Expansion>,
+    /// File sides whose diff is loaded in full despite its size
+    full: HashSet<(String, DiffSource)>,
+    /// Bumped when diffs may have changed without any file mtime changing
+    /// (index updates, HEAD moves)
+    generation: u64,
+}
+
+/// What the new side of a diff depends on: the working tree file for
+/// unstaged diffs, the index for staged ones
+type NewSideVersion = (Option<SystemTime>, u64);
+
+/// New-side lines revealed around the hunks of a file side, along with the
+/// version of that side their numbers refer to
+#[derive(Debug, Default)]
+struct Expansion {
+    version: NewSideVersion,
+    ranges: Vec<Range<u32>>,
+}
+
+fn new_side_version(
+    source: DiffSource,
+    mtime: Option<SystemTime>,
+    generation: u64,
+) -> NewSideVersion {
+    match source {
+        DiffSource::Unstaged => (mtime, 0),
+        DiffSource::Staged => (None, generation),
+    }
+}
+
+impl DiffCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Key for the current state of one side of `file`, emphasized by `granularity`
+    pub fn key(&self, file: &FileChange, source: DiffSource, granularity: Granularity) -> DiffKey {
+        let version = new_side_version(source, file.modified_time, self.generation);
+        DiffKey {
+            path: file.path.clone(),
+            old_path: file.old_path(source).map(str::to_string),
+            source,
+            mtime: file.modified_time,
+            generation: self.generation,
+            expanded: self
+                .expansions
+                .get(&(file.path.clone(), source))
+                .filter(|expansion| expansion.version == version)
+                .map(|expansion| expansion.ranges.clone())
+                .unwrap_or_default(),
+            full: self.full.contains(&(file.path.clone(), source)),
+            granularity,
+        }
+    }
+
+    /// Load the whole diff of a file side from now on
+    pub fn load_full(&mut self, path: &str, source: DiffSource) {
+        self.full.insert((path.to_string(), source));
+    }
+
+    /// Also show the new-side lines in `range` in diffs of the file side of
+    /// `key`, as long as that side does not change
+    pub fn expand(&mut self, key: &DiffKey, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+        let version = new_side_version(key.source, key.mtime, key.generation);
+        let expansion = self
+            .expansions
+            .entry((key.path.clone(), key.source))
+            .or_default();
+        if expansion.version != version {
+            // Line numbers of an older version point elsewhere now
+            expansion.version = version;
+            expansion.ranges.clear();
+        }
+        let ranges = &mut expansion.ranges;
+        ranges.push(range);
+        ranges.sort_by_key(|r| r.start);
+
+        // Merge overlapping and touching ranges
+        let mut merged: Vec<Range<u32>> = Vec::with_capacity(ranges.len());
+        for range in ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        *ranges = merged;
+    }
+
+    /// Latest diff of a file side, possibly stale
+    pub fn get(&self, path: &str, source: DiffSource) -> Option<&FileDiff> {
+        self.entries
+            .get(&(path.to_string(), source))
+            .map(|(_, diff, _)| diff)
+    }
+
+    /// Highlights of the latest diff of a file side
+    pub fn highlights(&self, path: &str, source: DiffSource) -> Option<&DiffHighlights> {
+        self.entries
+            .get(&(path.to_string(), source))
+            .map(|(_, _, highlights)| highlights)
+    }
+
+    /// Whether `key` should be sent to the worker; marks it pending if so
+    pub fn needs_request(&mut self, key: &DiffKey) -> bool {
+        let fresh = self
+            .entries
+            .get(&(key.path.clone(), key.source))
+            .is_some_and(|(cached, _, _)| cached == key);
+        !fresh && self.pending.insert(key.clone())
+    }
+
+    pub fn insert(&mut self, key: DiffKey, diff: FileDiff, highlights: DiffHighlights) {
+        self.pending.remove(&key);
+        let version = new_side_version(key.source, key.mtime, key.generation);
+        let side = (key.path.clone(), key.source);
+        if self
+            .expansions
+            .get(&side)
+            .is_some_and(|e| e.version != version)
+        {
+            self.expansions.remove(&side);
+        }
+        self.entries
+            .insert((key.path.clone(), key.source), (key, diff, highlights));
+    }
+
+    /// Mark every cached diff stale
+    pub fn invalidate(&mut self) {
+        self.generation += 1;
+        self.pending.clear();
+    }
+
+    /// Drop diffs of files that are no longer in the list
+    pub fn retain_paths(&mut self, paths: &HashSet<&str>) {
+        self.entries
+            .retain(|(path, _), _| paths.contains(path.as_str()));
+        self.expansions
+            .retain(|(path, _), _| paths.contains(path.as_str()));
+        self.full.retain(|(path, _)| paths.contains(path.as_str()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::FileStatus;
+
+    fn key(path: &str, generation: u64) -> DiffKey {
+        DiffKey {
+            path: path.to_string(),
+            old_path: None,
+            source: DiffSource::Unstaged,
+            mtime: None,
+            generation,
+            expanded: Vec::new(),
+            full: false,
+            granularity: Granularity::default(),
+        }
+    }
+
+    fn status(paths: &[&str], full: bool) -> GitRequest {
+        GitRequest::Status {
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            full,
+        }
+    }
+
+    #[test]
+    fn test_coalesce_merges_status_and_dedupes_diffs() {
+        let merged = coalesce(vec![
+            GitRequest::Diff(key("a.rs", 0)),
+            status(&["a.rs"], false),
+            GitRequest::Head,
+            status(&["b.rs", "a.rs"], true),
+            GitRequest::Diff(key("a.rs", 0)),
+            GitRequest::Head,
+            GitRequest::Settings(DiffSettings::default()),
+            GitRequest::StashPatch("s1".to_string()),
+            GitRequest::Stashes,
+            GitRequest::StashPatch("s1".to_string()),
+            GitRequest::Stashes,
+        ]);
+
+        assert_eq!(
+            merged,
+            vec![
+                GitRequest::Settings(DiffSettings::default()),
+                GitRequest::Head,
+                status(&["a.rs", "b.rs"], true),
+                GitRequest::Diff(key("a.rs", 0)),
+                GitRequest::Stashes,
+                GitRequest::StashPatch("s1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refresh_planner_routes_events() {
+        let mut planner = RefreshPlanner::new();
+        let paths = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect();
+        let not_renamed = |_: &str| false;
+
+        let event = Event::GitChange(paths(&["a.rs"]));
+        assert_eq!(
+            planner.requests(&event, not_renamed),
+            vec![status(&["a.rs"], false)]
+        );
+        // Touching either side of a rename rescans everything
+        assert_eq!(
+            planner.requests(&event, |path| path == "a.rs"),
+            vec![status(&["a.rs"], true)]
+        );
+        let many: Vec<String> = (0..=MAX_INCREMENTAL_PATHS).map(|i| i.to_string()).collect();
+        assert_eq!(
+            planner.requests(&Event::GitChange(many.clone()), not_renamed),
+            vec![GitRequest::Status {
+                paths: many,
+                full: true
+            }]
+        );
+
+        assert_eq!(
+            planner.requests(&Event::HeadChange, not_renamed),
+            vec![GitRequest::Head, status(&[], true)]
+        );
+        // A commit moves only the branch ref, but the staged files change
+        assert_eq!(
+            planner.requests(&Event::RefsChange, not_renamed),
+            vec![GitRequest::Head, status(&[], true)]
+        );
+        assert!(planner.requests(&Event::Tick, not_renamed).is_empty());
+
+        // The safety-net rescan is due again only after the interval
+        assert!(planner.rescan().is_none());
+        planner.last_full_scan -= FULL_RESCAN_INTERVAL;
+        assert_eq!(planner.rescan(), Some(status(&[], true)));
+        assert!(planner.rescan().is_none());
+    }
+
+    #[test]
+    fn test_diff_cache_freshness() {
+        let mut cache = DiffCache::new();
+        let diff = FileDiff {
+            path: "a.rs".to_string(),
+            source: DiffSource::Unstaged,
+            old_path: None,
+            similarity: None,
+            hunks: Vec::new(),
+            notice: None,
+        };
+
+        assert!(cache.needs_request(&key("a.rs", 0)));
+        // Already pending: not requested twice
+        assert!(!cache.needs_request(&key("a.rs", 0)));
+
+        cache.insert(key("a.rs", 0), diff, DiffHighlights::default());
+        assert!(!cache.needs_request(&key("a.rs", 0)));
+
+        // Invalidated entries stay readable but are requested again
+        cache.invalidate();
+        assert!(cache.get("a.rs", DiffSource::Unstaged).is_some());
+        assert!(cache.needs_request(&key("a.rs", 1)));
+    }
+
+    #[test]
+    fn test_diff_cache_expansions_merge_and_change_key() {
+        let mut cache = DiffCache::new();
+        let file = FileChange {
+            path: "a.rs".to_string(),
+            index_status: None,
+            worktree_status: Some(FileStatus::Modified),
+            staged_stats: Default::default(),
+            unstaged_stats: Default::default(),
+            modified_time: None,
+            index_old_path: None,
+            worktree_old_path: None,
+        };
+        let before = cache.key(&file, DiffSource::Unstaged, Granularity::Word);
+
+        cache.expand(&before, 20..30);
+        cache.expand(&before, 5..10);
+        cache.expand(&before, 10..12);
+        cache.expand(&before, 25..40);
+        let after = cache.key(&file, DiffSource::Unstaged, Granularity::Word);
+
+        assert_eq!(after.expanded, vec![5..12, 20..40]);
+        assert_ne!(before, after);
+        // The other side of the file is unaffected
+        assert!(cache
+            .key(&file, DiffSource::Staged, Granularity::Word)
+            .expanded
+            .is_empty());
+
+        // An edit to the file makes the expansions meaningless
+        let edited = FileChange {
+            modified_time: Some(SystemTime::UNIX_EPOCH),
+            ..file
+        };
+        let key = cache.key(&edited, DiffSource::Unstaged, Granularity::Word);
+        assert!(key.expanded.is_empty());
+        cache.expand(&key, 1..3);
+        assert_eq!(
+            cache
+                .key(&edited, DiffSource::Unstaged, Granularity::Word)
+                .expanded,
+            vec![1..3]
+        );
+    }
+}
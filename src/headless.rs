@@ -0,0 +1,410 @@
+use crate::config::Config;
+use crate::event::Event;
+use crate::git::{
+    DiffSource, FileChange, FileStatus, GitRepository, GitRequest, GitWatcher, GitWorker,
+    RefreshPlanner, StatusUpdate,
+};
+use color_eyre::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+/// Lines a `serve` client may fall behind by before it is disconnected
+const CLIENT_BACKLOG: usize = 1024;
+
+/// State of one side of a file, as printed on a line of the JSON stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusRecord {
+    pub path: String,
+    /// `null` once this side of the file has no changes anymore
+    pub status: Option<FileStatus>,
+    /// Whether this is the change between HEAD and the index, rather than
+    /// between the index and the working tree
+    pub staged: bool,
+    /// Source of a rename or copy
+    pub old_path: Option<String>,
+    pub additions: i32,
+    pub deletions: i32,
+    /// File modification time, seconds since the epoch
+    pub mtime: Option<u64>,
+    /// Checked out branch, `null` when HEAD is detached
+    pub branch: Option<String>,
+}
+
+/// Last printed record of every changed file side, turning status updates
+/// into the records that differ from it
+#[derive(Debug, Default)]
+pub struct StatusStream {
+    branch: Option<String>,
+    records: HashMap<(String, bool), StatusRecord>,
+}
+
+impl StatusStream {
+    pub fn new(branch: Option<String>) -> Self {
+        Self {
+            branch,
+            records: HashMap::new(),
+        }
+    }
+
+    /// Branch stamped on the following records
+    pub fn set_branch(&mut self, branch: Option<String>) {
+        self.branch = branch;
+    }
+
+    /// Whether `path` is either side of a known rename or copy, which a
+    /// per-path status update would split up
+    pub fn is_rename_path(&self, path: &str) -> bool {
+        self.records.values().any(|record| {
+            record.old_path.is_some()
+                && (record.path == path || record.old_path.as_deref() == Some(path))
+        })
+    }
+
+    /// Every record, sorted by path with the staged side first
+    pub fn snapshot(&self) -> Vec<StatusRecord> {
+        let mut records: Vec<StatusRecord> = self.records.values().cloned().collect();
+        records.sort_by(|a, b| (&a.path, !a.staged).cmp(&(&b.path, !b.staged)));
+        records
+    }
+
+    /// Apply a status update, returning the records to print: new and
+    /// changed file sides, then those that went clean
+    pub fn update(&mut self, update: StatusUpdate) -> Vec<StatusRecord> {
+        let (changes, cleared): (Vec<FileChange>, Vec<(String, bool)>) = match update {
+            StatusUpdate::Full { files, .. } => {
+                let cleared = self.records.keys().cloned().collect();
+                (files, cleared)
+            }
+            StatusUpdate::Paths(paths) => {
+                let cleared = self
+                    .records
+                    .keys()
+                    .filter(|(path, _)| paths.iter().any(|(p, _)| p == path))
+                    .cloned()
+                    .collect();
+                (
+                    paths.into_iter().filter_map(|(_, change)| change).collect(),
+                    cleared,
+                )
+            }
+        };
+
+        let mut previous: HashMap<(String, bool), StatusRecord> = cleared
+            .into_iter()
+            .filter_map(|key| self.records.remove_entry(&key))
+            .collect();
+
+        let mut output = Vec::new();
+        for change in &changes {
+            for record in self.records_of(change) {
+                let key = (record.path.clone(), record.staged);
+                if previous.remove(&key).as_ref() != Some(&record) {
+                    output.push(record.clone());
+                }
+                self.records.insert(key, record);
+            }
+        }
+
+        let mut gone: Vec<StatusRecord> = previous
+            .into_values()
+            .map(|record| StatusRecord {
+                status: None,
+                old_path: None,
+                additions: 0,
+                deletions: 0,
+                mtime: None,
+                branch: self.branch.clone(),
+                ..record
+            })
+            .collect();
+        gone.sort_by(|a, b| (&a.path, a.staged).cmp(&(&b.path, b.staged)));
+        output.extend(gone);
+        output
+    }
+
+    /// Records of the staged and unstaged side of a file, for the sides
+    /// that have changes
+    fn records_of(&self, change: &FileChange) -> Vec<StatusRecord> {
+        let mtime = change
+            .modified_time
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+        [DiffSource::Staged, DiffSource::Unstaged]
+            .into_iter()
+            .filter_map(|source| {
+                let status = change.status(source)?;
+                let stats = change.stats(source);
+                Some(StatusRecord {
+                    path: change.path.clone(),
+                    status: Some(status),
+                    staged: source == DiffSource::Staged,
+                    old_path: change.old_path(source).map(str::to_string),
+                    additions: stats.additions,
+                    deletions: stats.deletions,
+                    mtime,
+                    branch: self.branch.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The watcher and status worker of the TUI, feeding a `StatusStream`
+/// instead of the file list
+struct StatusEngine {
+    worker: GitWorker,
+    planner: RefreshPlanner,
+    stream: StatusStream,
+    receiver: mpsc::UnboundedReceiver<Event>,
+    _watcher: GitWatcher,
+}
+
+impl StatusEngine {
+    /// Open the repository, start watching it and queue the initial scan
+    fn start(repo_path: PathBuf, config: &Config) -> Result<Self> {
+        let diff_settings = config.diff_settings();
+        let mut repo = GitRepository::new(repo_path)?;
+        repo.set_diff_settings(diff_settings);
+        let stream = StatusStream::new(repo.head_info()?.branch);
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let worker = GitWorker::new(repo.path().to_path_buf(), sender.clone())?;
+        worker.send(GitRequest::Settings(diff_settings))?;
+        worker.send(GitRequest::Status {
+            paths: Vec::new(),
+            full: true,
+        })?;
+        let watcher = GitWatcher::new(
+            repo.path().to_path_buf(),
+            sender,
+            &config.exclude,
+            config.debounce,
+        )?;
+
+        Ok(Self {
+            worker,
+            planner: RefreshPlanner::new(),
+            stream,
+            receiver,
+            _watcher: watcher,
+        })
+    }
+
+    /// Wait for the next status update and return the records it changed,
+    /// or `None` once the process is interrupted
+    async fn next(&mut self) -> Result<Option<Vec<StatusRecord>>> {
+        loop {
+            let event = tokio::select! {
+                event = self.receiver.recv() => event,
+                _ = tokio::time::sleep(self.planner.until_rescan()) => {
+                    if let Some(request) = self.planner.rescan() {
+                        self.worker.send(request)?;
+                    }
+                    continue;
+                }
+                _ = tokio::signal::ctrl_c() => None,
+            };
+            let Some(event) = event else {
+                return Ok(None);
+            };
+
+            match event {
+                // The worker reads HEAD before the status requested along
+                // with it, so records are stamped with the new branch
+                Event::HeadLoaded(head) => self.stream.set_branch(head.branch),
+                Event::StatusLoaded(update) => return Ok(Some(self.stream.update(update))),
+                Event::WatchError(message) => eprintln!("File watcher error: {}", message),
+                Event::GitError(action, message) => {
+                    eprintln!("Failed to {}: {}", action, message)
+                }
+                event => {
+                    let stream = &self.stream;
+                    let requests = self
+                        .planner
+                        .requests(&event, |path| stream.is_rename_path(path));
+                    for request in requests {
+                        self.worker.send(request)?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A record as one line of the stream, newline included
+fn json_line(record: &StatusRecord) -> Result<String> {
+    Ok(format!("{}\n", serde_json::to_string(record)?))
+}
+
+/// Watch the repository without a terminal, printing one JSON object per
+/// line on stdout for every change of a file's status, until stdout closes
+/// or the process is interrupted
+pub async fn watch(repo_path: PathBuf, config: &Config) -> Result<()> {
+    let mut engine = StatusEngine::start(repo_path, config)?;
+    let mut stdout = std::io::stdout();
+    while let Some(records) = engine.next().await? {
+        for record in records {
+            let written = stdout
+                .write_all(json_line(&record)?.as_bytes())
+                .and_then(|_| stdout.flush());
+            match written {
+                Ok(()) => {}
+                // The consumer went away
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Like `watch`, but for every client connecting to `addr`. Each client
+/// first gets the current state of all changed files, then the changes.
+pub async fn serve(repo_path: PathBuf, config: &Config, addr: SocketAddr) -> Result<()> {
+    let mut engine = StatusEngine::start(repo_path, config)?;
+    let listener = TcpListener::bind(addr).await?;
+    eprintln!("Streaming status changes on {}", listener.local_addr()?);
+
+    let mut clients: Vec<mpsc::Sender<String>> = Vec::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let socket = match accepted {
+                    Ok((socket, _)) => socket,
+                    Err(e) => {
+                        eprintln!("Failed to accept a client: {}", e);
+                        continue;
+                    }
+                };
+                let snapshot = engine
+                    .stream
+                    .snapshot()
+                    .iter()
+                    .map(json_line)
+                    .collect::<Result<Vec<_>>>()?;
+                let (sender, lines) = mpsc::channel(CLIENT_BACKLOG);
+                tokio::spawn(send_lines(socket, snapshot, lines));
+                clients.push(sender);
+            }
+            records = engine.next() => {
+                let Some(records) = records? else {
+                    return Ok(());
+                };
+                let lines = records.iter().map(json_line).collect::<Result<Vec<_>>>()?;
+                // Clients whose connection closed have dropped their receiver;
+                // those too slow to keep up are dropped rather than buffered
+                // without bound, which ends their connection
+                clients.retain(|client| {
+                    lines.iter().all(|line| match client.try_send(line.clone()) {
+                        Ok(()) => true,
+                        Err(TrySendError::Full(_)) => {
+                            eprintln!("Disconnecting a client that fell behind");
+                            false
+                        }
+                        Err(TrySendError::Closed(_)) => false,
+                    })
+                });
+            }
+        }
+    }
+}
+
+/// Write the snapshot, then lines, to one client until either side goes away
+async fn send_lines(
+    mut socket: TcpStream,
+    snapshot: Vec<String>,
+    mut lines: mpsc::Receiver<String>,
+) {
+    for line in snapshot {
+        if socket.write_all(line.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+    while let Some(line) = lines.recv().await {
+        if socket.write_all(line.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(path: &str, index: Option<FileStatus>, worktree: Option<FileStatus>) -> FileChange {
+        let mut change = FileChange {
+            path: path.to_string(),
+            index_status: index,
+            worktree_status: worktree,
+            staged_stats: Default::default(),
+            unstaged_stats: Default::default(),
+            modified_time: Some(UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)),
+            index_old_path: None,
+            worktree_old_path: None,
+        };
+        change.staged_stats.additions = 1;
+        change.unstaged_stats.additions = 2;
+        change.unstaged_stats.deletions = 3;
+        change
+    }
+
+    fn full(files: Vec<FileChange>) -> StatusUpdate {
+        StatusUpdate::Full {
+            files,
+            changed_paths: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_record_json_shape() {
+        let mut stream = StatusStream::new(Some("main".to_string()));
+        let records = stream.update(full(vec![change(
+            "src/lib.rs",
+            None,
+            Some(FileStatus::Modified),
+        )]));
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            serde_json::to_string(&records[0]).unwrap(),
+            r#"{"path":"src/lib.rs","status":"modified","staged":false,"old_path":null,"additions":2,"deletions":3,"mtime":1700000000,"branch":"main"}"#
+        );
+    }
+
+    #[test]
+    fn test_only_changes_are_reported() {
+        let mut stream = StatusStream::new(Some("main".to_string()));
+        let both = change(
+            "a.rs",
+            Some(FileStatus::Modified),
+            Some(FileStatus::Modified),
+        );
+        assert_eq!(stream.update(full(vec![both.clone()])).len(), 2);
+
+        // Same state again: nothing to print
+        assert!(stream.update(full(vec![both])).is_empty());
+
+        // The unstaged side went away
+        let staged = change("a.rs", Some(FileStatus::Modified), None);
+        let records = stream.update(StatusUpdate::Paths(vec![(
+            "a.rs".to_string(),
+            Some(staged),
+        )]));
+        assert_eq!(records.len(), 1);
+        assert!(!records[0].staged);
+        assert_eq!(records[0].status, None);
+
+        // And the file is clean
+        let records = stream.update(full(Vec::new()));
+        assert_eq!(records.len(), 1);
+        assert!(records[0].staged);
+        assert_eq!(records[0].status, None);
+    }
+}
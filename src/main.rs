@@ -1,58 +1,115 @@
 mod app;
 mod banner;
+mod cli;
+mod composer;
+mod config;
 mod event;
 mod git;
+mod headless;
+mod notification;
+mod snapshot;
 mod terminal;
 mod ui;
+mod undo;
 
 use app::App;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use cli::{Cli, Command, RepoArgs};
 use color_eyre::Result;
+use config::{Config, ConfigFile, ConfigSources, ConfigWatcher};
 use event::{Event, EventHandler};
-use std::path::PathBuf;
+use git::GitRepository;
+use std::io::Write;
 use terminal::Terminal;
+use ui::theme::Background;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
 
-    // Parse command line arguments
-    let args: Vec<String> = std::env::args().collect();
-
-    // Handle --help and --version
-    if args.iter().any(|a| a == "--help" || a == "-h") {
-        banner::print_banner();
-        banner::print_version();
-        print_help();
-        return Ok(());
-    }
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            if e.kind() == ErrorKind::DisplayHelp {
+                banner::print_banner();
+            }
+            e.exit();
+        }
+    };
 
-    if args.iter().any(|a| a == "--version" || a == "-V") {
-        banner::print_banner();
-        banner::print_version();
-        return Ok(());
-    }
+    let args = match cli.into_command() {
+        Command::Tui(args) if args.json => {
+            let (repo, _, config) = load_config(&args.repo, args.overrides())?;
+            return headless::watch(repo.path().to_path_buf(), &config).await;
+        }
+        Command::Tui(args) => args,
+        Command::Status(args) => {
+            let (repo, _, _) = load_config(&args.repo, args.repo.overrides())?;
+            return snapshot::print_status(&repo, args.format.into());
+        }
+        Command::Check(args) => {
+            let (repo, _, _) = load_config(&args.repo, args.repo.overrides())?;
+            let code = snapshot::check(&repo, &args.conditions())?;
+            std::process::exit(code);
+        }
+        Command::Watch(args) => {
+            let (repo, _, config) = load_config(&args.repo, args.overrides())?;
+            return headless::watch(repo.path().to_path_buf(), &config).await;
+        }
+        Command::Serve(args) => {
+            let (repo, _, config) = load_config(&args.repo, args.overrides())?;
+            return headless::serve(repo.path().to_path_buf(), &config, args.listen).await;
+        }
+        Command::Completions { shell } => {
+            // clap_complete panics on write errors, so go through a buffer
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut Cli::command(), "git-monitor", &mut script);
+            return match std::io::stdout().write_all(&script) {
+                Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+    };
 
-    // Get repository path from args or use current directory
-    let repo_path = args
-        .get(1)
-        .filter(|a| !a.starts_with('-'))
-        .map(PathBuf::from)
-        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    // Load the configuration before touching the terminal, so errors print plainly
+    let (repo, sources, mut config) = load_config(&args.repo, args.overrides())?;
 
     // Initialize terminal
     let mut terminal = Terminal::new()?;
 
-    // Initialize application
-    let mut app = App::new(repo_path)?;
+    // Query the background while no one else reads the terminal input
+    let background = terminal
+        .background()
+        .or_else(Background::from_env)
+        .unwrap_or_default();
 
     // Create event handler
-    let mut events = EventHandler::new(200);
+    let mut events = EventHandler::new(config.tick_rate);
+
+    // Initialize application
+    let mut app = App::new(repo.path().to_path_buf(), events.sender(), config.diff_settings())?;
+    app.background = background;
+    app.apply_config(&config)?;
 
     // Start file watcher
-    app.start_watcher(events.sender())?;
+    app.start_watcher(events.sender(), &config.exclude, config.debounce)?;
+
+    // Reload the configuration when one of its files changes
+    let _config_watcher = match ConfigWatcher::new(sources.paths(), events.sender()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            app.notifications
+                .warning(format!("Config files are not watched: {}", e));
+            None
+        }
+    };
 
     // Main loop
     while app.is_running() {
+        // Make sure the selected diff is loaded or on its way
+        app.request_diff();
+
         // Render UI
         terminal.draw(|frame| {
             ui::render(frame, &mut app);
@@ -67,7 +124,24 @@ async fn main() -> Result<()> {
                 let diff_area = app.diff_view_area;
                 event::handle_mouse_event(&mut app, mouse_event, file_area, diff_area)
             }
-            Event::GitChange(changed_paths) => app.refresh_status_with_paths(changed_paths)?,
+            event @ (Event::GitChange(_)
+            | Event::IndexChange
+            | Event::HeadChange
+            | Event::RefsChange
+            | Event::OperationChange) => app.on_repo_change(&event),
+            Event::StashChange => app.on_stash_change(),
+            Event::WatchError(message) => app
+                .notifications
+                .warning(format!("File watcher error: {}", message)),
+            Event::StatusLoaded(update) => app.apply_status(update),
+            Event::HeadLoaded(head) => app.apply_head(head),
+            Event::DiffLoaded(key, diff, highlights) => app.apply_diff(key, diff, *highlights),
+            Event::StashesLoaded(stashes) => app.apply_stashes(stashes),
+            Event::StashPatchLoaded(id, lines) => app.apply_stash_patch(id, lines),
+            Event::GitError(action, message) => app
+                .notifications
+                .error(format!("Failed to {}: {}", action, message)),
+            Event::ConfigChange => reload_config(&mut app, &events, &sources, &mut config),
             Event::Resize(_, _) => {}
         }
     }
@@ -78,30 +152,46 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-fn print_help() {
-    const GREEN: &str = "\x1b[38;2;166;227;161m";
-    const YELLOW: &str = "\x1b[38;2;249;226;175m";
-    const TEXT: &str = "\x1b[38;2;205;214;244m";
-    const DIM: &str = "\x1b[38;2;108;112;134m";
-    const RESET: &str = "\x1b[0m";
-    const BOLD: &str = "\x1b[1m";
-
-    eprintln!("{BOLD}USAGE:{RESET}");
-    eprintln!("    {GREEN}git-monitor{RESET} {DIM}[PATH]{RESET}");
-    eprintln!();
-    eprintln!("{BOLD}ARGS:{RESET}");
-    eprintln!("    {YELLOW}<PATH>{RESET}    Path to Git repository {DIM}(default: current directory){RESET}");
-    eprintln!();
-    eprintln!("{BOLD}OPTIONS:{RESET}");
-    eprintln!("    {GREEN}-h{RESET}, {GREEN}--help{RESET}       Print help information");
-    eprintln!("    {GREEN}-V{RESET}, {GREEN}--version{RESET}    Print version information");
-    eprintln!();
-    eprintln!("{BOLD}KEYBINDINGS:{RESET}");
-    eprintln!("    {YELLOW}j/k{RESET} or {YELLOW}↑/↓{RESET}      Navigate files");
-    eprintln!("    {YELLOW}Tab{RESET}             Switch panels");
-    eprintln!("    {YELLOW}Enter{RESET}           Stage/Unstage file");
-    eprintln!("    {YELLOW}r{RESET}               Refresh status");
-    eprintln!("    {YELLOW}q{RESET} or {YELLOW}Esc{RESET}        Quit");
-    eprintln!();
-    eprintln!("{DIM}For more information, visit: https://github.com/vbarter/git-monitor{RESET}");
+/// Open the repository and load its configuration, with the command line
+/// values in `overrides` on top
+fn load_config(
+    args: &RepoArgs,
+    overrides: ConfigFile,
+) -> Result<(GitRepository, ConfigSources, Config)> {
+    let mut repo = GitRepository::new(args.path())?;
+    let sources = ConfigSources::new(repo.path(), args.config.clone(), overrides);
+    let config = sources.load()?;
+    repo.set_diff_settings(config.diff_settings());
+    Ok((repo, sources, config))
+}
+
+/// Apply the changed configuration, keeping the current one when the new
+/// one is invalid
+fn reload_config(
+    app: &mut App,
+    events: &EventHandler,
+    sources: &ConfigSources,
+    config: &mut Config,
+) {
+    let new = match sources.load() {
+        Ok(new) => new,
+        Err(e) => {
+            app.notifications
+                .error(format!("Invalid configuration: {}", e));
+            return;
+        }
+    };
+    if new == *config {
+        return;
+    }
+
+    events.set_tick_rate(new.tick_rate);
+    let result = app.apply_config(&new);
+    app.report_error("apply the configuration", result);
+    if new.debounce != config.debounce || new.exclude != config.exclude {
+        let result = app.start_watcher(events.sender(), &new.exclude, new.debounce);
+        app.report_error("restart the file watcher", result);
+    }
+    app.notifications.info("Configuration reloaded");
+    *config = new;
 }
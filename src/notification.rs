@@ -0,0 +1,156 @@
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Maximum number of notifications kept in the message log
+const LOG_CAPACITY: usize = 200;
+
+/// Maximum number of toasts shown at once
+const MAX_TOASTS: usize = 3;
+
+/// Notification severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// How long a toast of this severity stays on screen
+    pub fn timeout(&self) -> Duration {
+        match self {
+            Severity::Info | Severity::Success => Duration::from_secs(3),
+            Severity::Warning => Duration::from_secs(5),
+            Severity::Error => Duration::from_secs(8),
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Severity::Info => "ℹ",
+            Severity::Success => "✓",
+            Severity::Warning => "⚠",
+            Severity::Error => "✗",
+        }
+    }
+}
+
+/// A single message shown as a toast and kept in the log
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub severity: Severity,
+    pub message: String,
+    /// Wall-clock time, shown in the message log
+    pub timestamp: DateTime<Local>,
+    pub created: Instant,
+    pub timeout: Duration,
+}
+
+impl Notification {
+    pub fn is_expired(&self) -> bool {
+        self.created.elapsed() >= self.timeout
+    }
+}
+
+/// Toast queue and message log
+#[derive(Debug, Default)]
+pub struct Notifications {
+    log: VecDeque<Notification>,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, severity: Severity, message: impl Into<String>) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(Notification {
+            severity,
+            message: message.into(),
+            timestamp: Local::now(),
+            created: Instant::now(),
+            timeout: severity.timeout(),
+        });
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Severity::Info, message);
+    }
+
+    pub fn success(&mut self, message: impl Into<String>) {
+        self.push(Severity::Success, message);
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.push(Severity::Warning, message);
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(Severity::Error, message);
+    }
+
+    /// Toasts that have not timed out yet, oldest first
+    pub fn active(&self) -> Vec<&Notification> {
+        let mut active: Vec<&Notification> = self
+            .log
+            .iter()
+            .rev()
+            .filter(|n| !n.is_expired())
+            .take(MAX_TOASTS)
+            .collect();
+        active.reverse();
+        active
+    }
+
+    /// Full message log, oldest first
+    pub fn log(&self) -> &VecDeque<Notification> {
+        &self.log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_active_limits_to_newest_toasts() {
+        let mut notifications = Notifications::new();
+        for i in 0..5 {
+            notifications.info(format!("message {}", i));
+        }
+
+        let active: Vec<&str> = notifications
+            .active()
+            .iter()
+            .map(|n| n.message.as_str())
+            .collect();
+        assert_eq!(active, vec!["message 2", "message 3", "message 4"]);
+        assert_eq!(notifications.log().len(), 5);
+    }
+
+    #[test]
+    fn test_expired_toasts_stay_in_log() {
+        let mut notifications = Notifications::new();
+        notifications.error("boom");
+        notifications.log.front_mut().unwrap().timeout = Duration::ZERO;
+
+        assert!(notifications.active().is_empty());
+        assert_eq!(notifications.log().len(), 1);
+    }
+
+    #[test]
+    fn test_log_is_capped() {
+        let mut notifications = Notifications::new();
+        for i in 0..LOG_CAPACITY + 10 {
+            notifications.warning(format!("{}", i));
+        }
+
+        assert_eq!(notifications.log().len(), LOG_CAPACITY);
+        assert_eq!(notifications.log().front().unwrap().message, "10");
+    }
+}
@@ -0,0 +1,288 @@
+use crate::git::{DiffSource, FileChange, FileStatus, GitRepository, StatusUpdate};
+use crate::headless::StatusStream;
+use color_eyre::Result;
+
+/// Output format of the `status` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusFormat {
+    /// Grouped by staged, unstaged and untracked, with line counts
+    #[default]
+    Human,
+    /// One JSON object per file side, as printed by `watch`
+    Json,
+    /// The short format of `git status --porcelain`
+    Porcelain,
+}
+
+/// A condition `check` fails on. Each one has its own bit in the exit code,
+/// so several failing conditions add up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Staged or unstaged changes to tracked files
+    Dirty,
+    /// Files git does not track yet
+    Untracked,
+    /// Unresolved merge conflicts
+    Conflicts,
+}
+
+impl Condition {
+    pub const ALL: [Condition; 3] = [Condition::Dirty, Condition::Untracked, Condition::Conflicts];
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Condition::Dirty => 2,
+            Condition::Untracked => 4,
+            Condition::Conflicts => 8,
+        }
+    }
+
+    /// Whether `file` meets this condition
+    fn matches(&self, file: &FileChange) -> bool {
+        match self {
+            Condition::Dirty => {
+                file.index_status.is_some()
+                    || file
+                        .worktree_status
+                        .is_some_and(|status| status != FileStatus::Untracked)
+            }
+            Condition::Untracked => file.worktree_status == Some(FileStatus::Untracked),
+            Condition::Conflicts => file.worktree_status == Some(FileStatus::Conflicted),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Condition::Dirty => "worktree dirty",
+            Condition::Untracked => "untracked files present",
+            Condition::Conflicts => "conflicts present",
+        }
+    }
+}
+
+/// Current status of the repository, sorted by path
+fn read_status(repo: &GitRepository) -> Result<Vec<FileChange>> {
+    let mut files = repo.get_status()?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Print the status of the repository once
+pub fn print_status(repo: &GitRepository, format: StatusFormat) -> Result<()> {
+    let files = read_status(repo)?;
+    let branch = repo.head_info()?.branch;
+    let output = match format {
+        StatusFormat::Human => format_human(&files, branch.as_deref()),
+        StatusFormat::Json => {
+            let mut output = String::new();
+            let records = StatusStream::new(branch).update(StatusUpdate::Full {
+                files,
+                changed_paths: Vec::new(),
+            });
+            for record in records {
+                output.push_str(&serde_json::to_string(&record)?);
+                output.push('\n');
+            }
+            output
+        }
+        StatusFormat::Porcelain => format_porcelain(&files),
+    };
+    print!("{}", output);
+    Ok(())
+}
+
+/// Report every condition of `conditions` the repository meets on stderr,
+/// returning the exit code: 0 when none does, otherwise the sum of their
+/// codes
+pub fn check(repo: &GitRepository, conditions: &[Condition]) -> Result<i32> {
+    let files = read_status(repo)?;
+    let mut code = 0;
+    for condition in conditions {
+        let matching: Vec<&str> = files
+            .iter()
+            .filter(|file| condition.matches(file))
+            .map(|file| file.path.as_str())
+            .collect();
+        if matching.is_empty() || code & condition.exit_code() != 0 {
+            continue;
+        }
+        code |= condition.exit_code();
+        eprintln!("{} ({})", condition.description(), matching.len());
+        for path in matching {
+            eprintln!("    {}", path);
+        }
+    }
+    Ok(code)
+}
+
+fn format_human(files: &[FileChange], branch: Option<&str>) -> String {
+    let mut output = match branch {
+        Some(branch) => format!("On branch {}\n", branch),
+        None => "HEAD detached\n".to_string(),
+    };
+    if files.is_empty() {
+        output.push_str("Nothing to commit, working tree clean\n");
+        return output;
+    }
+
+    let untracked = |file: &&FileChange| file.worktree_status == Some(FileStatus::Untracked);
+    let sections = [
+        ("Staged", DiffSource::Staged),
+        ("Unstaged", DiffSource::Unstaged),
+    ];
+    for (title, source) in sections {
+        let lines: Vec<String> = files
+            .iter()
+            .filter(|file| !untracked(file))
+            .filter_map(|file| {
+                let status = file.status(source)?;
+                let stats = file.stats(source);
+                let path = match file.old_path(source) {
+                    Some(old_path) => format!("{} → {}", old_path, file.path),
+                    None => file.path.clone(),
+                };
+                Some(format!(
+                    "    {} {}  +{} -{}\n",
+                    status.symbol(),
+                    path,
+                    stats.additions,
+                    stats.deletions
+                ))
+            })
+            .collect();
+        if !lines.is_empty() {
+            output.push_str(&format!("{}:\n", title));
+            output.extend(lines);
+        }
+    }
+
+    let untracked: Vec<&FileChange> = files.iter().filter(untracked).collect();
+    if !untracked.is_empty() {
+        output.push_str("Untracked:\n");
+        for file in untracked {
+            output.push_str(&format!("    ? {}\n", file.path));
+        }
+    }
+    output
+}
+
+/// `XY path` lines like `git status --porcelain`, with `ORIG -> PATH` for
+/// renames and copies
+fn format_porcelain(files: &[FileChange]) -> String {
+    let mut output = String::new();
+    for file in files {
+        let (x, y) = match (file.index_status, file.worktree_status) {
+            (_, Some(FileStatus::Untracked)) => ('?', '?'),
+            (_, Some(FileStatus::Conflicted)) => ('U', 'U'),
+            (index, worktree) => (porcelain_code(index), porcelain_code(worktree)),
+        };
+        let old_path = file
+            .index_old_path
+            .as_deref()
+            .or(file.worktree_old_path.as_deref());
+        match old_path {
+            Some(old_path) => output.push_str(&format!(
+                "{}{} {} -> {}\n",
+                x,
+                y,
+                quote_path(old_path),
+                quote_path(&file.path)
+            )),
+            None => output.push_str(&format!("{}{} {}\n", x, y, quote_path(&file.path))),
+        }
+    }
+    output
+}
+
+fn porcelain_code(status: Option<FileStatus>) -> char {
+    match status {
+        None => ' ',
+        Some(FileStatus::Modified) => 'M',
+        Some(FileStatus::Added) => 'A',
+        Some(FileStatus::Deleted) => 'D',
+        Some(FileStatus::Renamed) => 'R',
+        Some(FileStatus::Copied) => 'C',
+        Some(FileStatus::Untracked) => '?',
+        Some(FileStatus::Conflicted) => 'U',
+    }
+}
+
+/// Quote a path the way git does in porcelain output: as a C string when it
+/// holds whitespace, quotes, backslashes, control or non-ASCII characters
+fn quote_path(path: &str) -> String {
+    let plain = |b: &u8| b.is_ascii_graphic() && *b != b'"' && *b != b'\\';
+    if path.bytes().all(|b| plain(&b)) {
+        return path.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for b in path.bytes() {
+        match b {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            b' ' => quoted.push(' '),
+            b if plain(&b) => quoted.push(b as char),
+            b => quoted.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(path: &str, index: Option<FileStatus>, worktree: Option<FileStatus>) -> FileChange {
+        FileChange {
+            path: path.to_string(),
+            index_status: index,
+            worktree_status: worktree,
+            staged_stats: Default::default(),
+            unstaged_stats: Default::default(),
+            modified_time: None,
+            index_old_path: None,
+            worktree_old_path: None,
+        }
+    }
+
+    #[test]
+    fn test_porcelain_format() {
+        let mut renamed = change(
+            "new.rs",
+            Some(FileStatus::Renamed),
+            Some(FileStatus::Modified),
+        );
+        renamed.index_old_path = Some("old.rs".to_string());
+        let files = vec![
+            change("a.rs", Some(FileStatus::Added), None),
+            change("b c.txt", None, Some(FileStatus::Untracked)),
+            change("conflict.rs", None, Some(FileStatus::Conflicted)),
+            renamed,
+            change("é.md", None, Some(FileStatus::Deleted)),
+        ];
+        assert_eq!(
+            format_porcelain(&files),
+            "A  a.rs\n\
+             ?? \"b c.txt\"\n\
+             UU conflict.rs\n\
+             RM old.rs -> new.rs\n \
+             D \"\\303\\251.md\"\n"
+        );
+    }
+
+    #[test]
+    fn test_conditions() {
+        let untracked = change("new.txt", None, Some(FileStatus::Untracked));
+        let staged = change("a.rs", Some(FileStatus::Modified), None);
+        let conflicted = change("c.rs", None, Some(FileStatus::Conflicted));
+
+        assert!(!Condition::Dirty.matches(&untracked));
+        assert!(Condition::Untracked.matches(&untracked));
+        assert!(Condition::Dirty.matches(&staged));
+        assert!(Condition::Dirty.matches(&conflicted));
+        assert!(Condition::Conflicts.matches(&conflicted));
+        assert!(!Condition::Conflicts.matches(&staged));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ui::theme::Background;
 use color_eyre::Result;
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
@@ -5,7 +6,8 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::prelude::*;
-use std::io::{stdout, Stdout};
+use std::io::{stdout, Stdout, Write};
+use std::time::Duration;
 
 pub type CrosstermTerminal = ratatui::Terminal<CrosstermBackend<Stdout>>;
 
@@ -29,6 +31,19 @@ impl Terminal {
         Ok(terminal)
     }
 
+    /// Ask the terminal for its background color (OSC 11), followed by a
+    /// device attributes request every terminal answers, so terminals that
+    /// ignore the first one are not waited for. Must run in raw mode, before
+    /// the event stream starts reading input.
+    pub fn background(&mut self) -> Option<Background> {
+        let backend = self.terminal.backend_mut();
+        backend.write_all(b"\x1b]11;?\x1b\\\x1b[c").ok()?;
+        Write::flush(backend).ok()?;
+        let reply = read_reply(Duration::from_millis(300))?;
+        let (r, g, b) = parse_background_reply(&reply)?;
+        Some(Background::from_rgb(r, g, b))
+    }
+
     pub fn draw<F>(&mut self, f: F) -> Result<()>
     where
         F: FnOnce(&mut Frame),
@@ -49,6 +64,70 @@ impl Terminal {
     }
 }
 
+/// Bytes the terminal sends back until the device attributes reply
+/// (`ESC [ ? ... c`) is complete or `timeout` runs out
+#[cfg(unix)]
+fn read_reply(timeout: Duration) -> Option<Vec<u8>> {
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+    use std::time::Instant;
+
+    let mut tty = std::fs::File::open("/dev/tty").ok()?;
+    let deadline = Instant::now() + timeout;
+    let mut reply = Vec::new();
+    while !is_attributes_reply_complete(&reply) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut fd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fd` is a valid pollfd for the open tty, polled alone
+        let ready = unsafe { libc::poll(&mut fd, 1, remaining.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+        let mut buffer = [0; 64];
+        match tty.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => reply.extend_from_slice(&buffer[..n]),
+        }
+    }
+    Some(reply)
+}
+
+#[cfg(not(unix))]
+fn read_reply(_timeout: Duration) -> Option<Vec<u8>> {
+    None
+}
+
+fn is_attributes_reply_complete(reply: &[u8]) -> bool {
+    reply
+        .windows(3)
+        .position(|w| w == b"\x1b[?")
+        .is_some_and(|start| reply[start..].contains(&b'c'))
+}
+
+/// The color of an `ESC ] 11 ; rgb:RRRR/GGGG/BBBB` reply, with 1 to 4 hex
+/// digits per channel, scaled to 8 bits
+fn parse_background_reply(reply: &[u8]) -> Option<(u8, u8, u8)> {
+    let reply = String::from_utf8_lossy(reply);
+    let start = reply.find("]11;rgb:")? + "]11;rgb:".len();
+    let end = reply[start..]
+        .find(['\x07', '\x1b'])
+        .map_or(reply.len(), |end| start + end);
+    let mut channels = reply[start..end].split('/').map(|hex| {
+        if hex.is_empty() || hex.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1 << (4 * hex.len())) - 1;
+        Some((value * 255 / max) as u8)
+    });
+    let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+    Some((r, g, b))
+}
+
 impl Drop for Terminal {
     fn drop(&mut self) {
         if let Err(e) = self.restore() {
@@ -56,3 +135,26 @@ impl Drop for Terminal {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_background_reply() {
+        let reply = b"\x1b]11;rgb:1e1e/1e1e/2e2e\x1b\\\x1b[?62;22c";
+        assert!(is_attributes_reply_complete(reply));
+        assert_eq!(parse_background_reply(reply), Some((30, 30, 46)));
+        assert_eq!(
+            parse_background_reply(b"\x1b]11;rgb:f/ff/fff\x07"),
+            Some((255, 255, 255))
+        );
+
+        // Terminals that only answer the device attributes request
+        assert_eq!(parse_background_reply(b"\x1b[?1;2c"), None);
+        assert_eq!(parse_background_reply(b"\x1b]11;rgb:zz/00/00\x07"), None);
+        assert!(!is_attributes_reply_complete(
+            b"\x1b]11;rgb:1e1e/1e1e/2e2e\x07"
+        ));
+    }
+}
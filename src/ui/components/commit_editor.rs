@@ -0,0 +1,100 @@
+use super::centered_rect;
+use crate::app::App;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+
+pub fn render_commit_editor(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let Some(composer) = &app.composer else {
+        return;
+    };
+
+    let popup = centered_rect(area, 72, 18);
+    frame.render_widget(Clear, popup);
+
+    let title = if composer.amend {
+        " Amend HEAD "
+    } else {
+        " Commit "
+    };
+
+    let block = Block::default()
+        .title(title)
+        .title_style(Style::default().fg(theme.text).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent))
+        .style(Style::default().bg(theme.background));
+
+    let inner = block.inner(popup);
+    frame.render_widget(block, popup);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2), // Staged summary
+            Constraint::Min(3),    // Message editor
+            Constraint::Length(1), // Error or key hints
+        ])
+        .split(inner);
+
+    // Staged file summary
+    let staged = app.staged_files();
+    let summary = if staged.is_empty() {
+        Line::from(Span::styled(
+            "No staged changes",
+            Style::default().fg(theme.modified),
+        ))
+    } else {
+        Line::from(vec![
+            Span::styled(
+                format!("{} staged: ", app.staged_count()),
+                Style::default().fg(theme.staged).bold(),
+            ),
+            Span::styled(staged.join(", "), Style::default().fg(theme.subtext)),
+        ])
+    };
+    frame.render_widget(Paragraph::new(summary).wrap(Wrap { trim: true }), chunks[0]);
+
+    // Message editor, scrolled to keep the cursor row visible
+    let editor_block = Block::default()
+        .borders(Borders::TOP)
+        .border_style(Style::default().fg(theme.border));
+    let editor_area = editor_block.inner(chunks[1]);
+    let visible_rows = editor_area.height.max(1) as usize;
+    let scroll = composer.row.saturating_sub(visible_rows - 1);
+
+    let lines: Vec<Line> = composer
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            // Highlight the subject line like git does
+            let style = if i == 0 {
+                Style::default().fg(theme.text).bold()
+            } else {
+                Style::default().fg(theme.text)
+            };
+            Line::from(Span::styled(line.clone(), style))
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(editor_block)
+            .scroll((scroll as u16, 0)),
+        chunks[1],
+    );
+
+    let cursor_x = editor_area.x + (composer.col as u16).min(editor_area.width.saturating_sub(1));
+    let cursor_y = editor_area.y + (composer.row - scroll) as u16;
+    frame.set_cursor_position((cursor_x, cursor_y));
+
+    // Error message or key hints
+    let footer = match &composer.error {
+        Some(error) => Span::styled(format!("✗ {}", error), Style::default().fg(theme.deleted)),
+        None => Span::styled(
+            "Ctrl+S: commit | Ctrl+A: toggle amend | Esc: cancel",
+            Style::default().fg(theme.subtext).dim(),
+        ),
+    };
+    frame.render_widget(Paragraph::new(Line::from(footer)), chunks[2]);
+}
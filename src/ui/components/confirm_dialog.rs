@@ -0,0 +1,44 @@
+use super::centered_rect;
+use crate::app::App;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+
+/// Render the confirmation dialog of a pending discard
+pub fn render_confirm_dialog(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let Some(discard) = &app.pending_discard else {
+        return;
+    };
+
+    let popup = centered_rect(area, 60, 7);
+    frame.render_widget(Clear, popup);
+
+    let block = Block::default()
+        .title(" Discard changes ")
+        .title_style(Style::default().fg(theme.deleted).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.deleted))
+        .style(Style::default().bg(theme.background));
+
+    let lines = vec![
+        Line::from(Span::styled(
+            format!("Discard {}?", discard.description()),
+            Style::default().fg(theme.text).bold(),
+        )),
+        Line::from(Span::styled(
+            "The discarded content can be restored with u.",
+            Style::default().fg(theme.subtext),
+        )),
+        Line::default(),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(theme.deleted).bold()),
+            Span::styled(": discard | ", Style::default().fg(theme.subtext).dim()),
+            Span::styled("n", Style::default().fg(theme.staged).bold()),
+            Span::styled("/Esc: cancel", Style::default().fg(theme.subtext).dim()),
+        ]),
+    ];
+    frame.render_widget(
+        Paragraph::new(lines).block(block).wrap(Wrap { trim: true }),
+        popup,
+    );
+}
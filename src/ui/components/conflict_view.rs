@@ -0,0 +1,168 @@
+use super::centered_rect;
+use crate::app::App;
+use crate::git::{ConflictRow, Resolution};
+use crate::ui::theme::Theme;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+/// Render the side-by-side conflict resolution view over the whole screen
+pub fn render_conflict_view(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let Some(view) = &app.conflict else {
+        return;
+    };
+    let file = &view.file;
+
+    let popup = centered_rect(
+        area,
+        area.width.saturating_sub(4),
+        area.height.saturating_sub(2),
+    );
+    frame.render_widget(Clear, popup);
+
+    let unresolved = file.unresolved_count();
+    let progress = if unresolved == 0 {
+        "all resolved".to_string()
+    } else {
+        format!("{} of {} unresolved", unresolved, file.conflict_count())
+    };
+    let block = Block::default()
+        .title(format!(" Resolve: {} ({}) ", file.path, progress))
+        .title_style(Style::default().fg(theme.text).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.conflicted))
+        .style(Style::default().bg(theme.background));
+    let inner = block.inner(popup);
+    frame.render_widget(block, popup);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // Column titles
+            Constraint::Min(1),    // Rows
+            Constraint::Length(1), // Key hints
+        ])
+        .split(inner);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[1]);
+    let title_columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[0]);
+
+    let column_title = |text: &'static str, color: Color| {
+        Paragraph::new(Span::styled(text, Style::default().fg(color).bold()))
+    };
+    frame.render_widget(column_title(" Ours", theme.staged), title_columns[0]);
+    frame.render_widget(column_title(" Theirs", theme.added), title_columns[1]);
+
+    let (ours, theirs): (Vec<Line>, Vec<Line>) = file
+        .rows()
+        .into_iter()
+        .skip(view.scroll)
+        .take(chunks[1].height as usize)
+        .map(|row| row_lines(app, row))
+        .unzip();
+    let column = |lines: Vec<Line<'static>>| {
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::RIGHT)
+                .border_style(Style::default().fg(theme.border)),
+        )
+    };
+    frame.render_widget(column(ours), columns[0]);
+    frame.render_widget(Paragraph::new(theirs), columns[1]);
+
+    let hints = if file.conflict_count() == 0 {
+        "No conflicting lines | Enter: mark resolved | Esc: close"
+    } else {
+        "n/p: region | o: ours | t: theirs | b: both | u: undo | Enter: mark resolved | Esc: close"
+    };
+    frame.render_widget(
+        Paragraph::new(Span::styled(
+            hints,
+            Style::default().fg(theme.subtext).dim(),
+        )),
+        chunks[2],
+    );
+}
+
+/// Our and their side of one row
+fn row_lines(app: &App, row: ConflictRow) -> (Line<'static>, Line<'static>) {
+    let theme = &app.theme;
+    let Some(view) = &app.conflict else {
+        return (Line::default(), Line::default());
+    };
+    let dim = Style::default().fg(theme.subtext).dim();
+
+    match row {
+        ConflictRow::Clean(text) => {
+            let line = Line::from(Span::styled(format!(" {}", text), dim));
+            (line.clone(), line)
+        }
+        ConflictRow::Fold(count) => {
+            let line = Line::from(Span::styled(
+                format!(" ⋯ {} unchanged lines", count),
+                dim.italic(),
+            ));
+            (line.clone(), line)
+        }
+        ConflictRow::Header(index) => {
+            let resolution = view.file.region(index).and_then(|r| r.resolution);
+            let style = if index == view.selected {
+                Style::default()
+                    .fg(theme.background)
+                    .bg(theme.conflicted)
+                    .bold()
+            } else {
+                Style::default().fg(theme.conflicted).bold()
+            };
+            let status = match resolution {
+                Some(resolution) => format!("→ {}", resolution.label()),
+                None => "unresolved".to_string(),
+            };
+            (
+                Line::from(Span::styled(
+                    format!(" Conflict {}/{} ", index + 1, view.file.conflict_count()),
+                    style,
+                )),
+                Line::from(Span::styled(format!(" {} ", status), style)),
+            )
+        }
+        ConflictRow::Pair {
+            conflict,
+            ours,
+            theirs,
+        } => {
+            let resolution = view.file.region(conflict).and_then(|r| r.resolution);
+            let kept = |side: Resolution| match resolution {
+                None => None,
+                Some(Resolution::Both) => Some(true),
+                Some(chosen) => Some(chosen == side),
+            };
+            (
+                side_line(theme, ours, kept(Resolution::Ours), theme.staged),
+                side_line(theme, theirs, kept(Resolution::Theirs), theme.added),
+            )
+        }
+    }
+}
+
+/// A line of one side of a conflict region: marked and colored while
+/// undecided or kept, dimmed when the other side was chosen
+fn side_line(theme: &Theme, text: Option<&str>, kept: Option<bool>, color: Color) -> Line<'static> {
+    let Some(text) = text else {
+        return Line::default();
+    };
+    let (marker, style) = match kept {
+        None => ("│", Style::default().fg(color)),
+        Some(true) => ("✓", Style::default().fg(color).bold()),
+        Some(false) => (" ", Style::default().fg(theme.subtext).dim().crossed_out()),
+    };
+    Line::from(vec![
+        Span::styled(marker, Style::default().fg(color)),
+        Span::styled(text.to_string(), style),
+    ])
+}
@@ -1,73 +1,91 @@
 use crate::app::{ActivePanel, App};
+use crate::git::{
+    BlobSummary, DiffHunk, DiffLayout, DiffLine, DiffNotice, DiffSource, FileDiff, Granularity,
+    LineKind,
+};
+use crate::ui::highlight::Segments;
 use crate::ui::theme::Theme;
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Paragraph};
+use std::ops::Range;
+use unicode_width::UnicodeWidthChar;
+
+/// Columns a tab expands to in the side-by-side layout
+const TAB_WIDTH: usize = 4;
 
 pub fn render_diff_view(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
     let is_active = app.active_panel == ActivePanel::DiffView;
 
-    let title = match app.selected_file() {
-        Some(file) => format!(" Diff: {} ", file.path),
+    let diff = app.get_diff().filter(|d| !d.is_empty());
+
+    let title = match app.selected_row() {
+        Some((file, source)) => {
+            let mut details = vec![match source {
+                DiffSource::Staged => "staged".to_string(),
+                DiffSource::Unstaged => "unstaged".to_string(),
+            }];
+            if let Some(similarity) = app.get_diff().and_then(|d| d.similarity) {
+                details.push(format!("{}% similar", similarity));
+            }
+            if app.diff_layout == DiffLayout::SideBySide {
+                details.push("side-by-side".to_string());
+            }
+            if app.granularity != Granularity::default() {
+                details.push(format!("{} diff", app.granularity.label()));
+            }
+            details.extend(app.diff_settings.labels());
+            let path = match app.get_diff().and_then(|d| d.old_path.as_deref()) {
+                Some(old_path) => format!("{} → {}", old_path, file.path),
+                None => file.path.clone(),
+            };
+            format!(" Diff: {} ({}) ", path, details.join(", "))
+        }
         None => " Diff Preview ".to_string(),
     };
 
     let border_color = if is_active {
-        Theme::ACCENT
+        theme.accent
     } else {
-        Theme::BORDER
+        theme.border
     };
 
     let block = Block::default()
         .title(title)
-        .title_style(Style::default().fg(Theme::TEXT).bold())
+        .title_style(Style::default().fg(theme.text).bold())
         .borders(Borders::ALL)
         .border_style(Style::default().fg(border_color));
 
-    let diff_content = app.get_diff().unwrap_or_default();
-
-    if diff_content.is_empty() {
-        let empty_message = if app.files.is_empty() {
+    let Some(diff) = diff else {
+        let empty_message = if app.loading {
+            "Scanning repository..."
+        } else if app.files.is_empty() {
             "No changes detected"
+        } else if app.selected_row().is_some() && app.get_diff().is_none() {
+            "Loading diff..."
         } else {
             "Select a file to view diff"
         };
 
         let paragraph = Paragraph::new(empty_message)
             .block(block)
-            .style(Style::default().fg(Theme::SUBTEXT))
+            .style(Style::default().fg(theme.subtext))
             .alignment(Alignment::Center);
 
         frame.render_widget(paragraph, area);
         return;
-    }
-
-    // Parse and colorize diff content
-    let styled_lines: Vec<Line> = diff_content
-        .lines()
-        .enumerate()
-        .map(|(_, line)| {
-            let (style, prefix) = if line.starts_with("@@") {
-                (
-                    Style::default().fg(Theme::DIFF_HUNK).bold(),
-                    "",
-                )
-            } else if line.starts_with('+') && !line.starts_with("+++") {
-                (
-                    Style::default().fg(Theme::DIFF_ADD),
-                    "",
-                )
-            } else if line.starts_with('-') && !line.starts_with("---") {
-                (
-                    Style::default().fg(Theme::DIFF_DEL),
-                    "",
-                )
-            } else {
-                (Style::default().fg(Theme::SUBTEXT), "")
-            };
+    };
 
-            Line::from(Span::styled(format!("{}{}", prefix, line), style))
-        })
-        .collect();
+    let mut styled_lines = match app.diff_layout {
+        DiffLayout::Unified => unified_lines(app, diff, is_active),
+        DiffLayout::SideBySide => {
+            split_lines(app, diff, is_active, area.width.saturating_sub(2) as usize)
+        }
+    };
+    // Below the hunks, so hunk offsets stay the same
+    if let Some(notice) = &diff.notice {
+        styled_lines.extend(notice_lines(theme, notice));
+    }
 
     let total_lines = styled_lines.len();
     let visible_lines = (area.height.saturating_sub(2)) as usize;
@@ -84,11 +102,7 @@ pub fn render_diff_view(frame: &mut Frame, app: &App, area: Rect) {
 
     // Render scroll indicator if needed
     if total_lines > visible_lines {
-        let scroll_percent = if max_scroll > 0 {
-            (scroll_offset * 100) / max_scroll
-        } else {
-            0
-        };
+        let scroll_percent = (scroll_offset * 100).checked_div(max_scroll).unwrap_or(0);
         let indicator = format!(" {}% ", scroll_percent);
         let indicator_area = Rect {
             x: area.x + area.width - indicator.len() as u16 - 2,
@@ -97,8 +111,468 @@ pub fn render_diff_view(frame: &mut Frame, app: &App, area: Rect) {
             height: 1,
         };
         frame.render_widget(
-            Paragraph::new(indicator).style(Style::default().fg(Theme::SUBTEXT).dim()),
+            Paragraph::new(indicator).style(Style::default().fg(theme.subtext).dim()),
             indicator_area,
         );
     }
 }
+
+/// Summary of a binary, LFS or truncated diff
+fn notice_lines(theme: &Theme, notice: &DiffNotice) -> Vec<Line<'static>> {
+    let title = |text: String| {
+        Line::from(Span::styled(
+            format!(" {}", text),
+            Style::default().fg(theme.diff_hunk).bold(),
+        ))
+    };
+
+    match notice {
+        DiffNotice::Binary { old, new } => vec![
+            title("Binary file changed".to_string()),
+            blob_line(theme, "old", old.as_ref(), theme.diff_del),
+            blob_line(theme, "new", new.as_ref(), theme.diff_add),
+        ],
+        DiffNotice::Lfs { old, new } => vec![
+            title("Git LFS object changed".to_string()),
+            blob_line(theme, "old", old.as_ref(), theme.diff_del),
+            blob_line(theme, "new", new.as_ref(), theme.diff_add),
+        ],
+        DiffNotice::Truncated { shown, total } => vec![
+            Line::default(),
+            title(format!("Showing {} of {} lines", shown, total)),
+            Line::from(Span::styled(
+                " File too large, press x to load anyway",
+                Style::default().fg(theme.subtext),
+            )),
+        ],
+    }
+}
+
+/// One side of a binary or LFS change: abbreviated id, size and image details
+fn blob_line(
+    theme: &Theme,
+    label: &str,
+    blob: Option<&BlobSummary>,
+    color: Color,
+) -> Line<'static> {
+    let label = Span::styled(format!("   {} ", label), Style::default().fg(color).bold());
+    let Some(blob) = blob else {
+        return Line::from(vec![
+            label,
+            Span::styled("(none)", Style::default().fg(theme.subtext).dim()),
+        ]);
+    };
+
+    let mut details = format!(
+        "{}  {}",
+        &blob.oid[..blob.oid.len().min(12)],
+        format_size(blob.size)
+    );
+    if let Some(image) = blob.image {
+        details.push_str(&format!(
+            "  {} {}×{}",
+            image.format, image.width, image.height
+        ));
+    }
+    Line::from(vec![
+        label,
+        Span::styled(details, Style::default().fg(theme.text)),
+    ])
+}
+
+/// Byte count in binary units, e.g. "1.5 MiB"
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Gutter bar marking the selected hunk
+fn hunk_gutter(theme: &Theme, is_selected: bool) -> Span<'static> {
+    if is_selected {
+        Span::styled("▌", Style::default().fg(theme.accent))
+    } else {
+        Span::raw(" ")
+    }
+}
+
+fn hunk_header(theme: &Theme, hunk: &DiffHunk, is_selected: bool) -> Line<'static> {
+    let header_style = if is_selected {
+        Style::default()
+            .fg(theme.diff_hunk)
+            .bg(theme.overlay)
+            .bold()
+    } else {
+        Style::default().fg(theme.diff_hunk).bold()
+    };
+    Line::from(vec![
+        hunk_gutter(theme, is_selected),
+        Span::styled(hunk.header.clone(), header_style),
+    ])
+}
+
+/// Colorize hunks, marking the selected one with a gutter bar
+fn unified_lines(app: &App, diff: &FileDiff, is_active: bool) -> Vec<Line<'static>> {
+    let theme = &app.theme;
+    let highlights = app.diff_highlights(diff);
+
+    let mut styled_lines: Vec<Line> = Vec::with_capacity(diff.line_count(DiffLayout::Unified));
+    for (hunk_index, hunk) in diff.hunks.iter().enumerate() {
+        let is_selected = is_active && hunk_index == app.selected_hunk;
+        styled_lines.push(hunk_header(theme, hunk, is_selected));
+
+        for (line_index, line) in hunk.lines.iter().enumerate() {
+            let mut highlighted = false;
+            let mut line_gutter = hunk_gutter(theme, is_selected);
+            if let Some(selection) = &app.line_selection {
+                highlighted = selection.contains(hunk_index, line_index);
+                if selection.hunk == hunk_index && selection.cursor == line_index {
+                    line_gutter = Span::styled("▶", Style::default().fg(theme.highlight));
+                }
+            }
+
+            let segments = highlights.and_then(|h| h.line(hunk_index, line_index));
+            let emphasis = highlights.map_or(&[][..], |h| {
+                h.emphasis(hunk_index, line_index, app.granularity)
+            });
+            let mut spans = vec![line_gutter];
+            spans.extend(
+                content_segments(theme, line, segments, emphasis, highlighted)
+                    .into_iter()
+                    .map(|(text, style)| Span::styled(text, style)),
+            );
+            styled_lines.push(Line::from(spans));
+        }
+    }
+    styled_lines
+}
+
+/// Old lines on the left and new lines on the right, each with its own
+/// line number gutter, separated by a vertical rule
+fn split_lines(app: &App, diff: &FileDiff, is_active: bool, width: usize) -> Vec<Line<'static>> {
+    let theme = &app.theme;
+    let highlights = app.diff_highlights(diff);
+
+    // Hunk gutter and separator take one column each
+    let half = width.saturating_sub(2) / 2;
+    let number_width = diff
+        .hunks
+        .iter()
+        .flat_map(|h| h.lines.iter())
+        .flat_map(|l| [l.old_lineno, l.new_lineno])
+        .flatten()
+        .max()
+        .unwrap_or(0)
+        .to_string()
+        .len();
+
+    let mut styled_lines: Vec<Line> = Vec::with_capacity(diff.line_count(DiffLayout::SideBySide));
+    for (hunk_index, hunk) in diff.hunks.iter().enumerate() {
+        let is_selected = is_active && hunk_index == app.selected_hunk;
+        styled_lines.push(hunk_header(theme, hunk, is_selected));
+
+        for row in hunk.split_rows() {
+            let mut gutter = hunk_gutter(theme, is_selected);
+            let mut highlighted = [false, false];
+            if let Some(selection) = app.line_selection.as_ref().filter(|s| s.hunk == hunk_index) {
+                for (side, line) in [row.old, row.new].into_iter().enumerate() {
+                    highlighted[side] = line.is_some_and(|l| selection.contains(hunk_index, l));
+                    if line == Some(selection.cursor) {
+                        gutter = Span::styled("▶", Style::default().fg(theme.highlight));
+                    }
+                }
+            }
+
+            let mut spans = vec![gutter];
+            for (side, index) in [row.old, row.new].into_iter().enumerate() {
+                if side == 1 {
+                    spans.push(Span::styled("│", Style::default().fg(theme.border)));
+                }
+                let line = index.map(|i| &hunk.lines[i]);
+                let lineno = line.and_then(|l| {
+                    if side == 0 {
+                        l.old_lineno
+                    } else {
+                        l.new_lineno
+                    }
+                });
+                let segments = index.and_then(|i| highlights.and_then(|h| h.line(hunk_index, i)));
+                let emphasis = index
+                    .zip(highlights)
+                    .map_or(&[][..], |(i, h)| h.emphasis(hunk_index, i, app.granularity));
+                spans.extend(split_side(
+                    theme,
+                    line,
+                    lineno,
+                    segments,
+                    emphasis,
+                    number_width,
+                    half,
+                    highlighted[side],
+                ));
+            }
+            styled_lines.push(Line::from(spans));
+        }
+    }
+    styled_lines
+}
+
+/// One half of a side-by-side row: line number plus content fitted to `width`
+#[allow(clippy::too_many_arguments)]
+fn split_side(
+    theme: &Theme,
+    line: Option<&DiffLine>,
+    lineno: Option<u32>,
+    segments: Option<&Segments>,
+    emphasis: &[Range<usize>],
+    number_width: usize,
+    width: usize,
+    highlighted: bool,
+) -> Vec<Span<'static>> {
+    // "No newline" markers carry the number of the line they annotate
+    let lineno = lineno.filter(|_| line.is_some_and(|l| l.kind != LineKind::NoNewline));
+    let number = match lineno {
+        Some(n) => format!("{:>w$} ", n, w = number_width),
+        None => " ".repeat(number_width + 1),
+    };
+    let content_width = width.saturating_sub(number_width + 1);
+
+    let Some(line) = line else {
+        // Filler opposite an unpaired addition or deletion
+        return vec![
+            Span::styled(number, Style::default().fg(theme.border)),
+            Span::styled(
+                " ".repeat(content_width),
+                Style::default().bg(theme.surface),
+            ),
+        ];
+    };
+
+    let segments = content_segments(theme, line, segments, emphasis, highlighted);
+    let mut spans = vec![Span::styled(
+        number,
+        Style::default().fg(theme.subtext).dim(),
+    )];
+    spans.extend(fit_segments(segments, content_width));
+    spans
+}
+
+/// Text of a diff line split into styled pieces: the prefix, then either the
+/// syntax-colored code over the add/delete tint or the plain diff color, with
+/// the `emphasis` byte ranges of the content picked out
+fn content_segments(
+    theme: &Theme,
+    line: &DiffLine,
+    segments: Option<&Segments>,
+    emphasis: &[Range<usize>],
+    highlighted: bool,
+) -> Vec<(String, Style)> {
+    let mut style = line_style(theme, line.kind);
+    let mut tint = Style::default();
+    let tinted = segments.is_some() || !emphasis.is_empty();
+    if let Some(bg) = tint_color(theme, line.kind).filter(|_| tinted) {
+        style = style.bg(bg);
+        tint = tint.bg(bg);
+    }
+    if highlighted {
+        style = style.bg(theme.overlay);
+        tint = tint.bg(theme.overlay);
+    }
+
+    let pieces: Vec<(String, Style)> = match segments.filter(|s| !s.is_empty()) {
+        Some(segments) => segments
+            .iter()
+            .map(|(color, text)| (text.clone(), tint.fg(*color)))
+            .collect(),
+        None => vec![(line.content.clone(), style)],
+    };
+
+    let mut emphasis_style = Style::default().bold();
+    if let Some(bg) = emphasis_color(theme, line.kind).filter(|_| !highlighted) {
+        emphasis_style = emphasis_style.bg(bg);
+    }
+
+    std::iter::once((line.kind.prefix().to_string(), style))
+        .chain(emphasize(pieces, emphasis, emphasis_style))
+        .collect()
+}
+
+/// Split pieces at the emphasized byte ranges (offsets into the joined
+/// text), patching `emphasis_style` onto the parts inside them
+fn emphasize(
+    pieces: Vec<(String, Style)>,
+    emphasis: &[Range<usize>],
+    emphasis_style: Style,
+) -> Vec<(String, Style)> {
+    if emphasis.is_empty() {
+        return pieces;
+    }
+
+    let mut result = Vec::with_capacity(pieces.len() + emphasis.len() * 2);
+    let mut offset = 0;
+    for (text, style) in pieces {
+        let end = offset + text.len();
+        // Cut points inside this piece, relative to its start
+        let mut cuts: Vec<usize> = emphasis
+            .iter()
+            .flat_map(|r| [r.start, r.end])
+            .filter(|&cut| cut > offset && cut < end)
+            .map(|cut| cut - offset)
+            .collect();
+        cuts.push(text.len());
+
+        let mut start = 0;
+        for cut in cuts {
+            let absolute = offset + start;
+            let part_style = if emphasis.iter().any(|r| r.contains(&absolute)) {
+                style.patch(emphasis_style)
+            } else {
+                style
+            };
+            result.push((text[start..cut].to_string(), part_style));
+            start = cut;
+        }
+        offset = end;
+    }
+    result
+}
+
+/// Expand tabs, then truncate or pad the pieces to exactly `width` columns.
+/// Padding takes the background of the last piece.
+fn fit_segments(segments: Vec<(String, Style)>, width: usize) -> Vec<Span<'static>> {
+    let mut spans = Vec::with_capacity(segments.len() + 1);
+    let mut used = 0;
+    let mut pad_style = Style::default();
+
+    for (text, style) in segments {
+        if used >= width {
+            break;
+        }
+        let mut fitted = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c == '\t' {
+                let spaces = (TAB_WIDTH - used % TAB_WIDTH).min(width - used);
+                fitted.push_str(&" ".repeat(spaces));
+                used += spaces;
+            } else {
+                let char_width = c.width().unwrap_or(0);
+                if used + char_width > width {
+                    // A wide character that does not fit ends the line
+                    break;
+                }
+                fitted.push(c);
+                used += char_width;
+            }
+            if used >= width {
+                break;
+            }
+        }
+        pad_style = Style::default().bg(style.bg.unwrap_or(Color::Reset));
+        spans.push(Span::styled(fitted, style));
+    }
+
+    if used < width {
+        spans.push(Span::styled(" ".repeat(width - used), pad_style));
+    }
+    spans
+}
+
+/// Background under the changed words of a paired added or deleted line
+fn emphasis_color(theme: &Theme, kind: LineKind) -> Option<Color> {
+    match kind {
+        LineKind::Addition => Some(theme.diff_add_emph),
+        LineKind::Deletion => Some(theme.diff_del_emph),
+        LineKind::Context | LineKind::NoNewline => None,
+    }
+}
+
+/// Background under syntax-highlighted or emphasized added and deleted lines
+fn tint_color(theme: &Theme, kind: LineKind) -> Option<Color> {
+    match kind {
+        LineKind::Addition => Some(theme.diff_add_bg),
+        LineKind::Deletion => Some(theme.diff_del_bg),
+        LineKind::Context | LineKind::NoNewline => None,
+    }
+}
+
+fn line_style(theme: &Theme, kind: LineKind) -> Style {
+    match kind {
+        LineKind::Addition => Style::default().fg(theme.diff_add),
+        LineKind::Deletion => Style::default().fg(theme.diff_del),
+        LineKind::NoNewline => Style::default().fg(theme.subtext).dim(),
+        LineKind::Context => Style::default().fg(theme.subtext),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fit(text: &str, width: usize) -> String {
+        fit_segments(vec![(text.to_string(), Style::default())], width)
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn test_fit_segments_pads_truncates_and_expands_tabs() {
+        assert_eq!(fit("ab", 4), "ab  ");
+        assert_eq!(fit("abcdef", 4), "abcd");
+        assert_eq!(fit("a\tb", 6), "a   b ");
+        // Wide characters never overflow the column
+        assert_eq!(fit("日本語", 5), "日本 ");
+    }
+
+    #[test]
+    fn test_emphasize_splits_pieces_at_ranges() {
+        let plain = Style::default();
+        let bold = Style::default().bold();
+        let pieces = vec![("let x".to_string(), plain), (" = 10;".to_string(), plain)];
+
+        let parts: Vec<(String, bool)> = emphasize(pieces, &[4..5, 8..10], bold)
+            .into_iter()
+            .map(|(text, style)| (text, style.add_modifier.contains(Modifier::BOLD)))
+            .collect();
+        let expected = [
+            ("let ", false),
+            ("x", true),
+            (" = ", false),
+            ("10", true),
+            (";", false),
+        ];
+        assert_eq!(
+            parts,
+            expected
+                .iter()
+                .map(|(t, b)| (t.to_string(), *b))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_fit_segments_spans_pieces() {
+        let segments = vec![
+            ("ab".to_string(), Style::default().fg(Color::Red)),
+            ("cdef".to_string(), Style::default().fg(Color::Blue)),
+        ];
+        let spans = fit_segments(segments, 4);
+        let texts: Vec<&str> = spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(texts, vec!["ab", "cd"]);
+    }
+
+    #[test]
+    fn test_format_size_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -1,39 +1,51 @@
-use crate::app::{ActivePanel, App};
-use crate::git::FileStatus;
+use crate::app::{ActivePanel, App, ListEntry};
+use crate::git::{DiffSource, FileStatus};
 use crate::ui::icons::FileIcon;
 use crate::ui::theme::Theme;
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, List, ListItem, ListState};
 
 pub fn render_file_list(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
     let is_active = app.active_panel == ActivePanel::FileList;
 
     let title = format!(" Changed Files ({}) ", app.files.len());
     let border_color = if is_active {
-        Theme::ACCENT
+        theme.accent
     } else {
-        Theme::BORDER
+        theme.border
     };
 
     let block = Block::default()
         .title(title)
-        .title_style(Style::default().fg(Theme::TEXT).bold())
+        .title_style(Style::default().fg(theme.text).bold())
         .borders(Borders::ALL)
         .border_style(Style::default().fg(border_color));
 
     // Available width inside the block (subtract borders)
     let inner_width = area.width.saturating_sub(2) as usize;
 
-    let items: Vec<ListItem> = app
-        .files
+    let entries = app.list_entries();
+
+    let items: Vec<ListItem> = entries
         .iter()
-        .enumerate()
-        .map(|(i, file)| {
+        .map(|entry| {
+            let i = match *entry {
+                ListEntry::Section(source, count) => return section_header(theme, source, count),
+                ListEntry::Row(i) => i,
+            };
+            let row = app.rows[i];
+            let file = &app.files[row.file];
             let is_selected = i == app.selected_index;
             let is_recently_changed = app.is_recently_changed(&file.path);
 
             // Status indicator and color
-            let (status_char, status_color) = get_status_display(&file.status, file.staged);
+            let (status_char, status_color) = match file.status(row.source) {
+                Some(status) => {
+                    get_status_display(theme, &status, row.source == DiffSource::Staged)
+                }
+                None => (" ", theme.subtext),
+            };
 
             // Split path into filename and directory
             let (filename, directory) = split_path(&file.path);
@@ -42,7 +54,7 @@ pub fn render_file_list(frame: &mut Frame, app: &App, area: Rect) {
             let file_icon = FileIcon::from_filename(&filename);
 
             // Calculate number width for alignment (e.g., " 1." vs "10.")
-            let total_files = app.files.len();
+            let total_files = app.rows.len();
             let num_width = if total_files >= 100 {
                 4 // "100."
             } else if total_files >= 10 {
@@ -55,7 +67,19 @@ pub fn render_file_list(frame: &mut Frame, app: &App, area: Rect) {
             // Format: "N. icon  filename directory... S"
             // Fixed parts: num(2-4) + space(1) + icon(1-2) + space(1) + space(1) + status(1)
             let fixed_width = num_width + 1 + 5;
-            let available_for_path = inner_width.saturating_sub(fixed_width);
+            let mut available_for_path = inner_width.saturating_sub(fixed_width);
+
+            // Renames and copies lead with where the file came from, as long
+            // as the new filename still fits after it
+            let origin = file
+                .old_path(row.source)
+                .map(|old| format!("{} → ", origin_label(old, &directory)))
+                .filter(|origin| {
+                    origin.chars().count() + filename.chars().count() <= available_for_path
+                });
+            if let Some(origin) = &origin {
+                available_for_path -= origin.chars().count();
+            }
 
             // Build the display strings
             let (display_filename, display_dir) =
@@ -68,7 +92,9 @@ pub fn render_file_list(frame: &mut Frame, app: &App, area: Rect) {
             } else {
                 display_dir.len() + 1 // +1 for space before dir
             };
-            let content_len = num_width + 1 + 3 + display_filename.len() + dir_display_len;
+            let origin_len = origin.as_ref().map_or(0, |o| o.chars().count());
+            let content_len =
+                num_width + 1 + 3 + origin_len + display_filename.len() + dir_display_len;
             let padding = inner_width.saturating_sub(content_len + 1); // +1 for status char
 
             // Calculate animation state
@@ -77,48 +103,61 @@ pub fn render_file_list(frame: &mut Frame, app: &App, area: Rect) {
                 let brightness = calculate_pulse_brightness(progress);
 
                 // Interpolate icon color towards bright yellow/white during pulse
-                let animated_icon_color = interpolate_color(
-                    file_icon.color,
-                    Theme::FLASH_BRIGHT,
-                    brightness * 0.7,
-                );
+                let animated_icon_color =
+                    interpolate_color(file_icon.color, theme.flash_bright, brightness * 0.7);
 
                 // Make text brighter during pulse
-                let animated_text_color = interpolate_color(
-                    Theme::TEXT,
-                    Theme::FLASH_BRIGHT,
-                    brightness * 0.5,
-                );
+                let animated_text_color =
+                    interpolate_color(theme.text, theme.flash_bright, brightness * 0.5);
 
-                let bg = Style::default()
-                    .bg(interpolate_color(Theme::SURFACE, Theme::FLASH_BG, brightness));
+                let bg = Style::default().bg(interpolate_color(
+                    theme.surface,
+                    theme.flash_bg,
+                    brightness,
+                ));
 
                 (animated_icon_color, animated_text_color, bg)
             } else if is_selected {
-                (file_icon.color, Theme::TEXT, Style::default().bg(Theme::OVERLAY))
+                (
+                    file_icon.color,
+                    theme.text,
+                    Style::default().bg(theme.overlay),
+                )
             } else {
-                (file_icon.color, Theme::TEXT, Style::default())
+                (file_icon.color, theme.text, Style::default())
             };
 
             // Build styled spans
             let num_display = format!("{:>width$} ", i + 1, width = num_width);
             let mut spans = vec![
                 // Line number
-                Span::styled(num_display, Style::default().fg(Theme::SUBTEXT).dim()),
+                Span::styled(num_display, Style::default().fg(theme.subtext).dim()),
                 // File type icon (with animation)
                 Span::styled(
                     format!("{} ", file_icon.icon),
                     Style::default().fg(icon_color),
                 ),
-                // Filename (with animation)
-                Span::styled(display_filename.clone(), Style::default().fg(text_color)),
             ];
 
+            // Old path of a rename or copy (dim)
+            if let Some(origin) = origin {
+                spans.push(Span::styled(
+                    origin,
+                    Style::default().fg(theme.subtext).dim(),
+                ));
+            }
+
+            // Filename (with animation)
+            spans.push(Span::styled(
+                display_filename.clone(),
+                Style::default().fg(text_color),
+            ));
+
             // Directory (dim)
             if !display_dir.is_empty() {
                 spans.push(Span::styled(
                     format!(" {}", display_dir),
-                    Style::default().fg(Theme::SUBTEXT).dim(),
+                    Style::default().fg(theme.subtext).dim(),
                 ));
             }
 
@@ -137,16 +176,47 @@ pub fn render_file_list(frame: &mut Frame, app: &App, area: Rect) {
 
     let list = List::new(items).block(block).highlight_style(
         Style::default()
-            .bg(Theme::OVERLAY)
+            .bg(theme.overlay)
             .add_modifier(Modifier::BOLD),
     );
 
     let mut state = ListState::default();
-    state.select(Some(app.selected_index));
+    state.select(
+        entries
+            .iter()
+            .position(|e| *e == ListEntry::Row(app.selected_index)),
+    );
 
     frame.render_stateful_widget(list, area, &mut state);
 }
 
+/// Section header line ("Staged Changes (2)" / "Changes (3)")
+fn section_header(theme: &Theme, source: DiffSource, count: usize) -> ListItem<'static> {
+    let (label, color) = match source {
+        DiffSource::Staged => ("Staged Changes", theme.staged),
+        DiffSource::Unstaged => ("Changes", theme.modified),
+    };
+
+    ListItem::new(Line::from(vec![
+        Span::styled(format!(" {} ", label), Style::default().fg(color).bold()),
+        Span::styled(
+            format!("({})", count),
+            Style::default().fg(theme.subtext).dim(),
+        ),
+    ]))
+}
+
+/// How to show the old path of a rename: just its filename when it stayed in
+/// `directory`, the whole path when it moved
+fn origin_label(old_path: &str, directory: &str) -> String {
+    let (old_filename, old_directory) = split_path(old_path);
+    if old_directory == directory {
+        old_filename
+    } else {
+        old_path.to_string()
+    }
+}
+
 /// Split a path into (filename, directory)
 fn split_path(path: &str) -> (String, String) {
     if let Some(pos) = path.rfind('/') {
@@ -205,20 +275,20 @@ fn format_filename_and_dir(filename: &str, directory: &str, available: usize) ->
     }
 }
 
-fn get_status_display(status: &FileStatus, staged: bool) -> (&'static str, Color) {
+fn get_status_display(theme: &Theme, status: &FileStatus, staged: bool) -> (&'static str, Color) {
     let color = match status {
         FileStatus::Modified => {
             if staged {
-                Theme::STAGED
+                theme.staged
             } else {
-                Theme::MODIFIED
+                theme.modified
             }
         }
-        FileStatus::Added => Theme::ADDED,
-        FileStatus::Deleted => Theme::DELETED,
-        FileStatus::Renamed => Theme::RENAMED,
-        FileStatus::Untracked => Theme::UNTRACKED,
-        FileStatus::Conflicted => Theme::CONFLICTED,
+        FileStatus::Added => theme.added,
+        FileStatus::Deleted => theme.deleted,
+        FileStatus::Renamed | FileStatus::Copied => theme.renamed,
+        FileStatus::Untracked => theme.untracked,
+        FileStatus::Conflicted => theme.conflicted,
     };
 
     (status.symbol(), color)
@@ -248,6 +318,20 @@ fn interpolate_color(from: Color, to: Color, factor: f64) -> Color {
 mod tests {
     use super::*;
 
+    // ==================== origin_label tests ====================
+
+    #[test]
+    fn test_origin_label_same_directory() {
+        assert_eq!(origin_label("src/old.rs", "src"), "old.rs");
+        assert_eq!(origin_label("old.md", ""), "old.md");
+    }
+
+    #[test]
+    fn test_origin_label_moved() {
+        assert_eq!(origin_label("lib/util.rs", "src"), "lib/util.rs");
+        assert_eq!(origin_label("util.rs", "src"), "util.rs");
+    }
+
     // ==================== split_path tests ====================
 
     #[test]
@@ -349,8 +433,12 @@ mod tests {
         for i in 0..=100 {
             let progress = i as f64 / 100.0;
             let brightness = calculate_pulse_brightness(progress);
-            assert!(brightness >= 0.0 && brightness <= 1.0,
-                "Brightness {} out of range at progress {}", brightness, progress);
+            assert!(
+                (0.0..=1.0).contains(&brightness),
+                "Brightness {} out of range at progress {}",
+                brightness,
+                progress
+            );
         }
     }
 
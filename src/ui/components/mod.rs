@@ -1,7 +1,31 @@
+mod commit_editor;
+mod confirm_dialog;
+mod conflict_view;
 mod diff_view;
 mod file_list;
+mod notifications;
+mod stash_panel;
 mod status_bar;
 
+pub use commit_editor::render_commit_editor;
+pub use confirm_dialog::render_confirm_dialog;
+pub use conflict_view::render_conflict_view;
 pub use diff_view::render_diff_view;
 pub use file_list::render_file_list;
+pub use notifications::{render_message_log, render_toasts};
+pub use stash_panel::render_stash_panel;
 pub use status_bar::{render_header, render_status_bar};
+
+use ratatui::layout::Rect;
+
+/// Center a rectangle of at most `width` x `height` inside `area`
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width.saturating_sub(4));
+    let height = height.min(area.height.saturating_sub(2));
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,120 @@
+use super::centered_rect;
+use crate::app::App;
+use crate::notification::Severity;
+use crate::ui::theme::Theme;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+/// Render active toasts stacked in the bottom-right corner, above `bottom`
+pub fn render_toasts(frame: &mut Frame, app: &App, area: Rect, bottom: u16) {
+    let theme = &app.theme;
+    let mut y = bottom;
+
+    for notification in app.notifications.active().iter().rev() {
+        let text = format!(
+            " {} {} ",
+            notification.severity.symbol(),
+            notification.message
+        );
+        let width = (text.chars().count() as u16 + 2).min(area.width.saturating_sub(4));
+        if y < area.y + 3 || width < 5 {
+            break;
+        }
+        y -= 3;
+
+        let toast_area = Rect {
+            x: area.x + area.width - width - 2,
+            y,
+            width,
+            height: 3,
+        };
+        let color = severity_color(theme, notification.severity);
+
+        frame.render_widget(Clear, toast_area);
+        frame.render_widget(
+            Paragraph::new(Span::styled(text, Style::default().fg(color))).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(color))
+                    .style(Style::default().bg(theme.surface)),
+            ),
+            toast_area,
+        );
+    }
+}
+
+/// Render the scrollable message log panel
+pub fn render_message_log(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    if !app.show_log {
+        return;
+    }
+
+    let popup = centered_rect(
+        area,
+        area.width.saturating_sub(8),
+        area.height.saturating_sub(4),
+    );
+    frame.render_widget(Clear, popup);
+
+    let log = app.notifications.log();
+    let block = Block::default()
+        .title(format!(" Messages ({}) ", log.len()))
+        .title_style(Style::default().fg(theme.text).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent))
+        .style(Style::default().bg(theme.background));
+
+    if log.is_empty() {
+        frame.render_widget(
+            Paragraph::new("No messages")
+                .block(block)
+                .style(Style::default().fg(theme.subtext))
+                .alignment(Alignment::Center),
+            popup,
+        );
+        return;
+    }
+
+    let lines: Vec<Line> = log
+        .iter()
+        .map(|notification| {
+            let color = severity_color(theme, notification.severity);
+            Line::from(vec![
+                Span::styled(
+                    notification.timestamp.format("%H:%M:%S ").to_string(),
+                    Style::default().fg(theme.subtext).dim(),
+                ),
+                Span::styled(
+                    format!("{} ", notification.severity.symbol()),
+                    Style::default().fg(color).bold(),
+                ),
+                Span::styled(
+                    notification.message.clone(),
+                    Style::default().fg(theme.text),
+                ),
+            ])
+        })
+        .collect();
+
+    // log_scroll counts lines back from the newest message
+    let visible = popup.height.saturating_sub(2) as usize;
+    let max_scroll = lines.len().saturating_sub(visible);
+    let scroll = max_scroll.saturating_sub(app.log_scroll);
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .scroll((scroll as u16, 0)),
+        popup,
+    );
+}
+
+fn severity_color(theme: &Theme, severity: Severity) -> Color {
+    match severity {
+        Severity::Info => theme.accent,
+        Severity::Success => theme.staged,
+        Severity::Warning => theme.modified,
+        Severity::Error => theme.deleted,
+    }
+}
@@ -0,0 +1,154 @@
+use super::centered_rect;
+use crate::app::App;
+use crate::ui::theme::Theme;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
+
+/// Render the stash list with a patch preview of the selected stash
+pub fn render_stash_panel(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let Some(panel) = &app.stash_panel else {
+        return;
+    };
+
+    let popup = centered_rect(
+        area,
+        area.width.saturating_sub(8),
+        area.height.saturating_sub(4),
+    );
+    frame.render_widget(Clear, popup);
+
+    let block = Block::default()
+        .title(format!(" Stashes ({}) ", app.stashes.len()))
+        .title_style(Style::default().fg(theme.text).bold())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent))
+        .style(Style::default().bg(theme.background));
+    let inner = block.inner(popup);
+    frame.render_widget(block, popup);
+
+    let list_height = (app.stashes.len().max(1) as u16).min(inner.height / 3);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(list_height), // Stash list
+            Constraint::Min(1),              // Patch preview
+            Constraint::Length(1),           // Key hints
+        ])
+        .split(inner);
+
+    let hints = Paragraph::new(Span::styled(
+        "s: stash all | S: stash staged | f: stash file | a: apply | p: pop | d: drop | Esc: close",
+        Style::default().fg(theme.subtext).dim(),
+    ));
+    frame.render_widget(hints, chunks[2]);
+
+    if app.stashes.is_empty() {
+        frame.render_widget(
+            Paragraph::new("No stashes")
+                .style(Style::default().fg(theme.subtext))
+                .alignment(Alignment::Center),
+            chunks[0],
+        );
+        return;
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let items: Vec<ListItem> = app
+        .stashes
+        .iter()
+        .map(|stash| {
+            let files = match stash.files.len() {
+                1 => "1 file".to_string(),
+                count => format!("{} files", count),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!(" {:<10}", stash.name()),
+                    Style::default().fg(theme.highlight),
+                ),
+                Span::styled(
+                    format!("{} ", stash.branch.as_deref().unwrap_or("-")),
+                    Style::default().fg(theme.renamed),
+                ),
+                Span::styled(
+                    format!("{:>8} ", format_age(now - stash.time)),
+                    Style::default().fg(theme.subtext).dim(),
+                ),
+                Span::styled(
+                    format!("{:>9}  ", files),
+                    Style::default().fg(theme.subtext),
+                ),
+                Span::styled(stash.summary().to_string(), Style::default().fg(theme.text)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(Style::default().bg(theme.overlay).bold());
+    let mut state = ListState::default();
+    state.select(Some(panel.selected));
+    frame.render_stateful_widget(list, chunks[0], &mut state);
+
+    // Patch of the selected stash, or the last one while it loads
+    let files = app
+        .selected_stash()
+        .map(|s| s.files.join(", "))
+        .unwrap_or_default();
+    let preview_block = Block::default()
+        .title(format!(" {} ", files))
+        .title_style(Style::default().fg(theme.subtext))
+        .borders(Borders::TOP)
+        .border_style(Style::default().fg(theme.border));
+    let lines: Vec<Line> = app
+        .stash_patch
+        .iter()
+        .flat_map(|(_, lines)| lines.iter())
+        .skip(panel.scroll)
+        .take(chunks[1].height as usize)
+        .map(|line| Line::from(Span::styled(line.clone(), patch_style(theme, line))))
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(preview_block), chunks[1]);
+}
+
+/// Color of a patch line by its prefix
+fn patch_style(theme: &Theme, line: &str) -> Style {
+    if line.starts_with("diff --git") || line.starts_with("+++") || line.starts_with("---") {
+        Style::default().fg(theme.text).bold()
+    } else if line.starts_with("@@") {
+        Style::default().fg(theme.diff_hunk)
+    } else if line.starts_with('+') {
+        Style::default().fg(theme.diff_add)
+    } else if line.starts_with('-') {
+        Style::default().fg(theme.diff_del)
+    } else {
+        Style::default().fg(theme.subtext)
+    }
+}
+
+/// Short relative age, like "5m ago"
+fn format_age(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86_399 => format!("{}h ago", seconds / 3600),
+        86_400..=2_591_999 => format!("{}d ago", seconds / 86_400),
+        2_592_000..=31_535_999 => format!("{}mo ago", seconds / 2_592_000),
+        _ => format!("{}y ago", seconds / 31_536_000),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_age_units() {
+        assert_eq!(format_age(-5), "just now");
+        assert_eq!(format_age(59), "just now");
+        assert_eq!(format_age(120), "2m ago");
+        assert_eq!(format_age(3 * 3600 + 5), "3h ago");
+        assert_eq!(format_age(2 * 86_400), "2d ago");
+        assert_eq!(format_age(70 * 86_400), "2mo ago");
+        assert_eq!(format_age(800 * 86_400), "2y ago");
+    }
+}
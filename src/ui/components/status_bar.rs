@@ -1,35 +1,75 @@
-use crate::app::App;
-use crate::ui::theme::Theme;
+use crate::app::{ActivePanel, App};
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Paragraph};
 
 pub fn render_header(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
     let watching_indicator = if app.files.is_empty() {
         "○ idle"
     } else {
         "● watching"
     };
 
-    let title = format!(
-        "  Git Monitor - [branch: {}] {}",
-        app.branch_name, watching_indicator
-    );
+    let head = &app.head;
+    let branch = match (&head.branch, &head.commit) {
+        (Some(branch), _) => format!("branch: {}", branch),
+        (None, Some(commit)) => format!("detached: {}", commit),
+        (None, None) => "no commits".to_string(),
+    };
+
+    let mut spans = vec![Span::styled(
+        format!("  Git Monitor - [{}", branch),
+        Style::default().fg(theme.text).bold(),
+    )];
+
+    if let Some(upstream) = &head.upstream {
+        spans.push(Span::styled(
+            format!(" → {}", upstream),
+            Style::default().fg(theme.subtext),
+        ));
+        if head.ahead > 0 {
+            spans.push(Span::styled(
+                format!(" ↑{}", head.ahead),
+                Style::default().fg(theme.staged).bold(),
+            ));
+        }
+        if head.behind > 0 {
+            spans.push(Span::styled(
+                format!(" ↓{}", head.behind),
+                Style::default().fg(theme.deleted).bold(),
+            ));
+        }
+    }
+    spans.push(Span::styled("]", Style::default().fg(theme.text).bold()));
+
+    if let Some(operation) = &head.operation {
+        spans.push(Span::styled(
+            format!(" {} ", operation),
+            Style::default()
+                .fg(theme.background)
+                .bg(theme.conflicted)
+                .bold(),
+        ));
+    }
+
+    spans.push(Span::styled(
+        format!(" {}", watching_indicator),
+        Style::default().fg(theme.text).bold(),
+    ));
 
     let version = " [v0.1.0] ";
 
-    let title_len = title.len();
+    let title_len: usize = spans.iter().map(|s| s.content.chars().count()).sum();
     let padding_len = area.width
-        .saturating_sub(title_len as u16 + version.len() as u16) as usize;
+        .saturating_sub(title_len as u16 + version.len() as u16 + 2) as usize;
 
-    let header_line = Line::from(vec![
-        Span::styled(title, Style::default().fg(Theme::TEXT).bold()),
-        Span::styled(" ".repeat(padding_len), Style::default()),
-        Span::styled(version, Style::default().fg(Theme::SUBTEXT)),
-    ]);
+    spans.push(Span::styled(" ".repeat(padding_len), Style::default()));
+    spans.push(Span::styled(version, Style::default().fg(theme.subtext)));
+    let header_line = Line::from(spans);
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Theme::BORDER));
+        .border_style(Style::default().fg(theme.border));
 
     let paragraph = Paragraph::new(header_line).block(block);
 
@@ -37,6 +77,7 @@ pub fn render_header(frame: &mut Frame, app: &App, area: Rect) {
 }
 
 pub fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
     let staged = app.staged_count();
     let modified = app.modified_count();
     let untracked = app.untracked_count();
@@ -47,11 +88,21 @@ pub fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
         staged, modified, untracked, last_update
     );
 
-    let help_text = " q: quit | j/k: navigate | Tab: switch panel | Enter: stage/unstage | r: refresh ";
+    let help_text = match app.active_panel {
+        ActivePanel::FileList => {
+            " q: quit | j/k: navigate | Tab: switch panel | Enter: stage/unstage | c: commit | r: refresh "
+        }
+        ActivePanel::DiffView if app.line_selection.is_some() => {
+            " j/k: extend selection | s: stage/unstage lines | v/Esc: cancel "
+        }
+        ActivePanel::DiffView => {
+            " j/k: scroll | n/p: hunk | s: stage/unstage hunk | v: select lines | D: discard hunk | t: split | w: word diff "
+        }
+    };
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Theme::BORDER));
+        .border_style(Style::default().fg(theme.border));
 
     let available_width = area.width.saturating_sub(2) as usize;
     let help_display = if available_width > status_text.len() + help_text.len() {
@@ -65,9 +116,9 @@ pub fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
         .saturating_sub(help_display.len());
 
     let line = Line::from(vec![
-        Span::styled(status_text, Style::default().fg(Theme::TEXT)),
+        Span::styled(status_text, Style::default().fg(theme.text)),
         Span::raw(" ".repeat(padding)),
-        Span::styled(help_display, Style::default().fg(Theme::SUBTEXT).dim()),
+        Span::styled(help_display, Style::default().fg(theme.subtext).dim()),
     ]);
 
     let paragraph = Paragraph::new(line).block(block);
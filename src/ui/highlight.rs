@@ -0,0 +1,242 @@
+use crate::git::{hunk_emphasis, FileDiff, Granularity, LineKind};
+use crate::ui::theme::Theme;
+use ratatui::style::Color;
+use std::ops::Range;
+use std::path::Path;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme as SyntaxTheme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Foreground-colored segments of one diff line
+pub type Segments = Vec<(Color, String)>;
+
+/// Syntax colors and intra-line emphasis for every line of a diff, indexed
+/// like `FileDiff::hunks`
+#[derive(Debug, Clone, Default)]
+pub struct DiffHighlights {
+    /// `None` when the file's language is unknown
+    syntax: Option<Vec<Vec<Segments>>>,
+    /// Granularity `emphasis` was computed for
+    granularity: Granularity,
+    /// Byte ranges of a line's content, per hunk line
+    emphasis: Vec<Vec<Vec<Range<usize>>>>,
+}
+
+impl DiffHighlights {
+    pub fn line(&self, hunk: usize, line: usize) -> Option<&Segments> {
+        self.syntax.as_ref()?.get(hunk)?.get(line)
+    }
+
+    /// Changed parts of a line compared to its paired line, none while the
+    /// diff for a newly picked granularity is still loading
+    pub fn emphasis(&self, hunk: usize, line: usize, granularity: Granularity) -> &[Range<usize>] {
+        if granularity != self.granularity {
+            return &[];
+        }
+        self.emphasis
+            .get(hunk)
+            .and_then(|lines| lines.get(line))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Language-aware highlighting and word diffs of diff content. Owned by the
+/// git worker, which highlights each diff before handing it to the UI.
+pub struct SyntaxHighlighter {
+    /// Name of the bundled syntect theme for code colors
+    theme: &'static str,
+    /// Loaded on first use, the bundled syntaxes take a moment to parse
+    syntaxes: Option<(SyntaxSet, SyntaxTheme)>,
+}
+
+impl Default for SyntaxHighlighter {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default().syntax,
+            syntaxes: None,
+        }
+    }
+}
+
+impl SyntaxHighlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Switch to another syntect theme for the following diffs
+    pub fn set_theme(&mut self, theme: &'static str) {
+        if theme == self.theme {
+            return;
+        }
+        self.theme = theme;
+        if let Some((_, loaded)) = &mut self.syntaxes {
+            *loaded = load_syntax_theme(theme);
+        }
+    }
+
+    /// Syntax colors and `granularity` emphasis for every line of `diff`
+    pub fn highlight(&mut self, diff: &FileDiff, granularity: Granularity) -> DiffHighlights {
+        let name = self.theme;
+        let (syntaxes, theme) = self
+            .syntaxes
+            .get_or_insert_with(|| (SyntaxSet::load_defaults_newlines(), load_syntax_theme(name)));
+
+        let emphasis = match granularity {
+            Granularity::Line => Vec::new(),
+            _ => diff
+                .hunks
+                .iter()
+                .map(|hunk| hunk_emphasis(hunk, granularity))
+                .collect(),
+        };
+        DiffHighlights {
+            syntax: detect_syntax(syntaxes, &diff.path)
+                .map(|syntax| highlight_diff(diff, syntax, syntaxes, theme)),
+            granularity,
+            emphasis,
+        }
+    }
+}
+
+fn load_syntax_theme(name: &str) -> SyntaxTheme {
+    ThemeSet::load_defaults()
+        .themes
+        .remove(name)
+        .unwrap_or_default()
+}
+
+/// Pick the syntax from the file name, like `FileIcon::from_filename` does:
+/// by extension first, then by the whole name (Makefile, Dockerfile, ...)
+fn detect_syntax<'a>(syntaxes: &'a SyntaxSet, path: &str) -> Option<&'a SyntaxReference> {
+    let path = Path::new(path);
+    let by_extension = path
+        .extension()
+        .and_then(|ext| syntaxes.find_syntax_by_extension(&ext.to_string_lossy()));
+    let by_name = || {
+        path.file_name()
+            .and_then(|name| syntaxes.find_syntax_by_extension(&name.to_string_lossy()))
+    };
+    by_extension
+        .or_else(by_name)
+        .filter(|syntax| syntax.name != "Plain Text")
+}
+
+/// Highlight each hunk as two streams, the old side (context and deletions)
+/// and the new side (context and additions), so multi-line constructs
+/// carry over between lines of the same version
+fn highlight_diff(
+    diff: &FileDiff,
+    syntax: &SyntaxReference,
+    syntaxes: &SyntaxSet,
+    theme: &SyntaxTheme,
+) -> Vec<Vec<Segments>> {
+    diff.hunks
+        .iter()
+        .map(|hunk| {
+            let mut old_side = HighlightLines::new(syntax, theme);
+            let mut new_side = HighlightLines::new(syntax, theme);
+            hunk.lines
+                .iter()
+                .map(|line| {
+                    let highlight = |side: &mut HighlightLines| {
+                        let text = format!("{}\n", line.content);
+                        side.highlight_line(&text, syntaxes)
+                            .map(|ranges| {
+                                ranges
+                                    .into_iter()
+                                    .map(|(style, piece)| {
+                                        let fg = style.foreground;
+                                        let piece = piece.trim_end_matches('\n').to_string();
+                                        (Color::Rgb(fg.r, fg.g, fg.b), piece)
+                                    })
+                                    .filter(|(_, piece)| !piece.is_empty())
+                                    .collect()
+                            })
+                            .unwrap_or_default()
+                    };
+                    match line.kind {
+                        LineKind::Deletion => highlight(&mut old_side),
+                        LineKind::Addition => highlight(&mut new_side),
+                        LineKind::Context => {
+                            highlight(&mut old_side);
+                            highlight(&mut new_side)
+                        }
+                        LineKind::NoNewline => Vec::new(),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{DiffHunk, DiffLine, DiffSource};
+
+    fn rust_diff(path: &str) -> FileDiff {
+        let line = |kind, content: &str| DiffLine {
+            kind,
+            content: content.to_string(),
+            old_lineno: None,
+            new_lineno: None,
+            crlf: false,
+        };
+        FileDiff {
+            path: path.to_string(),
+            source: DiffSource::Unstaged,
+            old_path: None,
+            similarity: None,
+            hunks: vec![DiffHunk {
+                header: "@@ -1,2 +1,2 @@".to_string(),
+                old_start: 1,
+                old_lines: 2,
+                new_start: 1,
+                new_lines: 2,
+                lines: vec![
+                    line(LineKind::Context, "/* start of"),
+                    line(LineKind::Deletion, "   comment */ fn old() {}"),
+                    line(LineKind::Addition, "fn new() {}"),
+                ],
+            }],
+            notice: None,
+        }
+    }
+
+    #[test]
+    fn test_highlights_keep_text_and_split_sides() {
+        let diff = rust_diff("src/lib.rs");
+        let highlights = SyntaxHighlighter::new().highlight(&diff, Granularity::Word);
+
+        for (i, line) in diff.hunks[0].lines.iter().enumerate() {
+            let text: String = highlights
+                .line(0, i)
+                .unwrap()
+                .iter()
+                .map(|(_, piece)| piece.as_str())
+                .collect();
+            assert_eq!(text, line.content);
+        }
+
+        // The addition is on the new side, where the comment was never
+        // closed, so it is colored like the comment on the context line
+        let comment_color = highlights.line(0, 0).unwrap()[0].0;
+        assert_eq!(highlights.line(0, 2).unwrap()[0].0, comment_color);
+        // The deletion closes the comment, so its code is colored differently
+        let deletion = highlights.line(0, 1).unwrap();
+        assert_ne!(deletion.last().unwrap().0, comment_color);
+    }
+
+    #[test]
+    fn test_unknown_language_is_not_highlighted() {
+        let highlights =
+            SyntaxHighlighter::new().highlight(&rust_diff("notes.unknownext"), Granularity::Word);
+        assert!(highlights.line(0, 0).is_none());
+        // Word diffs do not depend on the language
+        let emphasis = highlights.emphasis(0, 2, Granularity::Word);
+        assert_eq!(emphasis.len(), 1);
+        assert_eq!(emphasis[0], 3..6);
+        // Other granularities are not computed
+        assert!(highlights.emphasis(0, 2, Granularity::Char).is_empty());
+    }
+}
@@ -13,7 +13,7 @@ impl FileIcon {
         let dev_icon = DevIcon::from(filename);
 
         // Convert the hex color to ratatui Color
-        let color = parse_hex_color(&dev_icon.color);
+        let color = parse_hex_color(dev_icon.color);
 
         FileIcon {
             icon: dev_icon.icon.to_string(),
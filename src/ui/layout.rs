@@ -1,5 +1,9 @@
 use crate::app::App;
-use crate::ui::components::{render_diff_view, render_file_list, render_header, render_status_bar};
+use crate::ui::components::{
+    render_commit_editor, render_confirm_dialog, render_conflict_view, render_diff_view,
+    render_file_list, render_header, render_message_log, render_stash_panel, render_status_bar,
+    render_toasts,
+};
 use ratatui::prelude::*;
 
 pub fn render(frame: &mut Frame, app: &mut App) {
@@ -22,8 +26,8 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     let content_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(50),  // File list (wider for full filenames)
-            Constraint::Percentage(50),  // Diff preview
+            Constraint::Percentage(app.file_list_percent), // File list
+            Constraint::Percentage(100 - app.file_list_percent), // Diff preview
         ])
         .margin(1)
         .split(chunks[1]);
@@ -53,4 +57,15 @@ pub fn render(frame: &mut Frame, app: &mut App) {
 
     // Render status bar
     render_status_bar(frame, app, chunks[2]);
+
+    // Render overlays on top of everything else
+    render_conflict_view(frame, app, area);
+    render_stash_panel(frame, app, area);
+    render_commit_editor(frame, app, area);
+    render_confirm_dialog(frame, app, area);
+    render_message_log(frame, app, area);
+    render_toasts(frame, app, area, chunks[2].y);
+
+    // Approximate the 24-bit colors on terminals that lack them
+    app.color_depth.apply(frame.buffer_mut());
 }
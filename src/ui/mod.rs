@@ -1,7 +1,8 @@
 mod components;
 mod effects;
+pub mod highlight;
 pub mod icons;
 mod layout;
-mod theme;
+pub mod theme;
 
 pub use layout::render;
@@ -1,37 +1,622 @@
-#![allow(dead_code)]
-
+use ratatui::buffer::Buffer;
 use ratatui::style::Color;
+use serde::Deserialize;
 
-/// Catppuccin-inspired color theme
-pub struct Theme;
-
-impl Theme {
+/// Colors of the interface. Built-in palettes are picked by name, custom
+/// ones start from a built-in and replace some of its colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
     // Status colors
-    pub const STAGED: Color = Color::Rgb(166, 227, 161);     // Green #A6E3A1
-    pub const MODIFIED: Color = Color::Rgb(249, 226, 175);   // Yellow #F9E2AF
-    pub const ADDED: Color = Color::Rgb(137, 180, 250);      // Blue #89B4FA
-    pub const DELETED: Color = Color::Rgb(243, 139, 168);    // Red #F38BA8
-    pub const UNTRACKED: Color = Color::Rgb(108, 112, 134);  // Gray #6C7086
-    pub const RENAMED: Color = Color::Rgb(203, 166, 247);    // Purple #CBA6F7
-    pub const CONFLICTED: Color = Color::Rgb(245, 194, 231); // Pink #F5C2E7
+    pub staged: Color,
+    pub modified: Color,
+    pub added: Color,
+    pub deleted: Color,
+    pub untracked: Color,
+    pub renamed: Color,
+    pub conflicted: Color,
 
     // UI colors
-    pub const BACKGROUND: Color = Color::Rgb(30, 30, 46);    // Base #1E1E2E
-    pub const SURFACE: Color = Color::Rgb(49, 50, 68);       // Surface0 #313244
-    pub const OVERLAY: Color = Color::Rgb(69, 71, 90);       // Surface1 #45475A
-    pub const TEXT: Color = Color::Rgb(205, 214, 244);       // Text #CDD6F4
-    pub const SUBTEXT: Color = Color::Rgb(166, 173, 200);    // Subtext0 #A6ADC8
-    pub const BORDER: Color = Color::Rgb(88, 91, 112);       // Surface2 #585B70
-    pub const ACCENT: Color = Color::Rgb(137, 180, 250);     // Blue #89B4FA
-    pub const HIGHLIGHT: Color = Color::Rgb(180, 190, 254);  // Lavender #B4BEFE
+    pub background: Color,
+    pub surface: Color,
+    pub overlay: Color,
+    pub text: Color,
+    pub subtext: Color,
+    pub border: Color,
+    pub accent: Color,
+    pub highlight: Color,
 
     // Diff colors
-    pub const DIFF_ADD: Color = Color::Rgb(166, 227, 161);   // Green
-    pub const DIFF_DEL: Color = Color::Rgb(243, 139, 168);   // Red
-    pub const DIFF_HUNK: Color = Color::Rgb(137, 180, 250);  // Blue
+    pub diff_add: Color,
+    pub diff_del: Color,
+    pub diff_hunk: Color,
+    /// Green tint under highlighted code
+    pub diff_add_bg: Color,
+    /// Red tint under highlighted code
+    pub diff_del_bg: Color,
+    /// Changed words of an added line
+    pub diff_add_emph: Color,
+    /// Changed words of a deleted line
+    pub diff_del_emph: Color,
 
     // Animation colors
-    pub const FLASH_BRIGHT: Color = Color::Rgb(255, 230, 150);  // Warm yellow
-    pub const FLASH_DIM: Color = Color::Rgb(180, 160, 100);     // Dim yellow
-    pub const FLASH_BG: Color = Color::Rgb(80, 70, 50);         // Warm background for flash
+    pub flash_bright: Color,
+    pub flash_dim: Color,
+    pub flash_bg: Color,
+
+    /// Bundled syntect theme used for code colors
+    pub syntax: &'static str,
+}
+
+/// The colors a built-in theme is derived from, named after Catppuccin's
+struct Palette {
+    base: u32,
+    surface0: u32,
+    surface1: u32,
+    surface2: u32,
+    overlay0: u32,
+    subtext0: u32,
+    text: u32,
+    green: u32,
+    yellow: u32,
+    blue: u32,
+    red: u32,
+    mauve: u32,
+    pink: u32,
+    lavender: u32,
+    syntax: &'static str,
+}
+
+const fn rgb(hex: u32) -> Color {
+    Color::Rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+/// `percent` of `tint` over `base`
+const fn mix(base: u32, tint: u32, percent: u32) -> Color {
+    const fn channel(base: u32, tint: u32, shift: u32, percent: u32) -> u8 {
+        let base = (base >> shift) & 0xff;
+        let tint = (tint >> shift) & 0xff;
+        ((base * (100 - percent) + tint * percent) / 100) as u8
+    }
+    Color::Rgb(
+        channel(base, tint, 16, percent),
+        channel(base, tint, 8, percent),
+        channel(base, tint, 0, percent),
+    )
+}
+
+impl Palette {
+    const fn theme(&self) -> Theme {
+        Theme {
+            staged: rgb(self.green),
+            modified: rgb(self.yellow),
+            added: rgb(self.blue),
+            deleted: rgb(self.red),
+            untracked: rgb(self.overlay0),
+            renamed: rgb(self.mauve),
+            conflicted: rgb(self.pink),
+
+            background: rgb(self.base),
+            surface: rgb(self.surface0),
+            overlay: rgb(self.surface1),
+            text: rgb(self.text),
+            subtext: rgb(self.subtext0),
+            border: rgb(self.surface2),
+            accent: rgb(self.blue),
+            highlight: rgb(self.lavender),
+
+            diff_add: rgb(self.green),
+            diff_del: rgb(self.red),
+            diff_hunk: rgb(self.blue),
+            diff_add_bg: mix(self.base, self.green, 15),
+            diff_del_bg: mix(self.base, self.red, 15),
+            diff_add_emph: mix(self.base, self.green, 35),
+            diff_del_emph: mix(self.base, self.red, 35),
+
+            flash_bright: rgb(self.yellow),
+            flash_dim: mix(self.base, self.yellow, 65),
+            flash_bg: mix(self.base, self.yellow, 25),
+
+            syntax: self.syntax,
+        }
+    }
+}
+
+const LATTE: Palette = Palette {
+    base: 0xEFF1F5,
+    surface0: 0xCCD0DA,
+    surface1: 0xBCC0CC,
+    surface2: 0xACB0BE,
+    overlay0: 0x9CA0B0,
+    subtext0: 0x6C6F85,
+    text: 0x4C4F69,
+    green: 0x40A02B,
+    yellow: 0xDF8E1D,
+    blue: 0x1E66F5,
+    red: 0xD20F39,
+    mauve: 0x8839EF,
+    pink: 0xEA76CB,
+    lavender: 0x7287FD,
+    syntax: "InspiredGitHub",
+};
+
+const FRAPPE: Palette = Palette {
+    base: 0x303446,
+    surface0: 0x414559,
+    surface1: 0x51576D,
+    surface2: 0x626880,
+    overlay0: 0x737994,
+    subtext0: 0xA5ADCE,
+    text: 0xC6D0F5,
+    green: 0xA6D189,
+    yellow: 0xE5C890,
+    blue: 0x8CAAEE,
+    red: 0xE78284,
+    mauve: 0xCA9EE6,
+    pink: 0xF4B8E4,
+    lavender: 0xBABBF1,
+    syntax: "base16-ocean.dark",
+};
+
+const MACCHIATO: Palette = Palette {
+    base: 0x24273A,
+    surface0: 0x363A4F,
+    surface1: 0x494D64,
+    surface2: 0x5B6078,
+    overlay0: 0x6E738D,
+    subtext0: 0xA5ADCB,
+    text: 0xCAD3F5,
+    green: 0xA6DA95,
+    yellow: 0xEED49F,
+    blue: 0x8AADF4,
+    red: 0xED8796,
+    mauve: 0xC6A0F6,
+    pink: 0xF5BDE6,
+    lavender: 0xB7BDF8,
+    syntax: "base16-ocean.dark",
+};
+
+const MOCHA: Palette = Palette {
+    base: 0x1E1E2E,
+    surface0: 0x313244,
+    surface1: 0x45475A,
+    surface2: 0x585B70,
+    overlay0: 0x6C7086,
+    subtext0: 0xA6ADC8,
+    text: 0xCDD6F4,
+    green: 0xA6E3A1,
+    yellow: 0xF9E2AF,
+    blue: 0x89B4FA,
+    red: 0xF38BA8,
+    mauve: 0xCBA6F7,
+    pink: 0xF5C2E7,
+    lavender: 0xB4BEFE,
+    syntax: "base16-ocean.dark",
+};
+
+const SOLARIZED_DARK: Palette = Palette {
+    base: 0x002B36,     // base03
+    surface0: 0x073642, // base02
+    surface1: 0x0E4A57,
+    surface2: 0x586E75, // base01
+    overlay0: 0x586E75,
+    subtext0: 0x93A1A1, // base1
+    text: 0x839496,     // base0
+    green: 0x859900,
+    yellow: 0xB58900,
+    blue: 0x268BD2,
+    red: 0xDC322F,
+    mauve: 0x6C71C4,    // violet
+    pink: 0xD33682,     // magenta
+    lavender: 0x2AA198, // cyan
+    syntax: "Solarized (dark)",
+};
+
+const SOLARIZED_LIGHT: Palette = Palette {
+    base: 0xFDF6E3,     // base3
+    surface0: 0xEEE8D5, // base2
+    surface1: 0xE4DDC8,
+    surface2: 0x93A1A1, // base1
+    overlay0: 0x93A1A1,
+    subtext0: 0x586E75, // base01
+    text: 0x657B83,     // base00
+    green: 0x859900,
+    yellow: 0xB58900,
+    blue: 0x268BD2,
+    red: 0xDC322F,
+    mauve: 0x6C71C4,
+    pink: 0xD33682,
+    lavender: 0x2AA198,
+    syntax: "Solarized (light)",
+};
+
+const HIGH_CONTRAST: Palette = Palette {
+    base: 0x000000,
+    surface0: 0x1C1C1C,
+    surface1: 0x3A3A3A,
+    surface2: 0xFFFFFF,
+    overlay0: 0xBCBCBC,
+    subtext0: 0xD0D0D0,
+    text: 0xFFFFFF,
+    green: 0x5FFF5F,
+    yellow: 0xFFFF00,
+    blue: 0x5FAFFF,
+    red: 0xFF5F5F,
+    mauve: 0xD787FF,
+    pink: 0xFF87D7,
+    lavender: 0xFFFF87,
+    syntax: "base16-eighties.dark",
+};
+
+impl Theme {
+    /// Names of the built-in themes
+    pub const BUILTIN: [&'static str; 7] = [
+        "catppuccin-latte",
+        "catppuccin-frappe",
+        "catppuccin-macchiato",
+        "catppuccin-mocha",
+        "solarized-dark",
+        "solarized-light",
+        "high-contrast",
+    ];
+
+    /// The syntect themes bundled with the binary, for `syntax` of custom
+    /// themes
+    pub const SYNTAX_THEMES: [&'static str; 7] = [
+        "base16-ocean.dark",
+        "base16-eighties.dark",
+        "base16-mocha.dark",
+        "base16-ocean.light",
+        "InspiredGitHub",
+        "Solarized (dark)",
+        "Solarized (light)",
+    ];
+
+    /// A built-in theme by name
+    pub fn builtin(name: &str) -> Option<Theme> {
+        let theme = match name {
+            "catppuccin-latte" => LATTE.theme(),
+            "catppuccin-frappe" => FRAPPE.theme(),
+            "catppuccin-macchiato" => MACCHIATO.theme(),
+            "catppuccin-mocha" => Theme::default(),
+            "solarized-dark" => SOLARIZED_DARK.theme(),
+            "solarized-light" => SOLARIZED_LIGHT.theme(),
+            "high-contrast" => Theme {
+                // Full tints, the text on them stays readable
+                diff_add_bg: rgb(0x003A00),
+                diff_del_bg: rgb(0x4A0000),
+                diff_add_emph: rgb(0x007000),
+                diff_del_emph: rgb(0x8A0000),
+                ..HIGH_CONTRAST.theme()
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    /// The theme `auto` stands for: Catppuccin Latte on light backgrounds,
+    /// Mocha on dark ones
+    pub fn auto(background: Background) -> Theme {
+        match background {
+            Background::Light => LATTE.theme(),
+            Background::Dark => Theme::default(),
+        }
+    }
+
+    /// Replace one color, or the syntax theme, as set by `key = "value"` in
+    /// a custom theme. Colors are `#RRGGBB`, a name like `red` or an index
+    /// of the 256-color palette.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if key == "syntax" {
+            self.syntax = Theme::SYNTAX_THEMES
+                .into_iter()
+                .find(|name| *name == value)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown syntax theme {:?}, expected one of: {}",
+                        value,
+                        Theme::SYNTAX_THEMES.join(", ")
+                    )
+                })?;
+            return Ok(());
+        }
+        let color = self
+            .color_mut(key)
+            .ok_or_else(|| format!("unknown color {:?}", key))?;
+        *color = value.parse().map_err(|_| {
+            format!(
+                "invalid color {:?}, expected #RRGGBB or a color name",
+                value
+            )
+        })?;
+        Ok(())
+    }
+
+    fn color_mut(&mut self, key: &str) -> Option<&mut Color> {
+        let color = match key {
+            "staged" => &mut self.staged,
+            "modified" => &mut self.modified,
+            "added" => &mut self.added,
+            "deleted" => &mut self.deleted,
+            "untracked" => &mut self.untracked,
+            "renamed" => &mut self.renamed,
+            "conflicted" => &mut self.conflicted,
+            "background" => &mut self.background,
+            "surface" => &mut self.surface,
+            "overlay" => &mut self.overlay,
+            "text" => &mut self.text,
+            "subtext" => &mut self.subtext,
+            "border" => &mut self.border,
+            "accent" => &mut self.accent,
+            "highlight" => &mut self.highlight,
+            "diff_add" => &mut self.diff_add,
+            "diff_del" => &mut self.diff_del,
+            "diff_hunk" => &mut self.diff_hunk,
+            "diff_add_bg" => &mut self.diff_add_bg,
+            "diff_del_bg" => &mut self.diff_del_bg,
+            "diff_add_emph" => &mut self.diff_add_emph,
+            "diff_del_emph" => &mut self.diff_del_emph,
+            "flash_bright" => &mut self.flash_bright,
+            "flash_dim" => &mut self.flash_dim,
+            "flash_bg" => &mut self.flash_bg,
+            _ => return None,
+        };
+        Some(color)
+    }
+}
+
+/// Catppuccin Mocha, with the original hand-picked tints and flash colors
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            diff_add_bg: Color::Rgb(43, 59, 49),
+            diff_del_bg: Color::Rgb(61, 41, 54),
+            diff_add_emph: Color::Rgb(64, 99, 72),
+            diff_del_emph: Color::Rgb(104, 56, 78),
+            flash_bright: Color::Rgb(255, 230, 150), // Warm yellow
+            flash_dim: Color::Rgb(180, 160, 100),    // Dim yellow
+            flash_bg: Color::Rgb(80, 70, 50),        // Warm background for flash
+            ..MOCHA.theme()
+        }
+    }
+}
+
+/// Brightness of the terminal background, decides what `auto` stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Background {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Background {
+    /// By relative luminance of the background color
+    pub fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        let luminance = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+        if luminance > 127.5 {
+            Background::Light
+        } else {
+            Background::Dark
+        }
+    }
+
+    /// From `COLORFGBG` ("15;0" is light on dark), set by rxvt, Konsole and
+    /// others, for terminals that do not answer the color query
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var("COLORFGBG").ok()?;
+        let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+        Some(match bg {
+            7 | 9..=15 => Background::Light,
+            _ => Background::Dark,
+        })
+    }
+}
+
+/// Colors the terminal can show. Themes are in 24-bit color, rendered
+/// frames are converted to the nearest palette colors on other terminals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorDepth {
+    /// Detect from `COLORTERM` and `TERM`
+    #[default]
+    Auto,
+    TrueColor,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "16")]
+    Ansi16,
+}
+
+/// The 16 ANSI colors as xterm shows them by default
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Levels of each channel in the 6x6x6 cube of the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorDepth {
+    /// Resolve `Auto` from the environment
+    pub fn detect(self) -> Self {
+        if self != ColorDepth::Auto {
+            return self;
+        }
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor"
+            || colorterm == "24bit"
+            || std::env::var_os("WT_SESSION").is_some()
+        {
+            return ColorDepth::TrueColor;
+        }
+        let term = std::env::var("TERM").unwrap_or_default();
+        if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else if term.is_empty() || term.contains("direct") {
+            ColorDepth::TrueColor
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    /// The nearest color this depth can show
+    pub fn convert(self, color: Color) -> Color {
+        let rgb = match color {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(index) if index >= 16 && self == ColorDepth::Ansi16 => {
+                indexed_rgb(index)
+            }
+            _ => return color,
+        };
+        match self {
+            ColorDepth::Auto | ColorDepth::TrueColor => color,
+            ColorDepth::Ansi256 => Color::Indexed(nearest_256(rgb)),
+            ColorDepth::Ansi16 => nearest_16(rgb),
+        }
+    }
+
+    /// Convert every color of a rendered frame, so syntax colors, file icon
+    /// colors and animations are covered along with the theme
+    pub fn apply(self, buffer: &mut Buffer) {
+        if matches!(self, ColorDepth::Auto | ColorDepth::TrueColor) {
+            return;
+        }
+        for cell in buffer.content.iter_mut() {
+            cell.fg = self.convert(cell.fg);
+            cell.bg = self.convert(cell.bg);
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).unsigned_abs().pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> Color {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+/// Index of the closest cube color or gray in the 256-color palette
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - value as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = 16 + 36 * r + 6 * g + b;
+
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + ((average.saturating_sub(3)) / 10).min(23) as u8;
+
+    if distance(rgb, indexed_rgb(gray)) < distance(rgb, indexed_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// The color of a 256-color palette entry from 16 on
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_16[index as usize].1,
+        16..=231 => {
+            let i = index - 16;
+            (
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[(i / 6 % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_themes() {
+        for name in Theme::BUILTIN {
+            let theme = Theme::builtin(name).unwrap();
+            assert!(Theme::SYNTAX_THEMES.contains(&theme.syntax), "{}", name);
+        }
+        assert_eq!(Theme::builtin("catppuccin-mocha"), Some(Theme::default()));
+        assert_eq!(Theme::auto(Background::Dark), Theme::default());
+        assert_eq!(
+            Theme::auto(Background::Light).background,
+            Color::Rgb(239, 241, 245)
+        );
+        assert_eq!(Theme::builtin("nope"), None);
+    }
+
+    #[test]
+    fn test_custom_colors() {
+        let mut theme = Theme::default();
+        theme.set("text", "#102030").unwrap();
+        theme.set("accent", "red").unwrap();
+        theme.set("syntax", "InspiredGitHub").unwrap();
+        assert_eq!(theme.text, Color::Rgb(16, 32, 48));
+        assert_eq!(theme.accent, Color::Red);
+        assert_eq!(theme.syntax, "InspiredGitHub");
+
+        assert!(theme.set("txt", "#102030").is_err());
+        assert!(theme.set("text", "#10203").is_err());
+        assert!(theme.set("syntax", "Monokai").is_err());
+    }
+
+    #[test]
+    fn test_color_approximation() {
+        let mocha_base = Color::Rgb(30, 30, 46);
+        assert_eq!(ColorDepth::TrueColor.convert(mocha_base), mocha_base);
+        assert_eq!(
+            ColorDepth::Ansi256.convert(Color::Rgb(255, 0, 0)),
+            Color::Indexed(196)
+        );
+        assert_eq!(
+            ColorDepth::Ansi256.convert(Color::Rgb(128, 128, 128)),
+            Color::Indexed(244)
+        );
+        assert_eq!(ColorDepth::Ansi16.convert(mocha_base), Color::Black);
+        assert_eq!(
+            ColorDepth::Ansi16.convert(Color::Rgb(239, 241, 245)),
+            Color::Gray
+        );
+        assert_eq!(
+            ColorDepth::Ansi16.convert(Color::Rgb(250, 250, 250)),
+            Color::White
+        );
+        assert_eq!(
+            ColorDepth::Ansi16.convert(Color::Indexed(196)),
+            Color::LightRed
+        );
+        assert_eq!(ColorDepth::Ansi16.convert(Color::Cyan), Color::Cyan);
+    }
+
+    #[test]
+    fn test_background_brightness() {
+        assert_eq!(Background::from_rgb(30, 30, 46), Background::Dark);
+        assert_eq!(Background::from_rgb(253, 246, 227), Background::Light);
+    }
 }
@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+
+/// Discards kept for undo; older ones are only left as dangling blobs
+const MAX_ENTRIES: usize = 20;
+
+/// A working tree file around a discard. `None` content means the file did
+/// not exist.
+#[derive(Debug, Clone)]
+pub struct FileSnapshot {
+    pub path: String,
+    pub before: Option<Vec<u8>>,
+    pub after: Option<Vec<u8>>,
+    /// Id of the dangling blob holding `before`
+    pub blob: Option<String>,
+}
+
+/// One discard: what it was and the files it touched
+#[derive(Debug, Clone)]
+pub struct UndoEntry {
+    pub description: String,
+    pub files: Vec<FileSnapshot>,
+}
+
+/// In-session stack of discards, newest last
+#[derive(Debug, Default)]
+pub struct UndoBuffer {
+    entries: VecDeque<UndoEntry>,
+}
+
+impl UndoBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, entry: UndoEntry) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// The newest entry, left in place
+    pub fn last(&self) -> Option<&UndoEntry> {
+        self.entries.back()
+    }
+
+    pub fn pop(&mut self) -> Option<UndoEntry> {
+        self.entries.pop_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(description: &str) -> UndoEntry {
+        UndoEntry {
+            description: description.to_string(),
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_pop_returns_newest_first() {
+        let mut buffer = UndoBuffer::new();
+        buffer.push(entry("first"));
+        buffer.push(entry("second"));
+        assert_eq!(buffer.last().unwrap().description, "second");
+        assert_eq!(buffer.pop().unwrap().description, "second");
+        assert_eq!(buffer.pop().unwrap().description, "first");
+        assert!(buffer.pop().is_none());
+    }
+
+    #[test]
+    fn test_oldest_entries_are_dropped() {
+        let mut buffer = UndoBuffer::new();
+        for i in 0..MAX_ENTRIES + 5 {
+            buffer.push(entry(&i.to_string()));
+        }
+        let kept: Vec<String> = std::iter::from_fn(|| buffer.pop())
+            .map(|e| e.description)
+            .collect();
+        assert_eq!(kept.len(), MAX_ENTRIES);
+        assert_eq!(kept[0], (MAX_ENTRIES + 4).to_string());
+        assert_eq!(kept[MAX_ENTRIES - 1], "5");
+    }
+}